@@ -28,5 +28,5 @@ fn main() {
         origin[next_point]
     }
     let image_after = image.post_effect(post_effect);
-    image_after.into_file(&Path::new(save_path));
+    image_after.into_file(&Path::new(save_path)).unwrap();
 }
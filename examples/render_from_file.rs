@@ -7,6 +7,9 @@ fn main() {
     pretty_env_logger::init();
     let args: Vec<_> = args().collect();
     let file_path = &args[1];
-    render_from_file(&Path::new(file_path));
+    if let Err(e) = render_from_file(&Path::new(file_path)) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
     //PROFILER.lock().unwrap().stop().unwrap();
 }
@@ -0,0 +1,257 @@
+use crate::*;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{self, Read, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aov {
+    Depth,
+    Normal,
+    Albedo,
+    Position,
+    UV,
+    PrimitiveId,
+    MaterialId,
+}
+
+impl Aov {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "depth" => Some(Self::Depth),
+            "normal" => Some(Self::Normal),
+            "albedo" => Some(Self::Albedo),
+            "position" => Some(Self::Position),
+            "uv" => Some(Self::UV),
+            "primitiveid" => Some(Self::PrimitiveId),
+            "materialid" => Some(Self::MaterialId),
+            _ => None,
+        }
+    }
+    pub fn channel_names(self) -> &'static [&'static str] {
+        match self {
+            Self::Depth => &["Z"],
+            Self::Normal => &["N.X", "N.Y", "N.Z"],
+            Self::Albedo => &["albedo.R", "albedo.G", "albedo.B"],
+            Self::Position => &["P.X", "P.Y", "P.Z"],
+            Self::UV => &["uv.U", "uv.V"],
+            Self::PrimitiveId => &["primitiveId"],
+            Self::MaterialId => &["materialId"],
+        }
+    }
+    // offset into the filtered values of AovPixel, ids are not filtered
+    fn offset(self) -> Option<usize> {
+        match self {
+            Self::Depth => Some(0),
+            Self::Normal => Some(1),
+            Self::Albedo => Some(4),
+            Self::Position => Some(7),
+            Self::UV => Some(10),
+            Self::PrimitiveId | Self::MaterialId => None,
+        }
+    }
+}
+
+const AOV_FILTERED_LEN: usize = 12;
+
+#[derive(Debug, Clone, Copy)]
+pub struct AovSample {
+    pub depth: Float,
+    pub normal: Vector3f,
+    pub albedo: Spectrum,
+    pub position: Vector3f,
+    pub uv: Point2f,
+    pub primitive_id: Option<usize>,
+    pub material_id: Option<usize>,
+}
+
+impl Default for AovSample {
+    fn default() -> Self {
+        Self {
+            depth: 0.,
+            normal: Vector3f::new(0., 0., 0.),
+            albedo: Spectrum::new(0.),
+            position: Vector3f::new(0., 0., 0.),
+            uv: Point2f::new(0., 0.),
+            primitive_id: None,
+            material_id: None,
+        }
+    }
+}
+
+impl AovSample {
+    pub fn first_hit(ray: &Ray, scene: &Scene) -> Self {
+        let intersect = match scene.intersect(ray) {
+            Some(intersect) => intersect,
+            None => return Self::default(),
+        };
+        let shape_point = intersect.get_shape_point();
+        let n = shape_point.n;
+        let albedo = intersect
+            .compute_scattering_functions()
+            .map_or(Spectrum::new(0.), |bsdf| {
+                estimate_albedo(&bsdf, &-ray.d.normalize(), &n)
+            });
+        let primitive = intersect.get_primitive();
+        Self {
+            depth: (shape_point.p - ray.o).magnitude(),
+            normal: Vector3f::new(n.x, n.y, n.z),
+            albedo,
+            position: shape_point.p.coords,
+            uv: shape_point.uv,
+            primitive_id: Some(primitive.id()),
+            material_id: primitive
+                .get_source()
+                .get_material()
+                .and_then(|material| scene.material_id(&material)),
+        }
+    }
+    fn filtered(&self) -> [Float; AOV_FILTERED_LEN] {
+        let albedo: [Float; 3] = self.albedo.into();
+        [
+            self.depth,
+            self.normal.x,
+            self.normal.y,
+            self.normal.z,
+            albedo[0],
+            albedo[1],
+            albedo[2],
+            self.position.x,
+            self.position.y,
+            self.position.z,
+            self.uv.x,
+            self.uv.y,
+        ]
+    }
+}
+
+fn estimate_albedo(bsdf: &BSDF, wo: &Vector3f, n: &Normal3f) -> Spectrum {
+    let mut albedo = Spectrum::new(0.);
+    if !bsdf.is_all_delta() {
+        let count = 4;
+        for i in 0..count {
+            for j in 0..count {
+                let u = Point2f::new(
+                    (i as Float + 0.5) / count as Float,
+                    (j as Float + 0.5) / count as Float,
+                );
+                if let (wi, Some(f), pdf) = bsdf.sample_no_delta_f(wo, &u) {
+                    if pdf != 0. {
+                        albedo += f * n.dot(&wi).abs() / pdf;
+                    }
+                }
+            }
+        }
+        albedo /= (count * count) as Float;
+    }
+    for (wi, s) in bsdf.sample_all_delta_f(wo) {
+        albedo += s * n.dot(&wi).abs();
+    }
+    albedo
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AovPixel {
+    sum: [Float; AOV_FILTERED_LEN],
+    weight: Float,
+    id_weight: Float,
+    primitive_id: Option<usize>,
+    material_id: Option<usize>,
+}
+
+impl AovPixel {
+    pub fn add_sample(&mut self, sample: &AovSample, weight: Float) {
+        for (sum, value) in self.sum.iter_mut().zip(sample.filtered().iter()) {
+            *sum += value * weight;
+        }
+        self.weight += weight;
+        if weight > self.id_weight {
+            self.id_weight = weight;
+            self.primitive_id = sample.primitive_id;
+            self.material_id = sample.material_id;
+        }
+    }
+    pub fn merge(&mut self, rhs: &Self) {
+        for (sum, value) in self.sum.iter_mut().zip(rhs.sum.iter()) {
+            *sum += value;
+        }
+        self.weight += rhs.weight;
+        if rhs.id_weight > self.id_weight {
+            self.id_weight = rhs.id_weight;
+            self.primitive_id = rhs.primitive_id;
+            self.material_id = rhs.material_id;
+        }
+    }
+    pub fn write_to(&self, w: &mut dyn Write) -> io::Result<()> {
+        for sum in self.sum.iter() {
+            w.write_f32::<LittleEndian>(*sum)?;
+        }
+        w.write_f32::<LittleEndian>(self.weight)?;
+        w.write_f32::<LittleEndian>(self.id_weight)?;
+        for id in [self.primitive_id, self.material_id].iter() {
+            w.write_i64::<LittleEndian>(id.map_or(-1, |id| id as i64))?;
+        }
+        Ok(())
+    }
+    pub fn read_from(r: &mut dyn Read) -> io::Result<Self> {
+        let mut sum = [0.; AOV_FILTERED_LEN];
+        for s in &mut sum {
+            *s = r.read_f32::<LittleEndian>()?;
+        }
+        let weight = r.read_f32::<LittleEndian>()?;
+        let id_weight = r.read_f32::<LittleEndian>()?;
+        let mut read_id = || -> io::Result<Option<usize>> {
+            let id = r.read_i64::<LittleEndian>()?;
+            Ok(if id < 0 { None } else { Some(id as usize) })
+        };
+        Ok(Self {
+            sum,
+            weight,
+            id_weight,
+            primitive_id: read_id()?,
+            material_id: read_id()?,
+        })
+    }
+    pub fn value(&self, aov: Aov) -> Vec<Float> {
+        let id = |id: Option<usize>| vec![id.map_or(-1., |id| id as Float)];
+        match aov.offset() {
+            Some(offset) => {
+                let len = aov.channel_names().len();
+                self.sum[offset..offset + len]
+                    .iter()
+                    .map(|sum| if self.weight == 0. { 0. } else { sum / self.weight })
+                    .collect()
+            }
+            None if aov == Aov::PrimitiveId => id(self.primitive_id),
+            None => id(self.material_id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn aov_pixel_filters_values_and_keeps_ids() {
+        let sample = |depth: Float, id: usize| AovSample {
+            depth,
+            normal: Vector3f::new(0., 0., 1.),
+            primitive_id: Some(id),
+            ..AovSample::default()
+        };
+        let mut pixel = AovPixel::default();
+        pixel.add_sample(&sample(1., 3), 0.25);
+        let mut rhs = AovPixel::default();
+        rhs.add_sample(&sample(2., 5), 0.75);
+        pixel.merge(&rhs);
+        // values are weighted averages, ids come from the heaviest sample
+        assert_eq!(pixel.value(Aov::Depth), vec![1.75]);
+        assert_eq!(pixel.value(Aov::Normal), vec![0., 0., 1.]);
+        assert_eq!(pixel.value(Aov::PrimitiveId), vec![5.]);
+        assert_eq!(pixel.value(Aov::MaterialId), vec![-1.]);
+        assert_eq!(AovPixel::default().value(Aov::UV), vec![0., 0.]);
+        let mut buffer = Vec::new();
+        pixel.write_to(&mut buffer).unwrap();
+        let read = AovPixel::read_from(&mut buffer.as_slice()).unwrap();
+        assert_eq!(read.value(Aov::Depth), pixel.value(Aov::Depth));
+        assert_eq!(read.value(Aov::PrimitiveId), vec![5.]);
+    }
+}
@@ -0,0 +1,58 @@
+use super::Camera;
+use crate::*;
+
+// equirectangular, laid out like the map of an InfiniteAreaLight so a render can be baked back into one
+#[derive(Clone)]
+pub struct EnvironmentCamera {
+    resolution: Vector2f,
+}
+
+impl EnvironmentCamera {
+    pub fn new(resolution: Vector2u) -> Self {
+        Self {
+            resolution: Vector2f::new(resolution.x as Float, resolution.y as Float),
+        }
+    }
+}
+
+impl PrimitiveClipper for EnvironmentCamera {
+    fn clip(&self, _: &Primitive) -> bool {
+        false
+    }
+}
+
+impl Camera for EnvironmentCamera {
+    fn generate_ray(&self, film: Point2f, _: &mut dyn Sampler) -> Ray {
+        let phi_theta = Point2f::new(film.x / self.resolution.x, film.y / self.resolution.y);
+        Ray::new_od(
+            Point3f::new(0., 0., 0.),
+            normalize_phi_theta_to_spherical(&phi_theta),
+        )
+    }
+    fn as_clipper(&self) -> &dyn PrimitiveClipper {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn environment_covers_the_sphere() {
+        let camera = EnvironmentCamera::new(Vector2u::new(64, 32));
+        let mut sampler = RandomSampler::new(1);
+        let mut direction = |x, y| {
+            let ray = camera.generate_ray(Point2f::new(x, y), &mut sampler);
+            assert_eq!(ray.o, Point3f::new(0., 0., 0.));
+            assert!((ray.d.magnitude() - 1.) < 1e-5);
+            ray.d
+        };
+        // the top and bottom rows are the poles, the middle row goes around the equator
+        assert!((direction(10., 0.) - Vector3f::new(0., 0., 1.)).magnitude() < 1e-5);
+        assert!((direction(50., 32.) - Vector3f::new(0., 0., -1.)).magnitude() < 1e-5);
+        assert!((direction(0., 16.) - Vector3f::new(1., 0., 0.)).magnitude() < 1e-5);
+        assert!((direction(16., 16.) - Vector3f::new(0., 1., 0.)).magnitude() < 1e-5);
+        assert!((direction(32., 16.) - Vector3f::new(-1., 0., 0.)).magnitude() < 1e-5);
+        assert!((direction(64., 16.) - direction(0., 16.)).magnitude() < 1e-5);
+    }
+}
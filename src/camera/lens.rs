@@ -9,6 +9,21 @@ impl<T> LensCamera<T> {
     pub fn new(camera: T, lens_radius: Float, focal_distance: Float) -> Self {
         Self{camera, lens_radius, focal_distance}
     }
+    fn lens_area(&self) -> Float {
+        PI * self.lens_radius * self.lens_radius
+    }
+    // the ray through the center of the lens to the same point on the focal plane,
+    // with the cosine of the ray and the cosine of that ray
+    fn pinhole_ray(&self, ray: &Ray) -> Option<(Ray, Float, Float)> {
+        let d = ray.d.normalize();
+        if d.z <= 0. {
+            return None;
+        }
+        let lens = ray.o - d * (ray.o.z / d.z);
+        let focus = lens + d * (self.focal_distance / d.z);
+        let pinhole = Ray::new_od(Point3f::new(0., 0., 0.), focus.coords.normalize()).with_time(ray.time);
+        Some((pinhole, d.z, pinhole.d.z))
+    }
 }
 
 impl<T: PrimitiveClipper> PrimitiveClipper for LensCamera<T> {
@@ -23,29 +38,70 @@ impl<T: Send + Sync + Camera> Camera for LensCamera<T> {
     }
     fn generate_ray(&self, film: Point2f, sampler: &mut dyn sampler::Sampler) -> Ray {
         let ray = self.camera.generate_ray(film, sampler);
+        sampler.start_slot(DimensionSlot::Lens);
         let lens = self.lens_radius * concentric_sample_disk(sampler.get_2d());
-        let ft = self.focal_distance / ray.d.z;
+        let ft = (self.focal_distance - ray.o.z) / ray.d.z;
         assert!(!ft.is_nan() && !ft.is_infinite());
         let focus = ray.eval(ft);
-        let o = Point3f::new(lens.x, lens.y, 0.);
+        // the lens is centered where the ray crosses z = 0, the ray is clipped by the same planes
+        let center = ray.eval(-ray.o.z / ray.d.z);
+        let hither = ray.o.z;
+        let yon = ray.eval(ray.t_max).z;
+        let o = Point3f::new(center.x + lens.x, center.y + lens.y, 0.);
         let d = (focus - o).normalize();
-        Ray::new_od(o, d)
+        clip_ray(o, d, hither, yon)
+    }
+    // the inner camera is a pinhole, its importance is spread over the lens
+    fn we(&self, ray: &Ray) -> (Float, Option<Point2f>) {
+        if self.lens_radius <= 0. {
+            return self.camera.we(ray);
+        }
+        match self.pinhole_ray(ray) {
+            Some((pinhole, cos_theta, pinhole_cos_theta)) => {
+                let (we, raster) = self.camera.we(&pinhole);
+                (we * (pinhole_cos_theta / cos_theta).powi(4) / self.lens_area(), raster)
+            }
+            None => (0., None),
+        }
+    }
+    fn pdf_we(&self, ray: &Ray) -> (Float, Float) {
+        if self.lens_radius <= 0. {
+            return self.camera.pdf_we(ray);
+        }
+        match self.pinhole_ray(ray) {
+            Some((pinhole, cos_theta, pinhole_cos_theta)) => match self.camera.pdf_we(&pinhole) {
+                (_, pdf_dir) if pdf_dir != 0. => {
+                    (1. / self.lens_area(), pdf_dir * (pinhole_cos_theta / cos_theta).powi(3))
+                }
+                _ => (0., 0.),
+            },
+            None => (0., 0.),
+        }
+    }
+    fn sample_wi(&self, point: &ShapePoint, u: &Point2f) -> Option<(Vector3f, Float, Float, Point2f, ShapePoint)> {
+        if self.lens_radius <= 0. {
+            return self.camera.sample_wi(point, u);
+        }
+        let lens = self.lens_radius * concentric_sample_disk(*u);
+        let mut lens = ShapePoint::new_p_normal(Point3f::new(lens.x, lens.y, 0.), Normal3f::from(Vector3f::new(0., 0., 1.)));
+        lens.time = point.time;
+        sample_lens_wi(self, point, lens, self.lens_area())
     }
 }
 
 pub trait ParseWithLens<T> {
-    fn with_lens(self, property_set: &PropertySet) -> Box<dyn Camera>;
+    fn with_lens(self, property_set: &PropertySet) -> SceneResult<Box<dyn Camera>>;
 }
 
 impl<T: 'static + Camera> ParseWithLens<T> for T {
-    fn with_lens(self, property_set: &PropertySet) -> Box<dyn Camera> {
-        let lens_radius = property_set.get_value("lensradius");
-        let focal_distance = property_set.get_value("focaldistance").unwrap_or(1e6);
+    fn with_lens(self, property_set: &PropertySet) -> SceneResult<Box<dyn Camera>> {
+        let lens_radius = property_set.get_value("lensradius")?;
+        let focal_distance = property_set.get_value("focaldistance")?.unwrap_or(1e6);
         if let Some(lens_radius) = lens_radius {
-            Box::new(LensCamera::new(self, lens_radius, focal_distance))
+            Ok(Box::new(LensCamera::new(self, lens_radius, focal_distance)))
         }
         else {
-            Box::new(self)
+            Ok(Box::new(self))
         }
     }
 }
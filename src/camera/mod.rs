@@ -1,36 +1,316 @@
+mod environment;
 mod lens;
+mod orthographic;
 mod perspective;
+mod realistic;
+mod rig;
 use crate::*;
 use downcast_rs::DowncastSync;
-pub use perspective::*;
+use std::path::PathBuf;
+pub use environment::*;
 pub use lens::*;
+pub use orthographic::*;
+pub use perspective::*;
+pub use realistic::*;
+pub use rig::*;
 
 pub trait Camera: DowncastSync + PrimitiveClipper {
     fn generate_ray(&self, film: Point2f, sampler: &mut dyn Sampler) -> Ray;
+    // the weight scales the radiance carried by the ray, zero if the ray was blocked
+    fn generate_ray_with_weight(&self, film: Point2f, sampler: &mut dyn Sampler) -> (Ray, Float) {
+        (self.generate_ray(film, sampler), 1.)
+    }
     fn as_clipper(&self) -> &dyn PrimitiveClipper;
+    // the importance a camera ray carries and the raster point it belongs to, None if it misses the film,
+    // cameras without these cannot be reached by paths traced from the lights
+    fn we(&self, _ray: &Ray) -> (Float, Option<Point2f>) {
+        (0., None)
+    }
+    // the pdfs of the ray origin on the lens by area and of its direction by solid angle
+    fn pdf_we(&self, _ray: &Ray) -> (Float, Float) {
+        (0., 0.)
+    }
+    // a point on the lens seen from point: the direction to it, the importance arriving from it,
+    // the pdf by solid angle, the raster point and the lens point
+    fn sample_wi(&self, _point: &ShapePoint, _u: &Point2f) -> Option<(Vector3f, Float, Float, Point2f, ShapePoint)> {
+        None
+    }
+    // the times the camera rays are spread over
+    fn shutter(&self) -> (Float, Float) {
+        (0., 0.)
+    }
 }
 
 impl_downcast!(sync Camera);
 
+// connects a camera space point to a point on a lens with the given area
+pub fn sample_lens_wi(
+    camera: &dyn Camera,
+    point: &ShapePoint,
+    lens: ShapePoint,
+    lens_area: Float,
+) -> Option<(Vector3f, Float, Float, Point2f, ShapePoint)> {
+    let wi = lens.p - point.p;
+    let distance_2 = wi.magnitude_squared();
+    if distance_2 == 0. {
+        return None;
+    }
+    let wi = wi / distance_2.sqrt();
+    let pdf = distance_2 / (lens.n.dot(&wi).abs() * lens_area);
+    let (we, raster) = camera.we(&Ray::new_od(lens.p, -wi));
+    match raster {
+        Some(raster) if we != 0. && pdf.is_finite() => Some((wi, we, pdf, raster, lens)),
+        _ => None,
+    }
+}
+
+// cameras are created for a film and a view shift, the slope along x the view turns the center of its screen to
 impl ParseFromBlockSegment<'_> for Box<dyn Camera> {
-    type T = Box<dyn Fn(Vector2u) -> Box<dyn Camera>>;
-    fn parse_from_segment(segment: &BlockSegment) -> Option<Self::T> {
-        let object_value = segment.get_object_by_type("Camera")?.clone();
-        if object_value.get_name().unwrap() == "perspective" {
-            let fov = object_value.get_value("fov").unwrap_or(90.);
-            Some(Box::new(move |resolution| {
-                PerspectiveCamera::new(fov, resolution).with_lens(&object_value)
-            }))
-        } else {
-            trace!("{:?}", segment);
-            panic!()
+    type T = Box<dyn Fn(&Film, Float) -> SceneResult<Box<dyn Camera>>>;
+    fn parse_from_segment(segment: &BlockSegment) -> SceneResult<Option<Self::T>> {
+        let object_value = match segment.get_object_by_type("Camera") {
+            Some(object_value) => object_value.clone(),
+            None => return Ok(None),
+        };
+        let shutter_open = object_value.get_value("shutteropen")?.unwrap_or(0.);
+        let shutter_close = object_value.get_value("shutterclose")?.unwrap_or(1.);
+        if shutter_close < shutter_open {
+            return Err(object_value.error("Shutter closes before it opens"));
+        }
+        let camera_factory: Self::T = match object_value.get_name()? {
+            "perspective" => {
+                let fov = object_value.get_value("fov")?.unwrap_or(90.);
+                let (hither, yon) = parse_clip_planes(&object_value)?;
+                Box::new(move |film, view_shift| {
+                    let resolution = film.resolution();
+                    let screen_window = parse_screen_window(&object_value, &resolution)?;
+                    // the screen spans tan(fov / 2) along its shorter axis
+                    let shift = Vector2f::new(view_shift / (fov / 2.).to_radians().tan(), 0.);
+                    let screen_window = Bounds2f::new(&(screen_window.min + shift), &(screen_window.max + shift));
+                    PerspectiveCamera::new(fov, screen_window, hither, yon, resolution)
+                        .with_lens(&object_value)
+                })
+            }
+            "orthographic" => {
+                let (hither, yon) = parse_clip_planes(&object_value)?;
+                Box::new(move |film, _| {
+                    let resolution = film.resolution();
+                    let screen_window = parse_screen_window(&object_value, &resolution)?;
+                    OrthographicCamera::new(screen_window, hither, yon, resolution)
+                        .with_lens(&object_value)
+                })
+            }
+            "environment" => Box::new(move |film, _| {
+                Ok(Box::new(EnvironmentCamera::new(film.resolution())) as Box<dyn Camera>)
+            }),
+            "realistic" => {
+                let lens_file: PathBuf = object_value.get_required("lensfile")?;
+                let aperture_diameter = object_value.get_value("aperturediameter")?.unwrap_or(1.);
+                let focus_distance = object_value.get_value("focusdistance")?.unwrap_or(10.);
+                let simple_weighting = object_value.get_value("simpleweighting")?.unwrap_or(true);
+                Box::new(move |film, _| {
+                    let camera = RealisticCamera::from_file(
+                        &lens_file,
+                        aperture_diameter,
+                        focus_distance,
+                        simple_weighting,
+                        film,
+                    )
+                    .map_err(|e| {
+                        object_value.error(&format!(
+                            "Cant create lens from '{}': {}",
+                            lens_file.display(),
+                            e
+                        ))
+                    })?;
+                    Ok(Box::new(camera) as Box<dyn Camera>)
+                })
+            }
+            camera_type => {
+                trace!("{:?}", segment);
+                return Err(object_value.error(&format!("Unknown camera '{}'", camera_type)));
+            }
+        };
+        Ok(Some(Box::new(move |film, view_shift| {
+            let camera = camera_factory(film, view_shift)?;
+            Ok(Box::new(ShutterCamera::new(camera, shutter_open, shutter_close)) as Box<dyn Camera>)
+        })))
+    }
+}
+
+pub fn default_screen_window(aspect: Float) -> Bounds2f {
+    if aspect > 1. {
+        Bounds2f::new(&Point2f::new(-aspect, -1.), &Point2f::new(aspect, 1.))
+    } else {
+        Bounds2f::new(
+            &Point2f::new(-1., -1. / aspect),
+            &Point2f::new(1., 1. / aspect),
+        )
+    }
+}
+
+// the screen window is shifted by lensshift times its size, e.g. to render one tile of a poster
+pub fn parse_screen_window(property_set: &PropertySet, resolution: &Vector2u) -> SceneResult<Bounds2f> {
+    let screen_window = match property_set.get_value::<Vec<Float>>("screenwindow")? {
+        Some(window) => {
+            if window.len() != 4 {
+                return Err(property_set.error("Expected 4 values for screenwindow"));
+            }
+            if window[0] >= window[1] || window[2] >= window[3] {
+                return Err(property_set.error("Screen window is empty"));
+            }
+            Bounds2f::new(
+                &Point2f::new(window[0], window[2]),
+                &Point2f::new(window[1], window[3]),
+            )
+        }
+        None => {
+            let aspect = match property_set.get_value::<Float>("frameaspectratio")? {
+                Some(aspect) if aspect <= 0. => {
+                    return Err(property_set.error("Frame aspect ratio must be positive"));
+                }
+                Some(aspect) => aspect,
+                None => resolution.x as Float / resolution.y as Float,
+            };
+            default_screen_window(aspect)
+        }
+    };
+    match property_set.get_value::<Vec<Float>>("lensshift")? {
+        Some(shift) => {
+            if shift.len() != 2 {
+                return Err(property_set.error("Expected 2 values for lensshift"));
+            }
+            let d = screen_window.diagonal();
+            let shift = Vector2f::new(shift[0] * d.x, shift[1] * d.y);
+            Ok(Bounds2f::new(
+                &(screen_window.min + shift),
+                &(screen_window.max + shift),
+            ))
         }
+        None => Ok(screen_window),
+    }
+}
+
+pub fn parse_clip_planes(property_set: &PropertySet) -> SceneResult<(Float, Float)> {
+    let hither = property_set.get_value("hither")?.unwrap_or(1e-3);
+    let yon = property_set.get_value("yon")?.unwrap_or(1e30);
+    if hither <= 0. {
+        return Err(property_set.error("Hither must be positive"));
+    }
+    if yon <= hither {
+        return Err(property_set.error("Yon must be greater than hither"));
+    }
+    Ok((hither, yon))
+}
+
+// camera space ray from the hither plane to the yon plane
+pub fn clip_ray(o: Point3f, d: Vector3f, hither: Float, yon: Float) -> Ray {
+    let t_hither = (hither - o.z) / d.z;
+    let t_yon = (yon - o.z) / d.z;
+    Ray::new(o + d * t_hither, d, t_yon - t_hither)
+}
+
+pub fn screen_to_raster(screen_window: &Bounds2f, resolution: &Vector2f) -> Transform {
+    let screen_window_d = screen_window.diagonal();
+    Transform::translate(Vector3f::new(
+        -screen_window.min.x,
+        -screen_window.max.y,
+        0.,
+    ))
+    .apply(&Transform::scale(Vector3f::new(
+        1. / screen_window_d.x,
+        -(1. / screen_window_d.y),
+        1.,
+    )))
+    .apply(&Transform::scale(Vector3f::new(
+        resolution.x,
+        resolution.y,
+        1.,
+    )))
+}
+
+// keeps every primitive with a corner of its bound in front of the camera and inside the film
+// a primitive is clipped only if its whole bound is outside one plane of the view frustum,
+// the planes are tested in homogeneous raster space so points behind the camera are handled too
+pub fn clip_by_raster(primitive: &Primitive, camera_to_raster: &Transform, resolution: &Vector2f) -> bool {
+    if primitive.get_source().get_material().is_some() {
+        let bound = primitive.bound();
+        let corners = (0..8)
+            .map(|corner| {
+                let p = bound[corner];
+                *camera_to_raster.m * nalgebra::Vector4::new(p.x, p.y, p.z, 1.)
+            })
+            .collect::<Vec<_>>();
+        let outside = |f: &dyn Fn(&nalgebra::Vector4<Float>) -> bool| corners.iter().all(f);
+        outside(&|h| h.x < 0.)
+            || outside(&|h| h.x >= resolution.x * h.w)
+            || outside(&|h| h.y < 0.)
+            || outside(&|h| h.y >= resolution.y * h.w)
+            || outside(&|h| h.z < 0.)
+            || outside(&|h| h.z > h.w)
+    } else {
+        false
+    }
+}
+
+// samples the ray time inside the shutter interval
+pub struct ShutterCamera {
+    camera: Box<dyn Camera>,
+    shutter_open: Float,
+    shutter_close: Float,
+}
+
+impl ShutterCamera {
+    pub fn new(camera: Box<dyn Camera>, shutter_open: Float, shutter_close: Float) -> Self {
+        Self {
+            camera,
+            shutter_open,
+            shutter_close,
+        }
+    }
+    fn sample_time(&self, sampler: &mut dyn Sampler) -> Float {
+        sampler.start_slot(DimensionSlot::Time);
+        let u = sampler.get_1d();
+        self.shutter_open * (1. - u) + self.shutter_close * u
+    }
+}
+
+impl PrimitiveClipper for ShutterCamera {
+    fn clip(&self, primitive: &Primitive) -> bool {
+        self.camera.clip(primitive)
+    }
+}
+
+impl Camera for ShutterCamera {
+    fn generate_ray(&self, film: Point2f, sampler: &mut dyn Sampler) -> Ray {
+        let time = self.sample_time(sampler);
+        self.camera.generate_ray(film, sampler).with_time(time)
+    }
+    fn generate_ray_with_weight(&self, film: Point2f, sampler: &mut dyn Sampler) -> (Ray, Float) {
+        let time = self.sample_time(sampler);
+        let (ray, weight) = self.camera.generate_ray_with_weight(film, sampler);
+        (ray.with_time(time), weight)
+    }
+    fn as_clipper(&self) -> &dyn PrimitiveClipper {
+        self
+    }
+    fn we(&self, ray: &Ray) -> (Float, Option<Point2f>) {
+        self.camera.we(ray)
+    }
+    fn pdf_we(&self, ray: &Ray) -> (Float, Float) {
+        self.camera.pdf_we(ray)
+    }
+    fn sample_wi(&self, point: &ShapePoint, u: &Point2f) -> Option<(Vector3f, Float, Float, Point2f, ShapePoint)> {
+        self.camera.sample_wi(point, u)
+    }
+    fn shutter(&self) -> (Float, Float) {
+        (self.shutter_open, self.shutter_close)
     }
 }
 
 pub struct TransformCamera {
     camera: Box<dyn Camera>,
-    transform: Transform,
+    transform: AnimatedTransform,
 }
 
 impl From<Box<dyn Camera>> for TransformCamera {
@@ -39,15 +319,19 @@ impl From<Box<dyn Camera>> for TransformCamera {
     }
 }
 impl Transformable for TransformCamera {
+    // the transform maps world space into the space the inner camera transform starts from
     fn apply(self, transform: &Transform) -> Self {
-        Self::new(
-            self.camera,
-            self.transform.apply(&transform.clone().inverse()),
-        )
+        Self {
+            camera: self.camera,
+            transform: self.transform.apply(&transform.clone().inverse()),
+        }
     }
 }
 impl TransformCamera {
     pub fn new(camera: Box<dyn Camera>, transform: Transform) -> Self {
+        Self::new_animated(camera, transform.into())
+    }
+    pub fn new_animated(camera: Box<dyn Camera>, transform: AnimatedTransform) -> Self {
         Self {
             camera,
             transform: transform.inverse(),
@@ -57,18 +341,44 @@ impl TransformCamera {
 
 impl PrimitiveClipper for TransformCamera {
     fn clip(&self, primitive: &Primitive) -> bool {
-        let inv = self.transform.clone().inverse();
+        // a moving camera sees more than any single frame of it
+        if self.transform.is_animated() {
+            return false;
+        }
+        let inv = self.transform.start_transform().clone().inverse();
         self.camera.clip(&primitive.clone().apply(&inv))
     }
 }
 
 impl Camera for TransformCamera {
     fn generate_ray(&self, film: Point2f, sampler: &mut dyn Sampler) -> Ray {
-        self.camera.generate_ray(film, sampler).apply(&self.transform)
+        let ray = self.camera.generate_ray(film, sampler);
+        ray.apply(&self.transform.interpolate(ray.time))
+    }
+    fn generate_ray_with_weight(&self, film: Point2f, sampler: &mut dyn Sampler) -> (Ray, Float) {
+        let (ray, weight) = self.camera.generate_ray_with_weight(film, sampler);
+        (ray.apply(&self.transform.interpolate(ray.time)), weight)
     }
     fn as_clipper(&self) -> &dyn PrimitiveClipper {
         self
     }
+    fn we(&self, ray: &Ray) -> (Float, Option<Point2f>) {
+        let world_to_camera = self.transform.interpolate(ray.time).inverse();
+        self.camera.we(&ray.apply(&world_to_camera))
+    }
+    fn pdf_we(&self, ray: &Ray) -> (Float, Float) {
+        let world_to_camera = self.transform.interpolate(ray.time).inverse();
+        self.camera.pdf_we(&ray.apply(&world_to_camera))
+    }
+    fn sample_wi(&self, point: &ShapePoint, u: &Point2f) -> Option<(Vector3f, Float, Float, Point2f, ShapePoint)> {
+        let camera_to_world = self.transform.interpolate(point.time);
+        let camera_point = point.apply(&camera_to_world.clone().inverse());
+        let (wi, we, pdf, raster, lens) = self.camera.sample_wi(&camera_point, u)?;
+        Some((wi.apply(&camera_to_world).normalize(), we, pdf, raster, lens.apply(&camera_to_world)))
+    }
+    fn shutter(&self) -> (Float, Float) {
+        self.camera.shutter()
+    }
 }
 
 pub fn camera_apply(camera: Box<dyn Camera>, transform: &Transform) -> Box<dyn Camera> {
@@ -77,3 +387,11 @@ pub fn camera_apply(camera: Box<dyn Camera>, transform: &Transform) -> Box<dyn C
         Err(camera) => Box::new(TransformCamera::new(camera, transform.clone())),
     }
 }
+
+pub fn camera_apply_animated(camera: Box<dyn Camera>, transform: &AnimatedTransform) -> Box<dyn Camera> {
+    if transform.is_animated() {
+        Box::new(TransformCamera::new_animated(camera, transform.clone()))
+    } else {
+        camera_apply(camera, transform.start_transform())
+    }
+}
@@ -0,0 +1,68 @@
+use super::Camera;
+use crate::*;
+
+#[derive(Clone)]
+pub struct OrthographicCamera {
+    raster_to_camera: Transform,
+    resolution: Vector2f,
+    hither: Float,
+    yon: Float,
+}
+
+impl OrthographicCamera {
+    pub fn new(screen_window: Bounds2f, hither: Float, yon: Float, resolution: Vector2u) -> Self {
+        let resolution = Vector2f::new(resolution.x as Float, resolution.y as Float);
+        let screen_to_raster = screen_to_raster(&screen_window, &resolution);
+        let camera_to_screen = Transform::orthographic(hither, yon);
+        let camera_to_raster = camera_to_screen.apply(&screen_to_raster);
+        Self {
+            raster_to_camera: camera_to_raster.inverse(),
+            resolution,
+            hither,
+            yon,
+        }
+    }
+    pub fn generate_ray_without_sampler(&self, film: Point2f) -> Ray {
+        let film = Point3f::new(film.x, film.y, 0.);
+        let camera = film.apply(&self.raster_to_camera);
+        clip_ray(camera, Vector3f::new(0., 0., 1.), self.hither, self.yon)
+    }
+}
+
+impl PrimitiveClipper for OrthographicCamera {
+    fn clip(&self, primitive: &Primitive) -> bool {
+        clip_by_raster(primitive, &self.raster_to_camera.clone().inverse(), &self.resolution)
+    }
+}
+
+impl Camera for OrthographicCamera {
+    fn generate_ray(&self, film: Point2f, _: &mut dyn Sampler) -> Ray {
+        self.generate_ray_without_sampler(film)
+    }
+    fn as_clipper(&self) -> &dyn PrimitiveClipper {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn orthographic_maps_screen_window() {
+        let screen_window = Bounds2f::new(&Point2f::new(-2., -1.), &Point2f::new(2., 1.));
+        let camera = OrthographicCamera::new(screen_window, 0.5, 10., Vector2u::new(40, 20));
+        // raster y points down while camera y points up
+        assert_eq!(
+            camera.generate_ray_without_sampler(Point2f::new(0., 0.)),
+            Ray::new(Point3f::new(-2., 1., 0.5), Vector3f::new(0., 0., 1.), 9.5)
+        );
+        assert_eq!(
+            camera.generate_ray_without_sampler(Point2f::new(40., 20.)),
+            Ray::new(Point3f::new(2., -1., 0.5), Vector3f::new(0., 0., 1.), 9.5)
+        );
+        assert_eq!(
+            camera.generate_ray_without_sampler(Point2f::new(30., 10.)),
+            Ray::new(Point3f::new(1., 0., 0.5), Vector3f::new(0., 0., 1.), 9.5)
+        );
+    }
+}
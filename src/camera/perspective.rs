@@ -4,76 +4,53 @@ use crate::*;
 #[derive(Clone)]
 pub struct PerspectiveCamera {
     raster_to_camera: Transform,
+    camera_to_raster: Transform,
+    // area of the film on the plane z = 1
+    film_area: Float,
     resolution: Vector2f,
+    hither: Float,
+    yon: Float,
 }
 
 impl PerspectiveCamera {
-    pub fn new(fov: Float, resolution: Vector2u) -> Self {
-        let near = 1e-2;
-        let far = 1000.;
+    pub fn new(fov: Float, screen_window: Bounds2f, hither: Float, yon: Float, resolution: Vector2u) -> Self {
         let resolution = Vector2f::new(resolution.x as Float, resolution.y as Float);
-        let aspect = resolution.x / resolution.y;
-        let screen_window = if aspect > 1. {
-            Bounds2f::new(&Point2f::new(-aspect, -1.), &Point2f::new(aspect, 1.))
-        } else {
-            Bounds2f::new(
-                &Point2f::new(-1., -1. / aspect),
-                &Point2f::new(1., 1. / aspect),
-            )
-        };
-        let screen_window_d = screen_window.diagonal();
-        let screen_to_raster = Transform::translate(Vector3f::new(
-            -screen_window.min.x,
-            -screen_window.max.y,
-            0.,
-        ))
-        .apply(&Transform::scale(Vector3f::new(
-            1. / screen_window_d.x,
-            -(1. / screen_window_d.y),
-            1.,
-        )))
-        .apply(&Transform::scale(Vector3f::new(
-            resolution.x,
-            resolution.y,
-            1.,
-        )));
-        let camera_to_screen = Transform::perspective(fov, near, far);
+        let screen_to_raster = screen_to_raster(&screen_window, &resolution);
+        let camera_to_screen = Transform::perspective(fov, hither, yon);
         let camera_to_raster = camera_to_screen.apply(&screen_to_raster);
+        let raster_to_camera = camera_to_raster.clone().inverse();
+        let film_min = Point3f::new(0., 0., 0.).apply(&raster_to_camera);
+        let film_max = Point3f::new(resolution.x, resolution.y, 0.).apply(&raster_to_camera);
+        let film_min = film_min / film_min.z;
+        let film_max = film_max / film_max.z;
         Self {
-            raster_to_camera: camera_to_raster.inverse(),
+            raster_to_camera,
+            camera_to_raster,
+            film_area: ((film_max.x - film_min.x) * (film_max.y - film_min.y)).abs(),
             resolution,
+            hither,
+            yon,
+        }
+    }
+    // the raster point a camera space direction from the pinhole passes through
+    fn raster_point(&self, d: &Vector3f) -> Option<Point2f> {
+        let raster = (Point3f::new(0., 0., 0.) + d).apply(&self.camera_to_raster);
+        if raster.x >= 0. && raster.x < self.resolution.x && raster.y >= 0. && raster.y < self.resolution.y {
+            Some(raster.xy())
+        } else {
+            None
         }
     }
     pub fn generate_ray_without_sampler(&self, film: Point2f) -> Ray {
         let film = Point3f::new(film.x, film.y, 0.);
         let camera = film.apply(&self.raster_to_camera);
-        Ray::new_od(Point3f::new(0., 0., 0.), camera.coords.normalize())
+        clip_ray(Point3f::new(0., 0., 0.), camera.coords.normalize(), self.hither, self.yon)
     }
 }
 
 impl PrimitiveClipper for PerspectiveCamera {
     fn clip(&self, primitive: &Primitive) -> bool {
-        if primitive.get_source().get_material().is_some() {
-            let bound = primitive.bound();
-            let camera_to_raster = self.raster_to_camera.clone().inverse();
-            for corner in 0..8 {
-                let p = bound[corner];
-                let raster = p.apply(&camera_to_raster);
-                let clip = 
-                    //raster.z > 1.
-                    raster.z < 0.
-                    ||raster.x >= self.resolution.x
-                    || raster.x < 0.
-                    || raster.y >= self.resolution.y
-                    || raster.y < 0.;
-                if !clip {
-                    return false;
-                }
-            }
-            true
-        } else {
-            false
-        }
+        clip_by_raster(primitive, &self.raster_to_camera.clone().inverse(), &self.resolution)
     }
 }
 
@@ -84,6 +61,30 @@ impl Camera for PerspectiveCamera {
     fn as_clipper(&self) -> &dyn PrimitiveClipper {
         self
     }
+    // every camera ray passes the pinhole, only its direction matters
+    fn we(&self, ray: &Ray) -> (Float, Option<Point2f>) {
+        let d = ray.d.normalize();
+        let cos_theta = d.z;
+        if cos_theta <= 0. {
+            return (0., None);
+        }
+        match self.raster_point(&d) {
+            Some(raster) => (1. / (self.film_area * cos_theta.powi(4)), Some(raster)),
+            None => (0., None),
+        }
+    }
+    fn pdf_we(&self, ray: &Ray) -> (Float, Float) {
+        let d = ray.d.normalize();
+        if d.z <= 0. || self.raster_point(&d).is_none() {
+            return (0., 0.);
+        }
+        (1., 1. / (self.film_area * d.z.powi(3)))
+    }
+    fn sample_wi(&self, point: &ShapePoint, _: &Point2f) -> Option<(Vector3f, Float, Float, Point2f, ShapePoint)> {
+        let mut lens = ShapePoint::new_p_normal(Point3f::new(0., 0., 0.), Normal3f::from(Vector3f::new(0., 0., 1.)));
+        lens.time = point.time;
+        sample_lens_wi(self, point, lens, 1.)
+    }
 }
 
 #[cfg(test)]
@@ -93,13 +94,14 @@ mod tests {
     fn perspective_works() {
         let camera_point = Point3f::new(0., 0., 1000.);
         let resolution = Vector2u::new(1024, 768);
-        let camera = Box::new(PerspectiveCamera::new(40., resolution));
+        let screen_window = default_screen_window(1024. / 768.);
+        let camera = Box::new(PerspectiveCamera::new(40., screen_window, 1e-2, 1000., resolution));
         let r = camera.clone().raster_to_camera.inverse();
         let film = camera_point.apply(&r);
         assert_eq!(film, Point3f::new(512., 384., 1.));
         assert_eq!(
             camera.generate_ray_without_sampler(film.xy()),
-            Ray::new_od(Point3f::new(0., 0., 0.), Vector3f::new(0., 0., 1.))
+            Ray::new(Point3f::new(0., 0., 1e-2), Vector3f::new(0., 0., 1.), 1000. - 1e-2)
         );
     }
 }
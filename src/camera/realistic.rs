@@ -0,0 +1,391 @@
+use super::Camera;
+use crate::*;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy)]
+struct LensElement {
+    curvature_radius: Float,
+    thickness: Float,
+    eta: Float,
+    aperture_radius: Float,
+}
+
+// lens elements are stored from the scene side to the film side, in meters
+pub struct RealisticCamera {
+    elements: Vec<LensElement>,
+    resolution: Vector2f,
+    physical_extent: Bounds2f,
+    exit_pupil_bounds: Vec<Bounds2f>,
+    simple_weighting: bool,
+}
+
+const EXIT_PUPIL_BOUNDS_COUNT: usize = 64;
+const EXIT_PUPIL_SAMPLE_COUNT: usize = 256 * 256;
+
+fn intersect_spherical_element(radius: Float, z_center: Float, ray: &Ray) -> Option<(Float, Normal3f)> {
+    let o = ray.o - Point3f::new(0., 0., z_center);
+    let d = ray.d;
+    let a = d.dot(&d);
+    let b = 2. * d.dot(&o);
+    let c = o.dot(&o) - radius * radius;
+    let (t0, t1) = solve_quadratic(a, b, c)?;
+    let use_closer_t = (ray.d.z > 0.) ^ (radius < 0.);
+    let t = if use_closer_t { t0.min(t1) } else { t0.max(t1) };
+    if t < 0. {
+        return None;
+    }
+    let n = (o + t * ray.d).normalize();
+    let n = if n.dot(&-ray.d) < 0. { -n } else { n };
+    Some((t, n.into()))
+}
+
+// converts between camera space and lens space, which looks down -z
+fn flip_z(ray: &Ray) -> Ray {
+    Ray::new_od(
+        Point3f::new(ray.o.x, ray.o.y, -ray.o.z),
+        Vector3f::new(ray.d.x, ray.d.y, -ray.d.z),
+    )
+}
+
+impl RealisticCamera {
+    pub fn new(
+        lens_data: &[Float],
+        aperture_diameter: Float,
+        focus_distance: Float,
+        simple_weighting: bool,
+        film: &Film,
+    ) -> Result<Self, String> {
+        if lens_data.is_empty() || !lens_data.len().is_multiple_of(4) {
+            return Err(format!(
+                "Expected a multiple of 4 values in lens file, found {}",
+                lens_data.len()
+            ));
+        }
+        let elements = lens_data
+            .chunks(4)
+            .map(|element| {
+                let mut diameter = element[3];
+                if element[0] == 0. {
+                    if aperture_diameter > diameter {
+                        warn!(
+                            "Aperture diameter {} is greater than maximum possible {}, clamping it",
+                            aperture_diameter, diameter
+                        );
+                    } else {
+                        diameter = aperture_diameter;
+                    }
+                }
+                LensElement {
+                    curvature_radius: element[0] * 0.001,
+                    thickness: element[1] * 0.001,
+                    eta: element[2],
+                    aperture_radius: diameter * 0.001 / 2.,
+                }
+            })
+            .collect();
+        let resolution = film.resolution();
+        let physical_extent = film.physical_extent();
+        let mut camera = Self {
+            elements,
+            resolution: Vector2f::new(resolution.x as Float, resolution.y as Float),
+            physical_extent,
+            exit_pupil_bounds: Vec::new(),
+            simple_weighting,
+        };
+        let rear_thickness = camera.focus_thick_lens(focus_distance)?;
+        camera.elements.last_mut().unwrap().thickness = rear_thickness;
+        let film_radius = camera.physical_extent.diagonal().magnitude() / 2.;
+        camera.exit_pupil_bounds = (0..EXIT_PUPIL_BOUNDS_COUNT)
+            .map(|i| {
+                let r0 = i as Float / EXIT_PUPIL_BOUNDS_COUNT as Float * film_radius;
+                let r1 = (i + 1) as Float / EXIT_PUPIL_BOUNDS_COUNT as Float * film_radius;
+                camera.bound_exit_pupil(r0, r1)
+            })
+            .collect();
+        Ok(camera)
+    }
+    pub fn from_file(
+        path: &Path,
+        aperture_diameter: Float,
+        focus_distance: Float,
+        simple_weighting: bool,
+        film: &Film,
+    ) -> Result<Self, String> {
+        let s = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let mut lens_data = Vec::new();
+        for line in s.lines() {
+            let line = line.split('#').next().unwrap();
+            for value in line.split_whitespace() {
+                lens_data.push(
+                    value
+                        .parse::<Float>()
+                        .map_err(|_| format!("Invalid number '{}'", value))?,
+                );
+            }
+        }
+        Self::new(&lens_data, aperture_diameter, focus_distance, simple_weighting, film)
+    }
+    fn lens_rear_z(&self) -> Float {
+        self.elements.last().unwrap().thickness
+    }
+    fn lens_front_z(&self) -> Float {
+        self.elements.iter().map(|element| element.thickness).sum()
+    }
+    fn rear_element_radius(&self) -> Float {
+        self.elements.last().unwrap().aperture_radius
+    }
+    fn trace_lenses_from_film(&self, ray: &Ray) -> Option<Ray> {
+        let mut element_z = 0.;
+        let mut ray = flip_z(ray);
+        for (i, element) in self.elements.iter().enumerate().rev() {
+            element_z -= element.thickness;
+            let is_stop = element.curvature_radius == 0.;
+            let (t, n) = if is_stop {
+                if ray.d.z >= 0. {
+                    return None;
+                }
+                ((element_z - ray.o.z) / ray.d.z, None)
+            } else {
+                let radius = element.curvature_radius;
+                let (t, n) = intersect_spherical_element(radius, element_z + radius, &ray)?;
+                (t, Some(n))
+            };
+            let p = ray.eval(t);
+            if p.x * p.x + p.y * p.y > element.aperture_radius * element.aperture_radius {
+                return None;
+            }
+            ray.o = p;
+            if let Some(n) = n {
+                let eta_i = element.eta;
+                let eta_t = if i > 0 && self.elements[i - 1].eta != 0. {
+                    self.elements[i - 1].eta
+                } else {
+                    1.
+                };
+                ray.d = refract(&-ray.d.normalize(), &n, eta_i / eta_t)?;
+            }
+        }
+        Some(flip_z(&ray))
+    }
+    fn trace_lenses_from_scene(&self, ray: &Ray) -> Option<Ray> {
+        let mut element_z = -self.lens_front_z();
+        let mut ray = flip_z(ray);
+        for (i, element) in self.elements.iter().enumerate() {
+            let is_stop = element.curvature_radius == 0.;
+            let (t, n) = if is_stop {
+                ((element_z - ray.o.z) / ray.d.z, None)
+            } else {
+                let radius = element.curvature_radius;
+                let (t, n) = intersect_spherical_element(radius, element_z + radius, &ray)?;
+                (t, Some(n))
+            };
+            let p = ray.eval(t);
+            if p.x * p.x + p.y * p.y > element.aperture_radius * element.aperture_radius {
+                return None;
+            }
+            ray.o = p;
+            if let Some(n) = n {
+                let eta_i = if i == 0 || self.elements[i - 1].eta == 0. {
+                    1.
+                } else {
+                    self.elements[i - 1].eta
+                };
+                let eta_t = if element.eta != 0. { element.eta } else { 1. };
+                ray.d = refract(&-ray.d.normalize(), &n, eta_i / eta_t)?;
+            }
+            element_z += element.thickness;
+        }
+        Some(flip_z(&ray))
+    }
+    // returns the z of the principal plane and the focal point
+    fn compute_cardinal_points(ray_in: &Ray, ray_out: &Ray) -> (Float, Float) {
+        let tf = -ray_out.o.x / ray_out.d.x;
+        let fz = -ray_out.eval(tf).z;
+        let tp = (ray_in.o.x - ray_out.o.x) / ray_out.d.x;
+        let pz = -ray_out.eval(tp).z;
+        (pz, fz)
+    }
+    fn compute_thick_lens_approximation(&self) -> Result<([Float; 2], [Float; 2]), String> {
+        let x = 0.001 * self.physical_extent.diagonal().magnitude();
+        let scene_ray = Ray::new_od(
+            Point3f::new(x, 0., self.lens_front_z() + 1.),
+            Vector3f::new(0., 0., -1.),
+        );
+        let film_ray = self
+            .trace_lenses_from_scene(&scene_ray)
+            .ok_or("Cant trace a paraxial ray from the scene through the lens")?;
+        let (pz0, fz0) = Self::compute_cardinal_points(&scene_ray, &film_ray);
+        let film_ray = Ray::new_od(
+            Point3f::new(x, 0., self.lens_rear_z() - 1.),
+            Vector3f::new(0., 0., 1.),
+        );
+        let scene_ray = self
+            .trace_lenses_from_film(&film_ray)
+            .ok_or("Cant trace a paraxial ray from the film through the lens")?;
+        let (pz1, fz1) = Self::compute_cardinal_points(&film_ray, &scene_ray);
+        Ok(([pz0, pz1], [fz0, fz1]))
+    }
+    // thickness of the rear element that brings the plane at focus_distance into focus
+    fn focus_thick_lens(&self, focus_distance: Float) -> Result<Float, String> {
+        let (pz, fz) = self.compute_thick_lens_approximation()?;
+        let f = fz[0] - pz[0];
+        let z = -focus_distance;
+        let c = (pz[1] - z - pz[0]) * (pz[1] - z - 4. * f - pz[0]);
+        if c <= 0. {
+            return Err(format!(
+                "Focus distance {} is too short for the lens",
+                focus_distance
+            ));
+        }
+        let delta = 0.5 * (pz[1] - z + pz[0] - c.sqrt());
+        Ok(self.lens_rear_z() + delta)
+    }
+    fn bound_exit_pupil(&self, film_x0: Float, film_x1: Float) -> Bounds2f {
+        let rear_radius = 1.5 * self.rear_element_radius();
+        let rear_bounds = Bounds2f::new(
+            &Point2f::new(-rear_radius, -rear_radius),
+            &Point2f::new(rear_radius, rear_radius),
+        );
+        let mut pupil_bounds: Option<Bounds2f> = None;
+        for i in 0..EXIT_PUPIL_SAMPLE_COUNT {
+            let t = (i as Float + 0.5) / EXIT_PUPIL_SAMPLE_COUNT as Float;
+            let film = Point3f::new(film_x0 + t * (film_x1 - film_x0), 0., 0.);
+            let rear = Point2f::new(
+                rear_bounds.min.x + radical_inverse(i, 0) * 2. * rear_radius,
+                rear_bounds.min.y + radical_inverse(i, 1) * 2. * rear_radius,
+            );
+            let inside = pupil_bounds.as_ref().is_some_and(|bounds| bounds.inside(&rear));
+            if inside
+                || self
+                    .trace_lenses_from_film(&Ray::new_od(
+                        film,
+                        Point3f::new(rear.x, rear.y, self.lens_rear_z()) - film,
+                    ))
+                    .is_some()
+            {
+                pupil_bounds = Some(match pupil_bounds {
+                    Some(bounds) => bounds | &rear,
+                    None => Bounds2f::from(rear),
+                });
+            }
+        }
+        match pupil_bounds {
+            Some(bounds) => bounds.expand(
+                2. * rear_bounds.diagonal().magnitude()
+                    / (EXIT_PUPIL_SAMPLE_COUNT as Float).sqrt(),
+            ),
+            None => rear_bounds,
+        }
+    }
+    // returns the sampled point on the rear element and the area of the bound it was sampled from
+    fn sample_exit_pupil(&self, film: &Point2f, u: &Point2f) -> (Point3f, Float) {
+        let r_film = (film.x * film.x + film.y * film.y).sqrt();
+        let film_radius = self.physical_extent.diagonal().magnitude() / 2.;
+        let index = (r_film / film_radius * EXIT_PUPIL_BOUNDS_COUNT as Float) as usize;
+        let pupil_bounds = &self.exit_pupil_bounds[min(index, EXIT_PUPIL_BOUNDS_COUNT - 1)];
+        let d = pupil_bounds.diagonal();
+        let lens = Point2f::new(
+            pupil_bounds.min.x + u.x * d.x,
+            pupil_bounds.min.y + u.y * d.y,
+        );
+        // the bounds were computed along +x, rotate them to the film point
+        let (sin_theta, cos_theta) = if r_film != 0. {
+            (film.y / r_film, film.x / r_film)
+        } else {
+            (0., 1.)
+        };
+        (
+            Point3f::new(
+                cos_theta * lens.x - sin_theta * lens.y,
+                sin_theta * lens.x + cos_theta * lens.y,
+                self.lens_rear_z(),
+            ),
+            pupil_bounds.area(),
+        )
+    }
+}
+
+impl PrimitiveClipper for RealisticCamera {
+    fn clip(&self, _: &Primitive) -> bool {
+        false
+    }
+}
+
+impl Camera for RealisticCamera {
+    fn generate_ray(&self, film: Point2f, sampler: &mut dyn Sampler) -> Ray {
+        self.generate_ray_with_weight(film, sampler).0
+    }
+    fn generate_ray_with_weight(&self, film: Point2f, sampler: &mut dyn Sampler) -> (Ray, Float) {
+        let extent = &self.physical_extent;
+        let d = extent.diagonal();
+        let film = Point2f::new(
+            extent.min.x + film.x / self.resolution.x * d.x,
+            extent.min.y + film.y / self.resolution.y * d.y,
+        );
+        // the image is flipped by the lens
+        let film = Point3f::new(-film.x, film.y, 0.);
+        sampler.start_slot(DimensionSlot::Lens);
+        let (rear, bounds_area) = self.sample_exit_pupil(&film.xy(), &sampler.get_2d());
+        let film_ray = Ray::new_od(film, rear - film);
+        match self.trace_lenses_from_film(&film_ray) {
+            Some(ray) => {
+                let cos_theta = film_ray.d.normalize().z;
+                let cos_4_theta = (cos_theta * cos_theta) * (cos_theta * cos_theta);
+                let weight = if self.simple_weighting {
+                    cos_4_theta * bounds_area / self.exit_pupil_bounds[0].area()
+                } else {
+                    cos_4_theta * bounds_area / (self.lens_rear_z() * self.lens_rear_z())
+                };
+                (Ray::new_od(ray.o, ray.d.normalize()), weight)
+            }
+            None => (film_ray, 0.),
+        }
+    }
+    fn as_clipper(&self) -> &dyn PrimitiveClipper {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn realistic_focuses_at_focus_distance() {
+        let segments = read_scene_str(
+            r#"Film "image" "integer xresolution" [32] "integer yresolution" [32]"#,
+            Path::new("film.pbrt"),
+        )
+        .unwrap();
+        let (film, _, _) = Film::parse_from_segment(&segments[0]).unwrap().unwrap();
+        // a biconvex lens behind a 4mm aperture stop
+        let lens = [0., 2., 1., 4., 40., 4., 1.5, 16., -40., 0., 1., 16.];
+        let camera = RealisticCamera::new(&lens, 4., 1., true, &film).unwrap();
+        let mut sampler = RandomSampler::new(64);
+        sampler.set_pixel(&Point2u::new(0, 0));
+        let mut hits = |raster: Point2f, z: Float| {
+            let mut hits = Vec::new();
+            sampler.set_sample_index(0);
+            for _ in 0..64 {
+                let (ray, weight) = camera.generate_ray_with_weight(raster, &mut sampler);
+                sampler.next_sample();
+                if weight > 0. {
+                    hits.push((ray.o + ray.d * ((z - ray.o.z) / ray.d.z)).xy());
+                }
+            }
+            assert!(hits.len() > 32);
+            hits
+        };
+        // the rays through the lens come closest together on the plane in focus
+        let spread = |hits: Vec<Point2f>| hits.iter().map(|p| (p - hits[0]).magnitude()).fold(0., Float::max);
+        let center = Point2f::new(16., 16.);
+        let in_focus = spread(hits(center, 1.));
+        assert!(in_focus < 0.5 * spread(hits(center, 0.8)));
+        assert!(in_focus < 0.5 * spread(hits(center, 1.25)));
+        // the lens flips the image back, so it is oriented like the other cameras
+        let left = hits(Point2f::new(12., 16.), 1.);
+        assert!(left.iter().all(|p| p.x < 0. && p.y.abs() < 0.01));
+        let top = hits(Point2f::new(16., 12.), 1.);
+        assert!(top.iter().all(|p| p.y > 0. && p.x.abs() < 0.01));
+    }
+}
@@ -0,0 +1,149 @@
+use crate::*;
+use std::path::Path;
+
+// several views of the camera side by side along its x axis, e.g. the two eyes of a stereo pair,
+// every view is rendered against the same scene
+#[derive(Debug, Clone)]
+pub struct CameraRig {
+    views: usize,
+    interocular_distance: Float,
+    convergence_distance: Option<Float>,
+}
+
+impl Default for CameraRig {
+    fn default() -> Self {
+        Self {
+            views: 1,
+            interocular_distance: 0.065,
+            convergence_distance: None,
+        }
+    }
+}
+
+impl CameraRig {
+    pub fn new(views: usize, interocular_distance: Float, convergence_distance: Option<Float>) -> Self {
+        Self {
+            views,
+            interocular_distance,
+            convergence_distance,
+        }
+    }
+    pub fn views(&self) -> usize {
+        self.views
+    }
+    // file name suffix, rig camera to view transform and view shift of each view, the views stay parallel
+    // and converge by shifting their screens so the convergence point is at the center of all of them
+    pub fn view_transforms(&self) -> Vec<(String, Transform, Float)> {
+        if self.views == 1 {
+            return vec![(String::new(), Transform::default(), 0.)];
+        }
+        (0..self.views)
+            .map(|i| {
+                let offset = (i as Float - (self.views - 1) as Float / 2.) * self.interocular_distance;
+                let pos = Point3f::new(offset, 0., 0.);
+                let transform = Transform::look_at(pos, Point3f::new(offset, 0., 1.), Vector3f::new(0., 1., 0.));
+                let view_shift = match self.convergence_distance {
+                    Some(convergence_distance) => {
+                        let p = Point3f::new(0., 0., convergence_distance).apply(&transform);
+                        p.x / p.z
+                    }
+                    None => 0.,
+                };
+                let suffix = if self.views == 2 {
+                    String::from(["_left", "_right"][i])
+                } else {
+                    format!("_{}", i)
+                };
+                (suffix, transform, view_shift)
+            })
+            .collect()
+    }
+}
+
+impl ParseFromBlockSegment<'_> for CameraRig {
+    type T = CameraRig;
+    fn parse_from_segment(segment: &BlockSegment) -> SceneResult<Option<Self::T>> {
+        let property_set = match segment.get_object_by_type("Camera") {
+            Some(property_set) => property_set,
+            None => return Ok(None),
+        };
+        let default = Self::default();
+        let views = property_set.get_value::<Integer>("views")?.unwrap_or(1);
+        if views < 1 {
+            return Err(property_set.error("Camera views must be positive"));
+        }
+        let interocular_distance = property_set
+            .get_value("interoculardistance")?
+            .unwrap_or(default.interocular_distance);
+        let convergence_distance = property_set.get_value::<Float>("convergencedistance")?;
+        if convergence_distance.is_some_and(|distance| distance <= 0.) {
+            return Err(property_set.error("Convergence distance must be positive"));
+        }
+        if convergence_distance.is_some() && property_set.get_name()? != "perspective" {
+            return Err(property_set.error("Convergence distance needs a perspective camera"));
+        }
+        Ok(Some(Self::new(views as usize, interocular_distance, convergence_distance)))
+    }
+}
+
+// a primitive is kept if any of the views can see it
+impl PrimitiveClipper for Vec<Box<dyn Camera>> {
+    fn clip(&self, primitive: &Primitive) -> bool {
+        self.iter().all(|camera| camera.clip(primitive))
+    }
+}
+
+// image.png with suffix _left is image_left.png
+pub fn view_file_name(file_name: &str, suffix: &str) -> String {
+    let path = Path::new(file_name);
+    match (path.file_stem(), path.extension()) {
+        (Some(stem), Some(extension)) => path
+            .with_file_name(format!(
+                "{}{}.{}",
+                stem.to_string_lossy(),
+                suffix,
+                extension.to_string_lossy()
+            ))
+            .to_string_lossy()
+            .into_owned(),
+        _ => format!("{}{}", file_name, suffix),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_scene::*;
+    // the raster point the view sees p at, from the pinhole the center of its film looks through
+    fn raster(camera: &dyn Camera, p: Point3f) -> Point2f {
+        let o = camera.generate_ray(Point2f::new(8., 8.), &mut RandomSampler::new(1)).o;
+        camera.we(&Ray::new_od(o, (p - o).normalize())).1.unwrap()
+    }
+    #[test]
+    fn stereo_views_converge() {
+        let rig = CameraRig::new(2, 0.1, Some(2.));
+        let views = rig.view_transforms();
+        assert_eq!(views[0].0, "_left");
+        assert_eq!(views[1].0, "_right");
+        assert_eq!(view_file_name("out/image.png", "_left"), "out/image_left.png");
+        // the camera looks at the origin from 3.4 away, the views meet there
+        let camera = r#"Camera "perspective" "float fov" [40] "integer views" [2] "float convergencedistance" [3.4]"#;
+        let scene = box_scene(r#"Sampler "random""#, r#"Integrator "path""#)
+            .replace(r#"Camera "perspective" "float fov" [40]"#, camera);
+        let loaded = load_test_scene("stereo", &scene, &RenderOptions::default()).unwrap();
+        let (left, right) = (loaded.cameras[0].as_ref(), loaded.cameras[1].as_ref());
+        let center = Point3f::new(0., 0., 0.);
+        for camera in &[left, right] {
+            assert!((raster(*camera, center) - Point2f::new(8., 8.)).magnitude() < 1e-3);
+        }
+        // the eyes stay parallel, points off the axis have no vertical parallax
+        for p in &[Point3f::new(0.5, 0.4, 0.5), Point3f::new(-0.6, -0.3, -1.)] {
+            let (l, r) = (raster(left, *p), raster(right, *p));
+            assert!((l.y - r.y).abs() < 1e-3, "{:?} {:?}", l, r);
+            assert!((l.x - r.x).abs() > 0.01);
+        }
+        let orthographic = scene.replace(r#"Camera "perspective" "float fov" [40]"#, r#"Camera "orthographic""#);
+        let error = load_test_scene("stereo-orthographic", &orthographic, &RenderOptions::default()).err().unwrap();
+        assert!(error.message.contains("perspective camera"), "{}", error.message);
+    }
+}
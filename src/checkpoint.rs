@@ -0,0 +1,121 @@
+use crate::*;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const CHECKPOINT_MAGIC: &[u8; 4] = b"CPCK";
+const CHECKPOINT_VERSION: u32 = 2;
+
+#[derive(Debug, Clone)]
+pub struct CheckpointOptions {
+    pub path: PathBuf,
+    pub interval: Duration,
+    pub resume: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RenderProgress {
+    pub sample_index: usize,
+    pub sample_count: usize,
+    pub completed_tiles: usize,
+}
+
+// the samples of a checkpoint only continue with the sampler they were drawn from
+#[derive(Debug, Clone, PartialEq)]
+pub struct SamplerSignature {
+    pub name: String,
+    pub sample_per_pixel: usize,
+}
+
+impl SamplerSignature {
+    pub fn new(sampler: &dyn Sampler) -> Self {
+        Self {
+            name: String::from(sampler.name()),
+            sample_per_pixel: sampler.get_sample_per_pixel(),
+        }
+    }
+}
+
+pub fn write_checkpoint(
+    path: &Path,
+    film: &Film,
+    sampler: &SamplerSignature,
+    progress: &RenderProgress,
+) -> io::Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    {
+        let mut w = BufWriter::new(File::create(&tmp_path)?);
+        w.write_all(CHECKPOINT_MAGIC)?;
+        w.write_u32::<LittleEndian>(CHECKPOINT_VERSION)?;
+        w.write_u64::<LittleEndian>(sampler.name.len() as u64)?;
+        w.write_all(sampler.name.as_bytes())?;
+        w.write_u64::<LittleEndian>(sampler.sample_per_pixel as u64)?;
+        w.write_u64::<LittleEndian>(progress.sample_index as u64)?;
+        w.write_u64::<LittleEndian>(progress.sample_count as u64)?;
+        w.write_u64::<LittleEndian>(progress.completed_tiles as u64)?;
+        film.write_checkpoint(&mut w)?;
+        w.flush()?;
+    }
+    // renaming keeps the previous checkpoint intact if we are killed while writing
+    fs::rename(tmp_path, path)
+}
+
+pub fn read_checkpoint(path: &Path, film: &mut Film, sampler: &SamplerSignature) -> io::Result<RenderProgress> {
+    let mut r = BufReader::new(File::open(path)?);
+    let mut magic = [0; 4];
+    r.read_exact(&mut magic)?;
+    if &magic != CHECKPOINT_MAGIC || r.read_u32::<LittleEndian>()? != CHECKPOINT_VERSION {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Not a checkpoint file"));
+    }
+    let mut name = vec![0; r.read_u64::<LittleEndian>()? as usize];
+    r.read_exact(&mut name)?;
+    let checkpoint_sampler = SamplerSignature {
+        name: String::from_utf8_lossy(&name).into_owned(),
+        sample_per_pixel: r.read_u64::<LittleEndian>()? as usize,
+    };
+    if &checkpoint_sampler != sampler {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Checkpoint was rendered with the {} sampler at {} samples per pixel",
+                checkpoint_sampler.name, checkpoint_sampler.sample_per_pixel
+            ),
+        ));
+    }
+    let progress = RenderProgress {
+        sample_index: r.read_u64::<LittleEndian>()? as usize,
+        sample_count: r.read_u64::<LittleEndian>()? as usize,
+        completed_tiles: r.read_u64::<LittleEndian>()? as usize,
+    };
+    film.read_checkpoint(&mut r)?;
+    Ok(progress)
+}
+
+pub struct Checkpointer {
+    options: CheckpointOptions,
+    sampler: SamplerSignature,
+    last_write: Instant,
+}
+
+impl Checkpointer {
+    pub fn new(options: CheckpointOptions, sampler: &dyn Sampler) -> Self {
+        Self {
+            options,
+            sampler: SamplerSignature::new(sampler),
+            last_write: Instant::now(),
+        }
+    }
+    pub fn update(&mut self, film: &Film, progress: &RenderProgress) {
+        if self.last_write.elapsed() < self.options.interval {
+            return;
+        }
+        match write_checkpoint(&self.options.path, film, &self.sampler, progress) {
+            Ok(()) => info!("Wrote checkpoint {}", self.options.path.display()),
+            Err(e) => warn!("Cant write checkpoint {}: {}", self.options.path.display(), e),
+        }
+        self.last_write = Instant::now();
+    }
+}
@@ -2,3 +2,4 @@ pub type Float = f32;
 pub type Integer = i32;
 pub type Double = f64;
 pub const MACHINE_EPSILON: Float = std::f32::EPSILON * 0.5;
+pub const ONE_MINUS_EPSILON: Float = 1. - MACHINE_EPSILON;
@@ -1,40 +1,328 @@
 use crate::*;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{self, Read, Write};
+use std::sync::Arc;
 use std::{fmt::Debug, path::Path};
 
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FilmPixel {
+    pub contribution: Spectrum,
+    pub weight: Float,
+    pub sample_count: usize,
+    pub luminance_mean: Float,
+    pub luminance_m2: Float,
+}
+
+impl FilmPixel {
+    pub fn add_luminance(&mut self, y: Float) {
+        self.sample_count += 1;
+        let delta = y - self.luminance_mean;
+        self.luminance_mean += delta / self.sample_count as Float;
+        self.luminance_m2 += delta * (y - self.luminance_mean);
+    }
+    pub fn merge_statistics(&mut self, rhs: &Self) {
+        if rhs.sample_count == 0 {
+            return;
+        }
+        let n_lhs = self.sample_count as Float;
+        let n_rhs = rhs.sample_count as Float;
+        let n = n_lhs + n_rhs;
+        let delta = rhs.luminance_mean - self.luminance_mean;
+        self.luminance_mean += delta * n_rhs / n;
+        self.luminance_m2 += rhs.luminance_m2 + delta * delta * n_lhs * n_rhs / n;
+        self.sample_count += rhs.sample_count;
+    }
+    pub fn variance(&self) -> Float {
+        if self.sample_count < 2 {
+            0.
+        } else {
+            self.luminance_m2 / (self.sample_count - 1) as Float
+        }
+    }
+    pub fn write_to(&self, w: &mut dyn Write) -> io::Result<()> {
+        for c in 0..3 {
+            w.write_f32::<LittleEndian>(self.contribution[c])?;
+        }
+        w.write_f32::<LittleEndian>(self.weight)?;
+        w.write_u64::<LittleEndian>(self.sample_count as u64)?;
+        w.write_f32::<LittleEndian>(self.luminance_mean)?;
+        w.write_f32::<LittleEndian>(self.luminance_m2)
+    }
+    pub fn read_from(r: &mut dyn Read) -> io::Result<Self> {
+        let mut contribution = [0.; 3];
+        for c in &mut contribution {
+            *c = r.read_f32::<LittleEndian>()?;
+        }
+        Ok(Self {
+            contribution: Spectrum::from(contribution),
+            weight: r.read_f32::<LittleEndian>()?,
+            sample_count: r.read_u64::<LittleEndian>()? as usize,
+            luminance_mean: r.read_f32::<LittleEndian>()?,
+            luminance_m2: r.read_f32::<LittleEndian>()?,
+        })
+    }
+    pub fn relative_error(&self) -> Float {
+        if self.sample_count < 2 {
+            return Float::INFINITY;
+        }
+        (self.variance() / self.sample_count as Float).sqrt() / max(self.luminance_mean, 0.01)
+    }
+    pub fn value(&self) -> Spectrum {
+        if self.weight == 0. {
+            Spectrum::new(0.)
+        } else {
+            (self.contribution / self.weight).map_move(|f| max(f, 0.))
+        }
+    }
+}
+
 pub trait Renderable {
     fn bound(&self) -> &Bounds2u;
-    fn get_pixels(&mut self) -> &mut FixedVec2D<Spectrum>;
-    fn add_sample(&mut self, point: &Point2u, spectrum: Spectrum) {
-        let i = Point2u::new(0, 0)+ (point - self.bound().min);
-        self.get_pixels()[i] += spectrum;
+    fn get_pixels(&mut self) -> &mut FixedVec2D<FilmPixel>;
+    fn add_pixel(&mut self, point: &Point2u, pixel: &FilmPixel) {
+        let i = Point2u::new(0, 0) + (point - self.bound().min);
+        let p = &mut self.get_pixels()[i];
+        p.contribution += pixel.contribution;
+        p.weight += pixel.weight;
+        p.merge_statistics(pixel);
     }
-    fn add_samples(&mut self, point: &Point2u, sampels: &[(Vector2f, Spectrum)]) {
-        let mut sum = Spectrum::new(0.);
-        for sample in sampels {
-            sum += sample.1;
-        }
-        sum /= sampels.len() as Float;
-        self.add_sample(point, sum);
+    fn add_sample(&mut self, point: &Point2u, spectrum: Spectrum, weight: Float) {
+        self.add_pixel(
+            point,
+            &FilmPixel {
+                contribution: spectrum * weight,
+                weight,
+                ..FilmPixel::default()
+            },
+        );
     }
 }
 
+fn expand_bound(bound: &Bounds2u, delta: &Vector2u, clamp_bound: &Bounds2u) -> Bounds2u {
+    let expanded = Bounds2u::new(
+        &Point2u::new(
+            bound.min.x.saturating_sub(delta.x),
+            bound.min.y.saturating_sub(delta.y),
+        ),
+        &(bound.max + delta),
+    );
+    expanded & clamp_bound
+}
+
+#[derive(Clone)]
 pub struct Film {
-    pixels: FixedVec2D<Spectrum>,
+    pixels: FixedVec2D<FilmPixel>,
     bound: Bounds2u,
+    crop_bound: Bounds2u,
+    write_full_frame: bool,
+    filter: Arc<dyn Filter>,
+    aovs: Vec<Aov>,
+    aov_pixels: Option<FixedVec2D<AovPixel>>,
+    diagonal: Float,
+    // light that reached the film at arbitrary raster points, summed over splat_samples camera samples
+    splats: FixedVec2D<Spectrum>,
+    splat_samples: usize,
 }
 
 impl Film {
     pub fn new(resolution: Vector2u) -> Self {
-        let pixels = FixedVec2D::new(Spectrum::new(0.), resolution);
+        let pixels = FixedVec2D::new(FilmPixel::default(), resolution);
+        let splats = FixedVec2D::new(Spectrum::new(0.), resolution);
         let bound = Bounds2u::new(&Point2u::new(0, 0), &Point2u::from(resolution));
         Self {
             pixels,
+            crop_bound: bound.clone(),
+            write_full_frame: false,
             bound,
+            filter: Arc::new(BoxFilter::new(Vector2f::new(0.5, 0.5))),
+            aovs: Vec::new(),
+            aov_pixels: None,
+            diagonal: 0.035,
+            splats,
+            splat_samples: 0,
         }
     }
-    pub fn write_image(self, file_path: &Path) {
-        let s = ImageTexture::from(self.pixels.map(|s| s.map_move(gamma_correct)));
-        s.into_file(file_path);
+    pub fn resolution(&self) -> Vector2u {
+        self.bound.diagonal()
+    }
+    // diagonal of the sensor in meters
+    pub fn set_diagonal(&mut self, diagonal: Float) {
+        self.diagonal = diagonal;
+    }
+    // sensor extent in meters, centered at the origin
+    pub fn physical_extent(&self) -> Bounds2f {
+        let resolution = self.resolution();
+        let aspect = resolution.y as Float / resolution.x as Float;
+        let x = (self.diagonal * self.diagonal / (1. + aspect * aspect)).sqrt();
+        let y = aspect * x;
+        Bounds2f::new(
+            &Point2f::new(-x / 2., -y / 2.),
+            &Point2f::new(x / 2., y / 2.),
+        )
+    }
+    pub fn set_aovs(&mut self, aovs: Vec<Aov>) {
+        self.aov_pixels = if aovs.is_empty() {
+            None
+        } else {
+            Some(FixedVec2D::new(AovPixel::default(), self.pixels.size()))
+        };
+        self.aovs = aovs;
+    }
+    pub fn has_aovs(&self) -> bool {
+        !self.aovs.is_empty()
+    }
+    pub fn set_filter(&mut self, filter: Arc<dyn Filter>) {
+        self.filter = filter;
+    }
+    pub fn set_crop_window(&mut self, crop_window: &Bounds2f) {
+        let resolution = self.pixels.size().map(|u| u as Float);
+        let to_pixel = |p: &Point2f| {
+            Point2u::new(
+                clamp(p.x * resolution.x, 0., resolution.x).ceil() as usize,
+                clamp(p.y * resolution.y, 0., resolution.y).ceil() as usize,
+            )
+        };
+        self.crop_bound = Bounds2u::new(&to_pixel(&crop_window.min), &to_pixel(&crop_window.max));
+    }
+    pub fn set_pixel_bounds(&mut self, pixel_bounds: &Bounds2u) {
+        self.crop_bound = pixel_bounds.clone() & &self.bound;
+    }
+    pub fn crop_bound(&self) -> &Bounds2u {
+        &self.crop_bound
+    }
+    pub fn noise_level(&self) -> Float {
+        let points = self.crop_bound.index_inside();
+        let error_sum: Float = points.iter().map(|p| self.pixels[*p].relative_error()).sum();
+        error_sum / points.len() as Float
+    }
+    fn checkpoint_header(&self) -> [u64; 7] {
+        let resolution = self.pixels.size();
+        let crop_bound = &self.crop_bound;
+        [
+            resolution.x as u64,
+            resolution.y as u64,
+            crop_bound.min.x as u64,
+            crop_bound.min.y as u64,
+            crop_bound.max.x as u64,
+            crop_bound.max.y as u64,
+            self.aovs.len() as u64,
+        ]
+    }
+    pub fn write_checkpoint(&self, w: &mut dyn Write) -> io::Result<()> {
+        for u in self.checkpoint_header().iter() {
+            w.write_u64::<LittleEndian>(*u)?;
+        }
+        for pixel in self.pixels.as_ref() {
+            pixel.write_to(w)?;
+        }
+        for splat in self.splats.as_ref() {
+            for c in 0..3 {
+                w.write_f32::<LittleEndian>(splat[c])?;
+            }
+        }
+        w.write_u64::<LittleEndian>(self.splat_samples as u64)?;
+        if let Some(aov_pixels) = &self.aov_pixels {
+            for aov_pixel in aov_pixels.as_ref() {
+                aov_pixel.write_to(w)?;
+            }
+        }
+        Ok(())
+    }
+    pub fn read_checkpoint(&mut self, r: &mut dyn Read) -> io::Result<()> {
+        for u in self.checkpoint_header().iter() {
+            if r.read_u64::<LittleEndian>()? != *u {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Checkpoint does not match the film",
+                ));
+            }
+        }
+        for pixel in self.pixels.as_mut() {
+            *pixel = FilmPixel::read_from(r)?;
+        }
+        for splat in self.splats.as_mut() {
+            let mut rgb = [0.; 3];
+            for c in &mut rgb {
+                *c = r.read_f32::<LittleEndian>()?;
+            }
+            *splat = Spectrum::from(rgb);
+        }
+        self.splat_samples = r.read_u64::<LittleEndian>()? as usize;
+        if let Some(aov_pixels) = &mut self.aov_pixels {
+            for aov_pixel in aov_pixels.as_mut() {
+                *aov_pixel = AovPixel::read_from(r)?;
+            }
+        }
+        Ok(())
+    }
+    pub fn get_pixel(&self, point: &Point2u) -> &FilmPixel {
+        &self.pixels[*point]
+    }
+    // splats are not filtered, they go to the pixel containing their raster point
+    pub fn add_splats(&mut self, splats: &[(Point2f, Spectrum)], samples: usize) {
+        let resolution = self.pixels.size();
+        for (raster, splat) in splats {
+            let (x, y) = (raster.x.floor(), raster.y.floor());
+            if x >= 0. && y >= 0. && (x as usize) < resolution.x && (y as usize) < resolution.y {
+                self.splats[Point2u::new(x as usize, y as usize)] += *splat;
+            }
+        }
+        self.splat_samples += samples;
+    }
+    // the splats are an estimate for every pixel sampled, so they are divided by the samples per pixel
+    fn pixel_value(&self, point: &Point2u) -> Spectrum {
+        let value = self.pixels[*point].value();
+        if self.splat_samples == 0 {
+            return value;
+        }
+        let splat_scale = self.sample_bound().area() as Float / self.splat_samples as Float;
+        value + self.splats[*point] * splat_scale
+    }
+    pub fn set_write_full_frame(&mut self, write_full_frame: bool) {
+        self.write_full_frame = write_full_frame;
+    }
+    fn output_bound(&self) -> &Bounds2u {
+        if self.write_full_frame {
+            &self.bound
+        } else {
+            &self.crop_bound
+        }
+    }
+    pub fn write_image(&self, file_path: &Path) -> std::io::Result<()> {
+        let image_file_writer = image_file_writer(file_path).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "Unsupported image format")
+        })?;
+        let output_bound = self.output_bound();
+        let resolution = output_bound.diagonal();
+        let points = output_bound.index_inside_rows();
+        let pixels: Vec<Spectrum> = if image_file_writer.is_hdr() {
+            points.iter().map(|p| self.pixel_value(p)).collect()
+        } else {
+            points
+                .iter()
+                .map(|p| self.pixel_value(p).map_move(gamma_correct))
+                .collect()
+        };
+        let aov_pixels = match &self.aov_pixels {
+            Some(aov_pixels) => aov_pixels,
+            None => return image_file_writer.write_file(file_path, resolution, &pixels),
+        };
+        let mut channels = Vec::new();
+        for (c, name) in ["R", "G", "B"].iter().enumerate() {
+            channels.push((*name, pixels.iter().map(|s| s[c]).collect()));
+        }
+        for aov in &self.aovs {
+            let values: Vec<Vec<Float>> = points.iter().map(|p| aov_pixels[*p].value(*aov)).collect();
+            for (c, name) in aov.channel_names().iter().enumerate() {
+                channels.push((*name, values.iter().map(|v| v[c]).collect()));
+            }
+        }
+        image_file_writer.write_channels(file_path, resolution, channels)
+    }
+    // the pixels sampled for the crop window, its border reaches the crop window through the filter
+    pub fn sample_bound(&self) -> Bounds2u {
+        let sample_delta = self.filter.radius().map(|r| max(r - 0.5, 0.).ceil() as usize);
+        expand_bound(&self.crop_bound, &sample_delta, &self.bound)
     }
     pub fn gen_tiles(&self) -> Vec<FilmTile> {
         let tile_size = 16;
@@ -46,20 +334,37 @@ impl Film {
                 (resolution.y - 1) / tile_size + 1,
             ),
         );
-        let self_bound = self.bound();
+        let pixel_delta = self.filter.radius().map(|r| r.ceil() as usize);
+        let sample_bound = self.sample_bound();
         let mut r = Vec::new();
         for tile_index in tile_indices.index_inside() {
             let next = Point2u::new(tile_index.x + 1, tile_index.y + 1);
-            let bound = Bounds2u::new(&(tile_index * tile_size), &(next * tile_size)) & self_bound;
-            r.push(FilmTile::new(
-                bound
-            ));
+            let bound = Bounds2u::new(&(tile_index * tile_size), &(next * tile_size));
+            if !bound.overlaps(&sample_bound) {
+                continue;
+            }
+            let bound = bound & &sample_bound;
+            if bound.area() == 0 {
+                continue;
+            }
+            let pixel_bound = expand_bound(&bound, &pixel_delta, &self.crop_bound);
+            let mut tile = FilmTile::new(bound, pixel_bound, self.filter.clone());
+            if self.has_aovs() {
+                tile.enable_aovs();
+            }
+            r.push(tile);
         }
         r
     }
-    pub fn merge_tile(&mut self, tile: FilmTile) {
-        for (p, s) in tile.into_merge() {
-            self.add_sample(&p, s);
+    pub fn merge_tile(&mut self, mut tile: FilmTile) {
+        if let (Some(aov_pixels), Some(tile_aov_pixels)) = (&mut self.aov_pixels, tile.aov_pixels.take()) {
+            let min = tile.bound.min;
+            for (p, aov_pixel) in tile_aov_pixels.enumerate() {
+                aov_pixels[p + min.coords].merge(aov_pixel);
+            }
+        }
+        for (p, pixel) in tile.into_merge() {
+            self.add_pixel(&p, &pixel);
         }
     }
 }
@@ -67,43 +372,169 @@ impl Renderable for Film {
     fn bound(&self) -> &Bounds2u {
         &self.bound
     }
-    fn get_pixels(&mut self) -> &mut FixedVec2D<Spectrum> {
+    fn get_pixels(&mut self) -> &mut FixedVec2D<FilmPixel> {
         &mut self.pixels
     }
 }
 
 impl ParseFromBlockSegment<'_> for Film {
     type T = (Film, String, Vector2u);
-    fn parse_from_segment(segment: &BlockSegment) -> Option<Self::T> {
-        let property_set = segment.get_object_by_type("Film")?;
-        if property_set.get_name().unwrap() == "image" {
-            let x_resolution = property_set.get_value("xresolution").unwrap_or(640);
-            let y_resolution = property_set.get_value("yresolution").unwrap_or(480);
-            let resolution = Vector2u::new(x_resolution, y_resolution);
-            let file_name = property_set
-                .get_string("filename")
-                .unwrap_or_else(|| String::from("curry-pbrt.png"));
-            Some((Film::new(resolution), file_name, resolution))
-        } else {
-            panic!()
+    fn parse_from_segment(segment: &BlockSegment) -> SceneResult<Option<Self::T>> {
+        let property_set = match segment.get_object_by_type("Film") {
+            Some(property_set) => property_set,
+            None => return Ok(None),
+        };
+        match property_set.get_name()? {
+            "image" => {
+                let x_resolution = property_set.get_value("xresolution")?.unwrap_or(640);
+                let y_resolution = property_set.get_value("yresolution")?.unwrap_or(480);
+                if x_resolution == 0 || y_resolution == 0 {
+                    return Err(property_set.error("Resolution must be positive"));
+                }
+                let resolution = Vector2u::new(x_resolution, y_resolution);
+                let file_name = property_set
+                    .get_string("filename")?
+                    .unwrap_or_else(|| String::from("curry-pbrt.png"));
+                let image_file_writer = image_file_writer(Path::new(&file_name)).ok_or_else(|| {
+                    property_set.error(&format!("Unsupported image format '{}'", file_name))
+                })?;
+                let mut film = Film::new(resolution);
+                if let Some(aov_names) = property_set.get_value::<Vec<String>>("aovs")? {
+                    let mut aovs = Vec::new();
+                    for aov_name in aov_names {
+                        let aov = Aov::from_name(&aov_name).ok_or_else(|| {
+                            property_set.error(&format!("Unknown AOV '{}'", aov_name))
+                        })?;
+                        aovs.push(aov);
+                    }
+                    if !aovs.is_empty() && !image_file_writer.supports_channels() {
+                        return Err(property_set.error("AOV output requires an EXR filename"));
+                    }
+                    film.set_aovs(aovs);
+                }
+                let crop_window = property_set.get_value::<Vec<Float>>("cropwindow")?;
+                let pixel_bounds = property_set.get_value::<Vec<usize>>("pixelbounds")?;
+                if crop_window.is_some() && pixel_bounds.is_some() {
+                    return Err(property_set.error("Both cropwindow and pixelbounds are specified"));
+                }
+                if let Some(crop_window) = crop_window {
+                    if crop_window.len() != 4 {
+                        return Err(property_set.error("Expected 4 values for cropwindow"));
+                    }
+                    if crop_window.iter().any(|f| !(0. ..=1.).contains(f))
+                        || crop_window[0] >= crop_window[1]
+                        || crop_window[2] >= crop_window[3]
+                    {
+                        return Err(property_set.error("Expected cropwindow X0 < X1 and Y0 < Y1 in [0, 1]"));
+                    }
+                    let crop_window = Bounds2f::new(
+                        &Point2f::new(crop_window[0], crop_window[2]),
+                        &Point2f::new(crop_window[1], crop_window[3]),
+                    );
+                    film.set_crop_window(&crop_window);
+                }
+                if let Some(pixel_bounds) = pixel_bounds {
+                    if pixel_bounds.len() != 4 {
+                        return Err(property_set.error("Expected 4 values for pixelbounds"));
+                    }
+                    if pixel_bounds[0] >= pixel_bounds[1] || pixel_bounds[2] >= pixel_bounds[3] {
+                        return Err(property_set.error("Expected pixelbounds X0 < X1 and Y0 < Y1"));
+                    }
+                    if pixel_bounds[0] >= x_resolution || pixel_bounds[2] >= y_resolution {
+                        return Err(property_set.error("Pixelbounds are outside the film"));
+                    }
+                    let pixel_bounds = Bounds2u::new(
+                        &Point2u::new(pixel_bounds[0], pixel_bounds[2]),
+                        &Point2u::new(pixel_bounds[1], pixel_bounds[3]),
+                    );
+                    film.set_pixel_bounds(&pixel_bounds);
+                }
+                if film.crop_bound().area() == 0 {
+                    return Err(property_set.error("Crop window is empty"));
+                }
+                film.set_write_full_frame(property_set.get_default("writefullframe")?);
+                let diagonal = property_set.get_value("diagonal")?.unwrap_or(35.);
+                if diagonal <= 0. {
+                    return Err(property_set.error("Film diagonal must be positive"));
+                }
+                film.set_diagonal(diagonal * 0.001);
+                Ok(Some((film, file_name, resolution)))
+            }
+            film_type => Err(property_set.error(&format!("Unknown film '{}'", film_type))),
         }
     }
 }
 
 pub struct FilmTile {
+    sample_bound: Bounds2u,
     bound: Bounds2u,
-    pixels: FixedVec2D<Spectrum>,
+    pixels: FixedVec2D<FilmPixel>,
+    filter: Arc<dyn Filter>,
+    aov_pixels: Option<FixedVec2D<AovPixel>>,
 }
 
 impl FilmTile {
-    pub fn new(bound: Bounds2u) -> Self {
+    pub fn new(sample_bound: Bounds2u, bound: Bounds2u, filter: Arc<dyn Filter>) -> Self {
         let d = bound.diagonal();
         Self {
+            sample_bound,
             bound,
-            pixels: FixedVec2D::new(Spectrum::new(0.), d),
+            pixels: FixedVec2D::new(FilmPixel::default(), d),
+            filter,
+            aov_pixels: None,
+        }
+    }
+    pub fn enable_aovs(&mut self) {
+        self.aov_pixels = Some(FixedVec2D::new(AovPixel::default(), self.bound.diagonal()));
+    }
+    pub fn sample_bound(&self) -> &Bounds2u {
+        &self.sample_bound
+    }
+    fn splat(&self, point: &Point2u, offset: &Vector2f) -> Vec<(Point2u, Float)> {
+        let radius = self.filter.radius();
+        let p = Point2f::new(point.x as Float + offset.x, point.y as Float + offset.y);
+        let min_x = max((p.x - radius.x).ceil(), self.bound.min.x as Float) as usize;
+        let min_y = max((p.y - radius.y).ceil(), self.bound.min.y as Float) as usize;
+        let max_x = min((p.x + radius.x).floor() + 1., self.bound.max.x as Float) as usize;
+        let max_y = min((p.y + radius.y).floor() + 1., self.bound.max.y as Float) as usize;
+        let mut r = Vec::new();
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let weight = self
+                    .filter
+                    .evaluate(&Point2f::new(x as Float - p.x, y as Float - p.y));
+                r.push((Point2u::new(x, y), weight));
+            }
         }
+        r
     }
-    pub fn into_merge(self) -> Vec<(Point2u, Spectrum)> {
+    pub fn add_samples(&mut self, point: &Point2u, samples: &[(Vector2f, Spectrum)]) {
+        for (offset, spectrum) in samples {
+            for (p, weight) in self.splat(point, offset) {
+                self.add_sample(&p, *spectrum, weight);
+            }
+        }
+        let bound = &self.bound;
+        if (bound.min.x..bound.max.x).contains(&point.x) && (bound.min.y..bound.max.y).contains(&point.y) {
+            let mut pixel = FilmPixel::default();
+            for (_, spectrum) in samples {
+                pixel.add_luminance(spectrum.y());
+            }
+            self.add_pixel(point, &pixel);
+        }
+    }
+    pub fn add_aov_samples(&mut self, point: &Point2u, samples: &[(Vector2f, AovSample)]) {
+        for (offset, aov_sample) in samples {
+            let splat = self.splat(point, offset);
+            let min = self.bound.min;
+            if let Some(aov_pixels) = &mut self.aov_pixels {
+                for (p, weight) in splat {
+                    aov_pixels[Point2u::new(0, 0) + (p - min)].add_sample(aov_sample, weight);
+                }
+            }
+        }
+    }
+    pub fn into_merge(self) -> Vec<(Point2u, FilmPixel)> {
         let mut index = 0;
         let d = self.bound.diagonal();
         let min = self.bound.min;
@@ -121,13 +552,40 @@ impl Renderable for FilmTile {
     fn bound(&self) -> &Bounds2u {
         &self.bound
     }
-    fn get_pixels(&mut self) -> &mut FixedVec2D<Spectrum> {
+    fn get_pixels(&mut self) -> &mut FixedVec2D<FilmPixel> {
         &mut self.pixels
     }
 }
 
 impl Debug for FilmTile {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.bound)
+        write!(f, "{}", self.sample_bound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn parse_film(film: &str) -> SceneResult<Option<(Film, String, Vector2u)>> {
+        let segments = read_scene_str(film, Path::new("film.pbrt"))?;
+        Film::parse_from_segment(&segments[0])
+    }
+    #[test]
+    fn film_rejects_bounds_off_the_film() {
+        let film = "Film \"image\" \"integer xresolution\" [64] \"integer yresolution\" [36] ";
+        let (film_parsed, _, _) = parse_film(&format!("{}\"integer pixelbounds\" [10 20 30 40]", film)).unwrap().unwrap();
+        assert_eq!(film_parsed.crop_bound().min, Point2u::new(10, 30));
+        assert_eq!(film_parsed.crop_bound().max, Point2u::new(20, 36));
+        let error = |bounds: &str| match parse_film(&format!("{}{}", film, bounds)) {
+            Err(error) => error.message,
+            Ok(_) => panic!("{} parsed", bounds),
+        };
+        assert_eq!(error("\"integer pixelbounds\" [100 120 0 10]"), "Pixelbounds are outside the film");
+        assert_eq!(error("\"integer pixelbounds\" [20 10 0 10]"), "Expected pixelbounds X0 < X1 and Y0 < Y1");
+        assert_eq!(error("\"integer pixelbounds\" [64 70 0 10]"), "Pixelbounds are outside the film");
+        let crop_error = "Expected cropwindow X0 < X1 and Y0 < Y1 in [0, 1]";
+        assert_eq!(error("\"float cropwindow\" [0.5 1.5 0 1]"), crop_error);
+        assert_eq!(error("\"float cropwindow\" [0.5 0.5 0 1]"), crop_error);
+        assert_eq!(error("\"float cropwindow\" [0 1 -0.1 1]"), crop_error);
     }
 }
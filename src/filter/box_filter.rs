@@ -0,0 +1,22 @@
+use super::Filter;
+use crate::*;
+
+#[derive(Debug, Clone)]
+pub struct BoxFilter {
+    radius: Vector2f,
+}
+
+impl BoxFilter {
+    pub fn new(radius: Vector2f) -> Self {
+        Self { radius }
+    }
+}
+
+impl Filter for BoxFilter {
+    fn radius(&self) -> Vector2f {
+        self.radius
+    }
+    fn evaluate(&self, _p: &Point2f) -> Float {
+        1.
+    }
+}
@@ -0,0 +1,31 @@
+use super::Filter;
+use crate::*;
+
+#[derive(Debug, Clone)]
+pub struct GaussianFilter {
+    radius: Vector2f,
+    alpha: Float,
+    exp: Vector2f,
+}
+
+impl GaussianFilter {
+    pub fn new(radius: Vector2f, alpha: Float) -> Self {
+        Self {
+            radius,
+            alpha,
+            exp: radius.map(|r| (-alpha * r * r).exp()),
+        }
+    }
+    fn gaussian(&self, d: Float, exp: Float) -> Float {
+        max(0., (-self.alpha * d * d).exp() - exp)
+    }
+}
+
+impl Filter for GaussianFilter {
+    fn radius(&self) -> Vector2f {
+        self.radius
+    }
+    fn evaluate(&self, p: &Point2f) -> Float {
+        self.gaussian(p.x, self.exp.x) * self.gaussian(p.y, self.exp.y)
+    }
+}
@@ -0,0 +1,40 @@
+use super::Filter;
+use crate::*;
+
+#[derive(Debug, Clone)]
+pub struct LanczosSincFilter {
+    radius: Vector2f,
+    tau: Float,
+}
+
+impl LanczosSincFilter {
+    pub fn new(radius: Vector2f, tau: Float) -> Self {
+        Self { radius, tau }
+    }
+    fn windowed_sinc(&self, x: Float, radius: Float) -> Float {
+        let x = x.abs();
+        if x > radius {
+            0.
+        } else {
+            sinc(x) * sinc(x / self.tau)
+        }
+    }
+}
+
+fn sinc(x: Float) -> Float {
+    let x = x.abs();
+    if x < 1e-5 {
+        1.
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+impl Filter for LanczosSincFilter {
+    fn radius(&self) -> Vector2f {
+        self.radius
+    }
+    fn evaluate(&self, p: &Point2f) -> Float {
+        self.windowed_sinc(p.x, self.radius.x) * self.windowed_sinc(p.y, self.radius.y)
+    }
+}
@@ -0,0 +1,47 @@
+use super::Filter;
+use crate::*;
+
+#[derive(Debug, Clone)]
+pub struct MitchellFilter {
+    radius: Vector2f,
+    inv_radius: Vector2f,
+    b: Float,
+    c: Float,
+}
+
+impl MitchellFilter {
+    pub fn new(radius: Vector2f, b: Float, c: Float) -> Self {
+        Self {
+            radius,
+            inv_radius: radius.map(|r| 1. / r),
+            b,
+            c,
+        }
+    }
+    fn mitchell_1d(&self, x: Float) -> Float {
+        let b = self.b;
+        let c = self.c;
+        let x = (2. * x).abs();
+        if x > 1. {
+            ((-b - 6. * c) * x * x * x
+                + (6. * b + 30. * c) * x * x
+                + (-12. * b - 48. * c) * x
+                + (8. * b + 24. * c))
+                / 6.
+        } else {
+            ((12. - 9. * b - 6. * c) * x * x * x
+                + (-18. + 12. * b + 6. * c) * x * x
+                + (6. - 2. * b))
+                / 6.
+        }
+    }
+}
+
+impl Filter for MitchellFilter {
+    fn radius(&self) -> Vector2f {
+        self.radius
+    }
+    fn evaluate(&self, p: &Point2f) -> Float {
+        self.mitchell_1d(p.x * self.inv_radius.x) * self.mitchell_1d(p.y * self.inv_radius.y)
+    }
+}
@@ -0,0 +1,86 @@
+use crate::*;
+use std::sync::Arc;
+mod box_filter;
+mod gaussian;
+mod lanczos;
+mod mitchell;
+mod triangle;
+pub use box_filter::*;
+pub use gaussian::*;
+pub use lanczos::*;
+pub use mitchell::*;
+pub use triangle::*;
+
+pub trait Filter: Sync + Send {
+    fn radius(&self) -> Vector2f;
+    fn evaluate(&self, p: &Point2f) -> Float;
+}
+
+impl ParseFromBlockSegment<'_> for Arc<dyn Filter> {
+    type T = Arc<dyn Filter>;
+    fn parse_from_segment(segment: &BlockSegment) -> SceneResult<Option<Self::T>> {
+        let property_set = match segment.get_object_by_type("PixelFilter") {
+            Some(property_set) => property_set,
+            None => return Ok(None),
+        };
+        let filter_type = property_set.get_name()?;
+        let default_radius = match filter_type {
+            "box" => 0.5,
+            "triangle" => 2.,
+            "gaussian" => 1.5,
+            "mitchell" => 2.,
+            "sinc" => 4.,
+            _ => return Err(property_set.error(&format!("Unknown filter '{}'", filter_type))),
+        };
+        let radius = Vector2f::new(
+            property_set.get_value("xwidth")?.unwrap_or(default_radius),
+            property_set.get_value("ywidth")?.unwrap_or(default_radius),
+        );
+        if radius.x <= 0. || radius.y <= 0. {
+            return Err(property_set.error("Filter width must be positive"));
+        }
+        let filter: Arc<dyn Filter> = match filter_type {
+            "box" => Arc::new(BoxFilter::new(radius)),
+            "triangle" => Arc::new(TriangleFilter::new(radius)),
+            "gaussian" => {
+                let alpha = property_set.get_value("alpha")?.unwrap_or(2.);
+                Arc::new(GaussianFilter::new(radius, alpha))
+            }
+            "mitchell" => {
+                let b = property_set.get_value("B")?.unwrap_or(1. / 3.);
+                let c = property_set.get_value("C")?.unwrap_or(1. / 3.);
+                Arc::new(MitchellFilter::new(radius, b, c))
+            }
+            _ => {
+                let tau = property_set.get_value("tau")?.unwrap_or(3.);
+                Arc::new(LanczosSincFilter::new(radius, tau))
+            }
+        };
+        Ok(Some(filter))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn filters_fall_off_to_zero_at_radius() {
+        let radius = Vector2f::new(2., 1.5);
+        let filters: Vec<Arc<dyn Filter>> = vec![
+            Arc::new(TriangleFilter::new(radius)),
+            Arc::new(GaussianFilter::new(radius, 2.)),
+            Arc::new(MitchellFilter::new(radius, 1. / 3., 1. / 3.)),
+        ];
+        for filter in filters {
+            assert!(filter.evaluate(&Point2f::new(0., 0.)) > 0.);
+            assert!(filter.evaluate(&Point2f::new(radius.x, 0.)).abs() < 1e-5);
+            assert!(filter.evaluate(&Point2f::new(0., -radius.y)).abs() < 1e-5);
+        }
+        // the windowed sinc is cut off at the radius instead
+        let filter = LanczosSincFilter::new(radius, 3.);
+        assert!(filter.evaluate(&Point2f::new(0., 0.)) > 0.);
+        assert_eq!(filter.evaluate(&Point2f::new(radius.x + 1e-3, 0.)), 0.);
+        let filter = BoxFilter::new(radius);
+        assert_eq!(filter.evaluate(&Point2f::new(1.9, -1.4)), 1.);
+    }
+}
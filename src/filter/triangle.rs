@@ -0,0 +1,22 @@
+use super::Filter;
+use crate::*;
+
+#[derive(Debug, Clone)]
+pub struct TriangleFilter {
+    radius: Vector2f,
+}
+
+impl TriangleFilter {
+    pub fn new(radius: Vector2f) -> Self {
+        Self { radius }
+    }
+}
+
+impl Filter for TriangleFilter {
+    fn radius(&self) -> Vector2f {
+        self.radius
+    }
+    fn evaluate(&self, p: &Point2f) -> Float {
+        max(0., self.radius.x - p.x.abs()) * max(0., self.radius.y - p.y.abs())
+    }
+}
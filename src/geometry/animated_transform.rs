@@ -0,0 +1,305 @@
+use crate::*;
+use nalgebra::{Matrix3, Matrix4, Quaternion, Rotation3, UnitQuaternion};
+
+#[derive(Debug, Clone)]
+struct DecomposedTransform {
+    t: Vector3f,
+    r: Quaternion<Float>,
+    s: Matrix3<Float>,
+}
+
+impl DecomposedTransform {
+    fn new(m: &Matrix4<Float>) -> Self {
+        let t = Vector3f::new(m[(0, 3)], m[(1, 3)], m[(2, 3)]);
+        let m = m.fixed_slice::<nalgebra::U3, nalgebra::U3>(0, 0).into_owned();
+        // polar decomposition, m = r * s
+        let mut r = m;
+        for _ in 0..100 {
+            let r_it = match r.transpose().try_inverse() {
+                Some(r_it) => r_it,
+                None => break,
+            };
+            let r_next = 0.5 * (r + r_it);
+            let norm = (0..3)
+                .map(|i| (0..3).map(|j| (r[(i, j)] - r_next[(i, j)]).abs()).sum::<Float>())
+                .fold(0., max);
+            r = r_next;
+            if norm <= 0.0001 {
+                break;
+            }
+        }
+        let s = r.try_inverse().map_or_else(Matrix3::identity, |r_inv| r_inv * m);
+        let r = UnitQuaternion::from_rotation_matrix(&Rotation3::from_matrix_unchecked(r));
+        Self {
+            t,
+            r: r.into_inner(),
+            s,
+        }
+    }
+}
+
+fn slerp(t: Float, q1: &Quaternion<Float>, q2: &Quaternion<Float>) -> Quaternion<Float> {
+    let cos_theta = q1.dot(q2);
+    if cos_theta > 0.9995 {
+        (q1 * (1. - t) + q2 * t).normalize()
+    } else {
+        let theta = clamp(cos_theta, -1., 1.).acos();
+        let theta_p = theta * t;
+        let q_perp = (q2 - q1 * cos_theta).normalize();
+        q1 * theta_p.cos() + q_perp * theta_p.sin()
+    }
+}
+
+// interpolates the decomposed translation, rotation and scale between two keyframes
+#[derive(Debug, Clone)]
+pub struct AnimatedTransform {
+    start_transform: Transform,
+    end_transform: Transform,
+    start_time: Float,
+    end_time: Float,
+    decomposed: Option<Box<[DecomposedTransform; 2]>>,
+}
+
+impl AnimatedTransform {
+    pub fn new(
+        start_transform: Transform,
+        start_time: Float,
+        end_transform: Transform,
+        end_time: Float,
+    ) -> Self {
+        let decomposed = if start_transform.m != end_transform.m && start_time < end_time {
+            let start = DecomposedTransform::new(&start_transform.m);
+            let mut end = DecomposedTransform::new(&end_transform.m);
+            // take the shortest path between the rotations
+            if start.r.dot(&end.r) < 0. {
+                end.r = -end.r;
+            }
+            Some(Box::new([start, end]))
+        } else {
+            None
+        };
+        Self {
+            start_transform,
+            end_transform,
+            start_time,
+            end_time,
+            decomposed,
+        }
+    }
+    pub fn is_animated(&self) -> bool {
+        self.decomposed.is_some()
+    }
+    pub fn start_transform(&self) -> &Transform {
+        &self.start_transform
+    }
+    pub fn interpolate(&self, time: Float) -> Transform {
+        let decomposed = match &self.decomposed {
+            Some(decomposed) => decomposed,
+            None => return self.start_transform.clone(),
+        };
+        if time <= self.start_time {
+            return self.start_transform.clone();
+        }
+        if time >= self.end_time {
+            return self.end_transform.clone();
+        }
+        let dt = (time - self.start_time) / (self.end_time - self.start_time);
+        let [start, end] = decomposed.as_ref();
+        let t = start.t * (1. - dt) + end.t * dt;
+        let r = UnitQuaternion::from_quaternion(slerp(dt, &start.r, &end.r));
+        let s = start.s * (1. - dt) + end.s * dt;
+        let m = Matrix4::new_translation(&t) * r.to_homogeneous() * s.to_homogeneous();
+        Transform::from(m)
+    }
+    // bound of a box moving along with the transform over the whole time range
+    pub fn motion_bound(&self, bound: &Bounds3f) -> Bounds3f {
+        if !self.is_animated() {
+            return bound.clone().apply(&self.start_transform);
+        }
+        let steps = 128;
+        let mut motion_bound = bound.clone().apply(&self.start_transform);
+        for i in 1..steps {
+            let t = i as Float / (steps - 1) as Float;
+            let time = self.start_time * (1. - t) + self.end_time * t;
+            motion_bound |= bound.clone().apply(&self.interpolate(time));
+        }
+        // between two samples a point stays within half a step of travel of one of them
+        motion_bound.expand(0.5 * self.max_speed(bound) / (steps - 1) as Float)
+    }
+    // how far a point of the box can move per unit of the interpolation parameter
+    fn max_speed(&self, bound: &Bounds3f) -> Float {
+        let [start, end] = match &self.decomposed {
+            Some(decomposed) => decomposed.as_ref(),
+            None => return 0.,
+        };
+        // the rotation angle is twice the angle between the quaternions
+        let angle = 2. * clamp(start.r.dot(&end.r) / (start.r.norm() * end.r.norm()), -1., 1.).acos();
+        let (mut radius, mut scaling) = (0., 0.);
+        // both are convex in the point, so they peak at a corner
+        for i in 0..8 {
+            let p = bound.corner(i).coords;
+            let (p0, p1) = (start.s * p, end.s * p);
+            radius = max(radius, max(p0.magnitude(), p1.magnitude()));
+            scaling = max(scaling, (p1 - p0).magnitude());
+        }
+        (end.t - start.t).magnitude() + angle * radius + scaling
+    }
+    pub fn inverse(self) -> Self {
+        Self::new(
+            self.start_transform.inverse(),
+            self.start_time,
+            self.end_transform.inverse(),
+            self.end_time,
+        )
+    }
+}
+
+impl From<Transform> for AnimatedTransform {
+    fn from(transform: Transform) -> Self {
+        Self::new(transform.clone(), 0., transform, 0.)
+    }
+}
+
+impl Default for AnimatedTransform {
+    fn default() -> Self {
+        Self::from(Transform::default())
+    }
+}
+
+impl Transformable for AnimatedTransform {
+    fn apply(self, transform: &Transform) -> Self {
+        Self::new(
+            self.start_transform.apply(transform),
+            self.start_time,
+            self.end_transform.apply(transform),
+            self.end_time,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ActiveTransform {
+    Start,
+    End,
+    #[default]
+    All,
+}
+
+// the current transform while parsing, one for each end of the shutter interval
+#[derive(Debug, Clone, Default)]
+pub struct TransformState {
+    start: Option<Transform>,
+    end: Option<Transform>,
+    active: ActiveTransform,
+}
+
+impl TransformState {
+    // returns false if the segment is not a transform directive
+    pub fn parse(&mut self, segment: &BlockSegment) -> SceneResult<bool> {
+        let (object_type, property_set) = match segment.get_object() {
+            Some(object) => object,
+            None => return Ok(false),
+        };
+        if object_type == "ActiveTransform" {
+            self.active = match property_set.get_name()? {
+                "StartTime" => ActiveTransform::Start,
+                "EndTime" => ActiveTransform::End,
+                "All" => ActiveTransform::All,
+                active => {
+                    return Err(property_set.error(&format!("Unknown active transform '{}'", active)))
+                }
+            };
+            return Ok(true);
+        }
+        let this_transform = match Transform::parse_from_segment(segment)? {
+            Some(this_transform) => this_transform,
+            None => return Ok(false),
+        };
+        let replace = object_type == "Transform";
+        let update = |transform: &mut Option<Transform>| {
+            *transform = Some(match transform.take() {
+                Some(transform) if !replace => transform.apply(&this_transform),
+                _ => this_transform.clone(),
+            });
+        };
+        if self.active != ActiveTransform::End {
+            update(&mut self.start);
+        }
+        if self.active != ActiveTransform::Start {
+            update(&mut self.end);
+        }
+        Ok(true)
+    }
+    pub fn start(&self) -> Option<&Transform> {
+        self.start.as_ref()
+    }
+    pub fn animated(&self, times: &TransformTimes) -> Option<AnimatedTransform> {
+        if self.start.is_none() && self.end.is_none() {
+            return None;
+        }
+        let start = self.start.clone().unwrap_or_default();
+        let end = self.end.clone().unwrap_or_default();
+        Some(AnimatedTransform::new(start, times.start, end, times.end))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TransformTimes {
+    pub start: Float,
+    pub end: Float,
+}
+
+impl Default for TransformTimes {
+    fn default() -> Self {
+        Self { start: 0., end: 1. }
+    }
+}
+
+impl ParseFromBlockSegment<'_> for TransformTimes {
+    type T = TransformTimes;
+    fn parse_from_segment(segment: &BlockSegment) -> SceneResult<Option<Self::T>> {
+        let property_set = match segment.get_object_by_type("TransformTimes") {
+            Some(property_set) => property_set,
+            None => return Ok(None),
+        };
+        let mut property_set = property_set.clone();
+        let start = property_set.get_no_type_value()?;
+        let end = property_set.get_no_type_value()?;
+        if start > end {
+            return Err(property_set.error("Transform end time is before start time"));
+        }
+        Ok(Some(Self { start, end }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn animated_transform_interpolates() {
+        let start = Transform::translate(Vector3f::new(-2., 0., 0.));
+        let end = Transform::rotate(90., Vector3f::new(0., 0., 1.))
+            .apply(&Transform::translate(Vector3f::new(2., 0., 0.)));
+        let animated = AnimatedTransform::new(start, 0., end, 1.);
+        let p = Point3f::new(1., 0., 0.).apply(&animated.interpolate(0.5));
+        let expected = Point3f::new(1., 0., 0.).apply(&Transform::rotate(45., Vector3f::new(0., 0., 1.)));
+        assert!((p - expected).magnitude() < 1e-4);
+        let bound = animated.motion_bound(&Bounds3f::from(Point3f::new(1., 0., 0.)));
+        assert!(bound.inside(&p));
+    }
+    #[test]
+    fn motion_bound_covers_rotation() {
+        let start = Transform::default();
+        let end = Transform::rotate(170., Vector3f::new(0., 0., 1.));
+        let animated = AnimatedTransform::new(start, 0., end, 1.);
+        let bound = Bounds3f::new(&Point3f::new(90., -10., -1.), &Point3f::new(110., 10., 1.));
+        let motion_bound = animated.motion_bound(&bound);
+        for i in 0..=10000 {
+            let transform = animated.interpolate(i as Float / 10000.);
+            for corner in 0..8 {
+                let p = bound.corner(corner).apply(&transform);
+                assert!(motion_bound.inside(&p), "{:?} outside {}", p, motion_bound);
+            }
+        }
+    }
+}
@@ -272,6 +272,15 @@ impl Bounds2u {
         }
         r
     }
+    pub fn index_inside_rows(&self) -> Vec<Point2u> {
+        let mut r = Vec::new();
+        for j in self.min.y..self.max.y {
+            for i in self.min.x..self.max.x {
+                r.push(Point2u::new(i, j))
+            }
+        }
+        r
+    }
     pub fn point_to_offset(&self, point: &Point2u) -> usize {
         let o = point - self.min;
         o.x + o.y * self.diagonal().x
@@ -300,9 +309,37 @@ impl Transformable for Bounds3f {
 }
 
 impl Bounds3f {
+    pub fn bounding_sphere(&self) -> (Point3f, Float) {
+        let center = self.center();
+        let radius = if self.inside(&center) {
+            (self.max - center).magnitude()
+        } else {
+            0.
+        };
+        (center, radius)
+    }
     pub fn intersect_predicate(&self, ray: &Ray) -> bool {
         self.intersect_predicate_cached(&RayIntersectCache::from(*ray))
     }
+    // the parametric range of the ray inside the bounds, clipped to [0, ray.t_max]
+    pub fn intersect(&self, ray: &Ray) -> Option<(Float, Float)> {
+        let (mut t0, mut t1) = (0., ray.t_max);
+        for i in 0..3 {
+            let inverse_d = 1. / ray.d[i];
+            let mut t_near = (self.min[i] - ray.o[i]) * inverse_d;
+            let mut t_far = (self.max[i] - ray.o[i]) * inverse_d;
+            if t_near > t_far {
+                std::mem::swap(&mut t_near, &mut t_far);
+            }
+            t_far *= 1. + 2. * gamma(3);
+            t0 = max(t0, t_near);
+            t1 = min(t1, t_far);
+            if t0 > t1 {
+                return None;
+            }
+        }
+        Some((t0, t1))
+    }
     fn intersect_component(&self, ray: &RayIntersectCache, c: usize) -> (Float, Float) {
         (
             (self[ray.is_negative_d[c]][c] - ray.ray.o[c]) * ray.inverse_d[c],
@@ -1,3 +1,4 @@
+mod animated_transform;
 mod bounds;
 mod normal;
 mod point;
@@ -6,6 +7,7 @@ mod shape;
 mod transform;
 mod vector;
 use crate::*;
+pub use animated_transform::*;
 pub use bounds::*;
 pub use normal::*;
 pub use point::*;
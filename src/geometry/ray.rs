@@ -8,11 +8,12 @@ pub struct Ray {
     pub o: Point3f,
     pub d: Vector3f,
     pub t_max: Float,
+    pub time: Float,
 }
 
 impl Display for Ray {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "o: {} d: {} t_max: {}", self.o, self.d, self.t_max)
+        write!(f, "o: {} d: {} t_max: {} time: {}", self.o, self.d, self.t_max, self.time)
     }
 }
 
@@ -22,20 +23,24 @@ impl Ray {
             o,
             d,
             t_max: Float::max_value(),
+            time: 0.,
         }
     }
     pub fn new_shape_point_d(o: &ShapePoint, d: Vector3f) -> Self {
-        Self::new_od(o.point_offset_by_error(&d), d)
+        Self::new_od(o.point_offset_by_error(&d), d).with_time(o.time)
     }
     pub fn from_to(from: Point3f, to: Point3f) -> Self {
         let o = from;
         let d = to - from;
         let a_bit = 0.00001;
         let t_max = 1. - a_bit;
-        Self { o, d, t_max }
+        Self { o, d, t_max, time: 0. }
     }
     pub fn new(o: Point3f, d: Vector3f, t_max: Float) -> Self {
-        Self { o, d, t_max }
+        Self { o, d, t_max, time: 0. }
+    }
+    pub fn with_time(self, time: Float) -> Self {
+        Self { time, ..self }
     }
     pub fn eval(&self, t: Float) -> Point3f {
         self.o + self.d * t
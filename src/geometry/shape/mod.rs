@@ -14,19 +14,19 @@ pub use triangle::*;
 pub trait Shape: DowncastSync + std::fmt::Debug {
     fn area(&self) -> Float;
     fn bound(&self) -> Bounds3f;
-    fn sample(&self, sampler: &mut dyn Sampler) -> (ShapePoint, Float);
+    fn sample(&self, u: &Point2f) -> (ShapePoint, Float);
     fn pdf(&self, _: &ShapePoint) -> Float {
         1. / self.area()
     }
-    fn sample_by_point(&self, point: &Point3f, sampler: &mut dyn Sampler) -> (ShapePoint, Float) {
-        self.default_sample_by_point(point, sampler)
+    fn sample_by_point(&self, point: &Point3f, u: &Point2f) -> (ShapePoint, Float) {
+        self.default_sample_by_point(point, u)
     }
     fn default_sample_by_point(
         &self,
         point: &Point3f,
-        sampler: &mut dyn Sampler,
+        u: &Point2f,
     ) -> (ShapePoint, Float) {
-        let (shape_point, pdf) = self.sample(sampler);
+        let (shape_point, pdf) = self.sample(u);
         let wi = shape_point.p - point;
         if wi.magnitude_squared() == 0. {
             (shape_point, 0.)
@@ -90,10 +90,10 @@ pub fn shape_apply(shape: Arc<dyn Shape>, transform: &Transform) -> Arc<dyn Shap
     }
 }
 
-pub fn shapes_apply(shapes: Vec<Arc<dyn Shape>>, transform: Transform) -> Vec<Arc<dyn Shape>> {
+pub fn shapes_apply(shapes: Vec<Arc<dyn Shape>>, transform: AnimatedTransform) -> Vec<Arc<dyn Shape>> {
     let mut r: Vec<Arc<dyn Shape>> = Vec::new();
     for shape in shapes {
-        r.push(Arc::new(TransformShape::new(shape, transform.clone())));
+        r.push(Arc::new(TransformShape::new_animated(shape, transform.clone())));
     }
     r
 }
@@ -103,6 +103,7 @@ pub struct ShapePoint {
     pub p: Point3f,
     pub n: Normal3f,
     pub uv: Point2f,
+    pub time: Float,
     p_error: Vector3f,
 }
 
@@ -114,7 +115,13 @@ impl ShapePoint {
         Self::new(p, n, Point2f::new(0., 0.), p_error)
     }
     pub fn new(p: Point3f, n: Normal3f, uv: Point2f, p_error: Vector3f) -> Self {
-        Self { p, n, uv, p_error }
+        Self {
+            p,
+            n,
+            uv,
+            time: 0.,
+            p_error,
+        }
     }
     pub fn point_offset_by_error(&self, w: &Vector3f) -> Point3f {
         let d: Float = self.n.as_ref().abs().dot(&self.p_error);
@@ -154,6 +161,7 @@ impl Transformable for ShapePoint {
             p,
             n,
             uv: self.uv,
+            time: self.time,
             p_error,
         }
     }
@@ -184,6 +192,10 @@ impl ShapeIntersect {
     pub fn get_t(&self) -> Float {
         self.t
     }
+    pub fn with_time(mut self, time: Float) -> Self {
+        self.p.time = time;
+        self
+    }
 }
 
 impl Transformable for ShapeIntersect {
@@ -195,22 +207,33 @@ impl Transformable for ShapeIntersect {
     }
 }
 
-pub fn parse_shape(property_set: &PropertySet) -> Vec<Arc<dyn Shape>> {
-    match property_set.get_name().unwrap() {
+pub fn parse_shape(property_set: &PropertySet) -> SceneResult<Vec<Arc<dyn Shape>>> {
+    Ok(match property_set.get_name()? {
         "sphere" => {
-            let radius = property_set.get_value("radius").unwrap_or(1.);
+            let radius = property_set.get_value("radius")?.unwrap_or(1.);
             vec![Arc::new(Sphere::new(radius))]
         }
         "trianglemesh" => {
-            let indices = property_set.get_value("indices").unwrap();
-            let vertices = property_set.get_value("P").unwrap();
+            let indices: Vec<usize> = property_set.get_required("indices")?;
+            let vertices: Vec<Point3f> = property_set.get_required("P")?;
+            if !indices.len().is_multiple_of(3) {
+                return Err(property_set.error("Number of indices is not a multiple of 3"));
+            }
+            if indices.iter().any(|i| *i >= vertices.len()) {
+                return Err(property_set.error("Index out of range of vertices"));
+            }
             let triangle_mesh = TriangleMesh::new(indices, vertices, None, None).into();
             create_triangles(triangle_mesh)
         }
         "plymesh" => {
-            let path: PathBuf = property_set.get_value("filename").unwrap();
+            let path: PathBuf = property_set.get_required("filename")?;
+            if !path.is_file() {
+                return Err(property_set.error(&format!("Cant find ply file {}", path.display())));
+            }
             create_plymesh(&path)
         }
-        _ => panic!(),
-    }
+        shape_type => {
+            return Err(property_set.error(&format!("Unknown shape '{}'", shape_type)));
+        }
+    })
 }
@@ -51,8 +51,8 @@ impl Shape for Sphere {
             ))
         }
     }
-    fn sample(&self, sampler: &mut dyn Sampler) -> (ShapePoint, Float) {
-        let d = uniform_sample_hemisphere(sampler.get_2d());
+    fn sample(&self, u: &Point2f) -> (ShapePoint, Float) {
+        let d = uniform_sample_hemisphere(*u);
         let p = Point3f::from(d * self.radius);
         let n = Normal3f::from(d);
         let p = p * (self.radius / p.coords.magnitude());
@@ -63,18 +63,16 @@ impl Shape for Sphere {
         )
     }
     #[allow(clippy::many_single_char_names)]
-    fn sample_by_point(&self, point: &Point3f, sampler: &mut dyn Sampler) -> (ShapePoint, Float) {
+    fn sample_by_point(&self, point: &Point3f, u: &Point2f) -> (ShapePoint, Float) {
         let distance_2 = point.coords.magnitude_squared();
         let radius_2 = self.radius * self.radius;
         if distance_2 <= radius_2 {
-            return self.default_sample_by_point(point, sampler);
+            return self.default_sample_by_point(point, u);
         }
         let distance = distance_2.sqrt();
         let z = point.coords / distance;
         let (x, y) = coordinate_system(&z);
 
-        let u = sampler.get_2d();
-
         let sin_theta_max_2 = radius_2 / distance_2;
         let cos_theta_max = max(1. - sin_theta_max_2, 0.).sqrt();
         let cos_theta = (1. - u.x) + u.x * cos_theta_max;
@@ -108,7 +106,7 @@ impl Shape for Sphere {
     }
 }
 
-fn solve_quadratic(a: Float, b: Float, c: Float) -> Option<(Float, Float)> {
+pub fn solve_quadratic(a: Float, b: Float, c: Float) -> Option<(Float, Float)> {
     let a = a as Double;
     let b = b as Double;
     let c = c as Double;
@@ -1,61 +1,71 @@
 use super::{Shape, ShapeIntersect, ShapePoint};
 use crate::{
     def::Float,
-    geometry::{Bounds3f, Point3f, Ray, Transform, Transformable},
-    sampler::Sampler,
+    geometry::{AnimatedTransform, Bounds3f, Point2f, Point3f, Ray, Transform, Transformable},
     Vector3f,
 };
 use std::sync::Arc;
 
+// sampling and pdfs use the transform at the start of the shutter interval,
+// only intersections follow the animation
 #[derive(Debug, Clone)]
 pub struct TransformShape {
     shape: Arc<dyn Shape>,
+    animated_transform: AnimatedTransform,
     transform: Transform,
+    bound: Bounds3f,
 }
 
 impl TransformShape {
     pub fn new(shape: Arc<dyn Shape>, transform: Transform) -> Self {
+        Self::new_animated(shape, transform.into())
+    }
+    pub fn new_animated(shape: Arc<dyn Shape>, animated_transform: AnimatedTransform) -> Self {
+        let bound = animated_transform.motion_bound(&shape.bound());
         Self {
             shape,
-            transform,
+            transform: animated_transform.start_transform().clone(),
+            animated_transform,
+            bound,
         }
     }
     pub fn inverse_transform(&self) -> Transform {
         self.transform.clone().inverse()
     }
+    fn transform_at(&self, time: Float) -> Transform {
+        if self.animated_transform.is_animated() {
+            self.animated_transform.interpolate(time)
+        } else {
+            self.transform.clone()
+        }
+    }
 }
 
 impl From<Arc<dyn Shape>> for TransformShape {
     fn from(shape: Arc<dyn Shape>) -> Self {
-        Self {
-            shape,
-            transform: Transform::default(),
-        }
+        Self::new(shape, Transform::default())
     }
 }
 
 impl Transformable for TransformShape {
-    fn apply(self, transform_: &Transform) -> Self {
-        let transform = self.transform.apply(transform_);
-        Self {
-            shape: self.shape,
-            transform,
-        }
+    fn apply(self, transform: &Transform) -> Self {
+        Self::new_animated(self.shape, self.animated_transform.apply(transform))
     }
 }
 
 impl Shape for TransformShape {
     fn intersect_predicate(&self, ray: &Ray) -> bool {
-        self.shape
-            .intersect_predicate(&ray.apply(&self.inverse_transform()))
+        let inverse_transform = self.transform_at(ray.time).inverse();
+        self.shape.intersect_predicate(&ray.apply(&inverse_transform))
     }
     fn bound(&self) -> Bounds3f {
-        self.shape.bound().apply(&self.transform)
+        self.bound.clone()
     }
     fn intersect(&self, ray: &Ray) -> Option<ShapeIntersect> {
+        let transform = self.transform_at(ray.time);
         let intersect: Option<ShapeIntersect> =
-            self.shape.intersect(&ray.apply(&self.inverse_transform()));
-        Some(intersect?.apply(&self.transform))
+            self.shape.intersect(&ray.apply(&transform.clone().inverse()));
+        Some(intersect?.apply(&transform))
     }
     fn by_point_pdf(&self, point: &Point3f, shape_point: &ShapePoint) -> Float {
         self.shape.by_point_pdf(
@@ -63,16 +73,16 @@ impl Shape for TransformShape {
             &shape_point.apply(&self.inverse_transform()),
         )
     }
-    fn sample_by_point(&self, point: &Point3f, sampler: &mut dyn Sampler) -> (ShapePoint, Float) {
+    fn sample_by_point(&self, point: &Point3f, u: &Point2f) -> (ShapePoint, Float) {
         let point = point.apply(&self.inverse_transform());
-        let (shape_point, pdf) = self.shape.sample_by_point(&point, sampler);
+        let (shape_point, pdf) = self.shape.sample_by_point(&point, u);
         (shape_point.apply(&self.transform), pdf)
     }
     fn pdf(&self, shape_point: &ShapePoint) -> Float {
         self.shape.pdf(&shape_point.apply(&self.inverse_transform()))
     }
-    fn sample(&self, sampler: &mut dyn Sampler) -> (ShapePoint, Float) {
-        let (shape_point, pdf) = self.shape.sample(sampler);
+    fn sample(&self, u: &Point2f) -> (ShapePoint, Float) {
+        let (shape_point, pdf) = self.shape.sample(u);
         (shape_point.apply(&self.transform), pdf)
     }
     fn area(&self) -> Float {
@@ -81,11 +91,11 @@ impl Shape for TransformShape {
     fn default_sample_by_point(
         &self,
         point: &Point3f,
-        sampler: &mut dyn Sampler,
+        u: &Point2f,
     ) -> (ShapePoint, Float) {
         let (shape_point, pdf) = self
             .shape
-            .default_sample_by_point(&point.apply(&self.inverse_transform()), sampler);
+            .default_sample_by_point(&point.apply(&self.inverse_transform()), u);
         (shape_point.apply(&self.transform), pdf)
     }
     fn default_by_point_pdf(&self, point: &Point3f, shape_point: &ShapePoint) -> Float {
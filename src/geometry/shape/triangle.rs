@@ -117,8 +117,8 @@ impl Shape for Triangle {
         bound = bound | &p2;
         bound
     }
-    fn sample(&self, sampler: &mut dyn Sampler) -> (ShapePoint, Float) {
-        let b = uniform_sample_triangle(sampler.get_2d());
+    fn sample(&self, u: &Point2f) -> (ShapePoint, Float) {
+        let b = uniform_sample_triangle(*u);
         let (b0, b1, b2) = (b.x, b.y, 1. - b.x - b.y);
         let (p, n, uv) = self.shape_point_interpolate(b0, b1, b2);
         let p_error = gamma(6) * self.abs_sum(b0, b1, b2);
@@ -122,6 +122,10 @@ impl Transform {
             0.,
         ))
     }
+    pub fn orthographic(near: Float, far: Float) -> Self {
+        Self::translate(Vector3f::new(0., 0., -near))
+            .apply(&Self::scale(Vector3f::new(1., 1., 1. / (far - near))))
+    }
 }
 fn is_norm(v: Vector3f) -> bool {
     let l = v.magnitude();
@@ -152,10 +156,14 @@ impl Transformable for Transform {
 }
 
 impl ParseFromProperty for Transform {
-    fn parse_from_property(type_name: &str, basic_types: &BasicTypes) -> Self {
-        let fs = Vec::parse_from_property(type_name, basic_types);
+    fn parse_from_property(type_name: &str, basic_types: &BasicTypes) -> Option<Self> {
+        let fs: Vec<Float> = Vec::parse_from_property(type_name, basic_types)?;
+        if fs.len() != 16 {
+            return None;
+        }
         let m = Matrix4::from_vec(fs);
-        Self::from(m)
+        let m_inv = m.try_inverse()?;
+        Some(Self::new(m, m_inv))
     }
     fn parse_default() -> Self {
         Transform::default()
@@ -170,26 +178,27 @@ impl ParseConsumeProperty for Transform {
 
 impl ParseFromBlockSegment<'_> for Transform {
     type T = Transform;
-    fn parse_from_segment(segment: &BlockSegment) -> Option<Self::T> {
-        let (transform_type, property_set) = segment.get_object()?;
+    fn parse_from_segment(segment: &BlockSegment) -> SceneResult<Option<Self::T>> {
+        let (transform_type, property_set) = match segment.get_object() {
+            Some(object) => object,
+            None => return Ok(None),
+        };
         let mut property_set = property_set.clone();
-        match transform_type {
-            "ConcatTransform" => Some(property_set.get_no_type_value().unwrap()),
-            "Transform" => Some(property_set.get_no_type_value().unwrap()),
-            "Translate" => Some(Transform::translate(
-                property_set.get_no_type_value().unwrap(),
-            )),
+        Ok(match transform_type {
+            "ConcatTransform" => Some(property_set.get_no_type_value()?),
+            "Transform" => Some(property_set.get_no_type_value()?),
+            "Translate" => Some(Transform::translate(property_set.get_no_type_value()?)),
             "Rotate" => Some(Transform::rotate(
-                property_set.get_no_type_value().unwrap(),
-                property_set.get_no_type_value().unwrap(),
+                property_set.get_no_type_value()?,
+                property_set.get_no_type_value()?,
             )),
-            "Scale" => Some(Transform::scale(property_set.get_no_type_value().unwrap())),
+            "Scale" => Some(Transform::scale(property_set.get_no_type_value()?)),
             "LookAt" => Some(Transform::look_at(
-                property_set.get_no_type_value().unwrap(),
-                property_set.get_no_type_value().unwrap(),
-                property_set.get_no_type_value().unwrap(),
+                property_set.get_no_type_value()?,
+                property_set.get_no_type_value()?,
+                property_set.get_no_type_value()?,
             )),
             _ => None,
-        }
+        })
     }
 }
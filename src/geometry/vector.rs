@@ -15,9 +15,12 @@ impl Transformable for Vector3f {
 }
 
 impl ParseFromProperty for Vector3f {
-    fn parse_from_property(_: &str, basic_type: &BasicTypes) -> Self {
-        let floats = basic_type.get_floats().unwrap();
-        Vector3f::new(floats[0], floats[1], floats[2])
+    fn parse_from_property(_: &str, basic_type: &BasicTypes) -> Option<Self> {
+        let floats = basic_type.get_floats()?;
+        if floats.len() != 3 {
+            return None;
+        }
+        Some(Vector3f::new(floats[0], floats[1], floats[2]))
     }
     fn parse_default() -> Self {
         Vector3f::new(0.,0.,0.)
@@ -0,0 +1,700 @@
+use crate::*;
+use std::sync::Arc;
+
+enum VertexKind {
+    Camera,
+    // a light at infinity if None, that is where camera subpaths leaving the scene end
+    Light(Option<Arc<dyn Light>>),
+    Surface(BSDF),
+    // the surface of an area light reached by a camera subpath, lights absorb what reaches them
+    Emitter(Arc<dyn Light>),
+}
+
+// a vertex of a camera or light subpath, the pdfs are by area, forward along the subpath and in reverse
+struct Vertex {
+    kind: VertexKind,
+    point: ShapePoint,
+    // point lights and the pinhole of camera rays have no surface to convert densities with
+    on_surface: bool,
+    wo: Vector3f,
+    beta: Spectrum,
+    delta: bool,
+    pdf_fwd: Float,
+    pdf_rev: Float,
+}
+
+impl Vertex {
+    fn new(kind: VertexKind, point: ShapePoint, on_surface: bool, beta: Spectrum) -> Self {
+        Self {
+            kind,
+            point,
+            on_surface,
+            wo: Vector3f::new(0., 0., 0.),
+            beta,
+            delta: false,
+            pdf_fwd: 0.,
+            pdf_rev: 0.,
+        }
+    }
+    fn p(&self) -> Point3f {
+        self.point.p
+    }
+    fn light(&self) -> Option<&Arc<dyn Light>> {
+        match &self.kind {
+            VertexKind::Light(light) => light.as_ref(),
+            VertexKind::Emitter(light) => Some(light),
+            _ => None,
+        }
+    }
+    fn is_light(&self) -> bool {
+        matches!(self.kind, VertexKind::Light(_) | VertexKind::Emitter(_))
+    }
+    fn is_delta_light(&self) -> bool {
+        match &self.kind {
+            VertexKind::Light(Some(light)) => light.is_delta(),
+            _ => false,
+        }
+    }
+    fn is_infinite_light(&self) -> bool {
+        match &self.kind {
+            VertexKind::Light(None) => true,
+            VertexKind::Light(Some(light)) => light.is_infinite(),
+            _ => false,
+        }
+    }
+    fn is_connectible(&self) -> bool {
+        match &self.kind {
+            VertexKind::Camera => true,
+            // a distant light has a fixed direction, nothing can connect to it
+            VertexKind::Light(light) => !matches!(light, Some(light) if light.is_delta() && light.is_infinite()),
+            VertexKind::Surface(bsdf) => !bsdf.is_all_delta(),
+            VertexKind::Emitter(_) => false,
+        }
+    }
+    fn f(&self, next: &Vertex) -> Spectrum {
+        match &self.kind {
+            VertexKind::Surface(bsdf) => bsdf.f(&self.wo, &(next.p() - self.p()).normalize()),
+            _ => Spectrum::new(0.),
+        }
+    }
+    // a pdf by solid angle at this vertex as a pdf by area at next
+    fn convert_density(&self, pdf: Float, next: &Vertex) -> Float {
+        if next.is_infinite_light() {
+            return pdf;
+        }
+        let w = next.p() - self.p();
+        let distance_2 = w.magnitude_squared();
+        if distance_2 == 0. {
+            return 0.;
+        }
+        let pdf = pdf / distance_2;
+        if next.on_surface {
+            pdf * next.point.n.dot(&(w / distance_2.sqrt())).abs()
+        } else {
+            pdf
+        }
+    }
+    // the pdf by area of sampling next from this vertex, which was reached from prev
+    fn pdf(&self, scene: &Scene, camera: Option<&dyn Camera>, prev: Option<&Vertex>, next: &Vertex) -> Float {
+        let wn = next.p() - self.p();
+        if wn.magnitude_squared() == 0. {
+            return 0.;
+        }
+        let wn = wn.normalize();
+        let pdf = match &self.kind {
+            VertexKind::Light(_) | VertexKind::Emitter(_) => return self.pdf_light(scene, next),
+            VertexKind::Camera => camera.map_or(0., |camera| {
+                camera.pdf_we(&Ray::new_od(self.p(), wn).with_time(self.point.time)).1
+            }),
+            VertexKind::Surface(bsdf) => match prev {
+                Some(prev) => bsdf.pdf(&(prev.p() - self.p()).normalize(), &wn),
+                None => 0.,
+            },
+        };
+        self.convert_density(pdf, next)
+    }
+    // the pdf by area of next being the second vertex of a light subpath starting here
+    fn pdf_light(&self, scene: &Scene, next: &Vertex) -> Float {
+        let w = next.p() - self.p();
+        let distance_2 = w.magnitude_squared();
+        if distance_2 == 0. {
+            return 0.;
+        }
+        let w = w / distance_2.sqrt();
+        let world_bound = scene.world_bound();
+        let pdf = if self.is_infinite_light() {
+            let (_, radius) = world_bound.bounding_sphere();
+            1. / (PI * radius * radius)
+        } else {
+            match self.light() {
+                Some(light) => light.pdf_le(&Ray::new_od(self.p(), w), &self.point.n, &world_bound).1 / distance_2,
+                None => 0.,
+            }
+        };
+        if next.on_surface {
+            pdf * next.point.n.dot(&w).abs()
+        } else {
+            pdf
+        }
+    }
+    // the pdf by area of a light subpath starting at this vertex, toward next
+    fn pdf_light_origin(&self, scene: &Scene, next: &Vertex) -> Float {
+        let w = (next.p() - self.p()).normalize();
+        if self.is_infinite_light() {
+            return infinite_light_density(scene, &-w);
+        }
+        match self.light() {
+            Some(light) => {
+                let (pdf_pos, _) = light.pdf_le(&Ray::new_od(self.p(), w), &self.point.n, &scene.world_bound());
+                pdf_pos / scene.get_lights().len() as Float
+            }
+            None => 0.,
+        }
+    }
+    // the light leaving this vertex toward next
+    fn le(&self, scene: &Scene, next: &Vertex) -> Spectrum {
+        let mut le = Spectrum::new(0.);
+        match &self.kind {
+            VertexKind::Light(None) => {
+                let ray = Ray::new_od(next.p(), (self.p() - next.p()).normalize());
+                for light in scene.get_lights() {
+                    if let Some(light_le) = light.le_out_scene(&ray) {
+                        le += light_le;
+                    }
+                }
+            }
+            VertexKind::Emitter(light) => {
+                if let Some(light_le) = light.le(&self.point) {
+                    le = light_le;
+                }
+            }
+            _ => (),
+        }
+        le
+    }
+}
+
+// the pdf of the light subpaths starting at infinity that arrive from direction w
+fn infinite_light_density(scene: &Scene, w: &Vector3f) -> Float {
+    let lights = scene.get_lights();
+    let ray = Ray::new_od(Point3f::new(0., 0., 0.), *w);
+    let pdf: Float = lights.iter().filter(|light| light.is_infinite()).map(|light| light.out_scene_pdf(&ray)).sum();
+    pdf / lights.len() as Float
+}
+
+// the geometry term between two vertices, zero if they cannot see each other
+fn g(scene: &Scene, v0: &Vertex, v1: &Vertex) -> Float {
+    let d = v0.p() - v1.p();
+    let distance_2 = d.magnitude_squared();
+    if distance_2 == 0. {
+        return 0.;
+    }
+    let d = d / distance_2.sqrt();
+    let mut g = 1. / distance_2;
+    if v0.on_surface {
+        g *= v0.point.n.dot(&d).abs();
+    }
+    if v1.on_surface {
+        g *= v1.point.n.dot(&d).abs();
+    }
+    if g != 0. && !VisibilityTester::new(&v0.point, &v1.point).unoccluded(scene) {
+        return 0.;
+    }
+    g
+}
+
+#[derive(Clone, Copy)]
+struct MisVertex {
+    pdf_fwd: Float,
+    pdf_rev: Float,
+    delta: bool,
+}
+
+impl From<&Vertex> for MisVertex {
+    fn from(vertex: &Vertex) -> Self {
+        Self {
+            pdf_fwd: vertex.pdf_fwd,
+            pdf_rev: vertex.pdf_rev,
+            delta: vertex.delta,
+        }
+    }
+}
+
+// bidirectional path tracing, every camera subpath is connected to a light subpath with all the strategies
+// of the same length, weighted by the power heuristic with exponent one
+pub struct BDPTIntegrator {
+    max_depth: usize,
+}
+
+impl BDPTIntegrator {
+    pub fn new(max_depth: usize) -> Self {
+        Self { max_depth }
+    }
+    #[allow(clippy::too_many_arguments)]
+    fn random_walk(
+        &self,
+        scene: &Scene,
+        sampler: &mut dyn Sampler,
+        ray: Ray,
+        beta: Spectrum,
+        pdf: Float,
+        max_depth: usize,
+        from_light: bool,
+        path: &mut Vec<Vertex>,
+    ) {
+        let mut ray = ray;
+        let mut beta = beta;
+        let mut pdf_fwd = pdf;
+        let mut bounces = 0;
+        while bounces < max_depth && !beta.is_black() {
+            let prev = path.len() - 1;
+            let intersect = match scene.intersect(&ray) {
+                Some(intersect) => intersect,
+                None => {
+                    // camera subpaths leaving the scene reach the lights at infinity
+                    if !from_light {
+                        let mut point = ShapePoint::new_p_normal(ray.o + ray.d, Normal3f::from(-ray.d));
+                        point.time = ray.time;
+                        let mut vertex = Vertex::new(VertexKind::Light(None), point, false, beta);
+                        vertex.pdf_fwd = pdf_fwd;
+                        path.push(vertex);
+                    }
+                    break;
+                }
+            };
+            let shape_point = *intersect.get_shape_point();
+            // scenes with media are rejected, so interfaces only separate vacuum from vacuum
+            if intersect.is_medium_interface() {
+                ray = Ray::new_shape_point_d(&shape_point, ray.d);
+                continue;
+            }
+            let kind = match (intersect.get_light(), intersect.compute_scattering_functions()) {
+                (Some(light), _) => VertexKind::Emitter(light),
+                (None, Some(bsdf)) => VertexKind::Surface(bsdf),
+                (None, None) => break,
+            };
+            let mut vertex = Vertex::new(kind, shape_point, true, beta);
+            vertex.wo = -ray.d.normalize();
+            vertex.pdf_fwd = path[prev].convert_density(pdf_fwd, &vertex);
+            let wo = vertex.wo;
+            let is_emitter = matches!(vertex.kind, VertexKind::Emitter(_));
+            if is_emitter && from_light {
+                break;
+            }
+            path.push(vertex);
+            bounces += 1;
+            if is_emitter || bounces >= max_depth {
+                break;
+            }
+            let current = path.len() - 1;
+            let bsdf = match &path[current].kind {
+                VertexKind::Surface(bsdf) => bsdf,
+                _ => break,
+            };
+            sampler.start_slot(if from_light {
+                DimensionSlot::LightPathBsdf(bounces - 1)
+            } else {
+                DimensionSlot::Bsdf(bounces - 1)
+            });
+            let (wi, f, f_pdf, is_delta) = bsdf.sample_f(&wo, sampler);
+            let f = match f {
+                Some(f) if f_pdf != 0. && !f.is_black() => f,
+                _ => break,
+            };
+            beta *= f * wi.dot(&shape_point.n).abs() / f_pdf;
+            // delta vertices are skipped by the weights, their pdfs stay zero
+            let pdf_rev = if is_delta {
+                pdf_fwd = 0.;
+                0.
+            } else {
+                pdf_fwd = bsdf.pdf(&wo, &wi);
+                bsdf.pdf(&wi, &wo)
+            };
+            let pdf_rev = path[current].convert_density(pdf_rev, &path[prev]);
+            path[prev].pdf_rev = pdf_rev;
+            path[current].delta = is_delta;
+            ray = Ray::new_shape_point_d(&shape_point, wi);
+        }
+    }
+    fn camera_subpath(
+        &self,
+        ray: &Ray,
+        scene: &Scene,
+        sampler: &mut dyn Sampler,
+        camera: Option<&dyn Camera>,
+        max_depth: usize,
+    ) -> Vec<Vertex> {
+        let (_, pdf_dir) = camera.map_or((0., 0.), |camera| camera.pdf_we(ray));
+        let mut point = ShapePoint::new_p_normal(ray.o, Normal3f::from(ray.d));
+        point.time = ray.time;
+        // the camera weight of the ray is applied by the caller
+        let mut path = vec![Vertex::new(VertexKind::Camera, point, false, Spectrum::new(1.))];
+        self.random_walk(scene, sampler, *ray, Spectrum::new(1.), pdf_dir, max_depth, false, &mut path);
+        path
+    }
+    fn light_subpath(&self, scene: &Scene, sampler: &mut dyn Sampler, time: Float, max_depth: usize) -> Vec<Vertex> {
+        let lights = scene.get_lights();
+        if lights.is_empty() {
+            return Vec::new();
+        }
+        sampler.start_slot(DimensionSlot::LightPathChoice);
+        let light = &lights[sampler.get_usize(lights.len())];
+        let light_pdf = 1. / lights.len() as Float;
+        sampler.start_slot(DimensionSlot::LightPathOrigin);
+        let u1 = sampler.get_2d();
+        sampler.start_slot(DimensionSlot::LightPathDirection);
+        let u2 = sampler.get_2d();
+        let (ray, n, le, pdf_pos, pdf_dir) = light.sample_le(&u1, &u2, time, &scene.world_bound());
+        let le = match le {
+            Some(le) if pdf_pos != 0. && pdf_dir != 0. && !le.is_black() => le,
+            _ => return Vec::new(),
+        };
+        let mut point = ShapePoint::new_p_normal(ray.o, n);
+        point.time = time;
+        let on_surface = !light.is_delta() && !light.is_infinite();
+        let mut vertex = Vertex::new(VertexKind::Light(Some(light.clone())), point, on_surface, le);
+        vertex.pdf_fwd = pdf_pos * light_pdf;
+        let mut path = vec![vertex];
+        let beta = le * n.dot(&ray.d).abs() / (light_pdf * pdf_pos * pdf_dir);
+        self.random_walk(scene, sampler, ray, beta, pdf_dir, max_depth, true, &mut path);
+        // the rays of lights at infinity are spread over a disk, its density is the one by area
+        if light.is_infinite() {
+            if let Some(vertex) = path.get_mut(1) {
+                vertex.pdf_fwd = pdf_pos;
+                if vertex.on_surface {
+                    vertex.pdf_fwd *= ray.d.dot(&vertex.point.n).abs();
+                }
+            }
+            path[0].pdf_fwd = infinite_light_density(scene, &-ray.d);
+        }
+        path
+    }
+    // the strategy with s light and t camera subpath vertices, and the raster point if t is one
+    #[allow(clippy::too_many_arguments)]
+    fn connect(
+        &self,
+        scene: &Scene,
+        light_path: &[Vertex],
+        camera_path: &[Vertex],
+        s: usize,
+        t: usize,
+        camera: Option<&dyn Camera>,
+        sampler: &mut dyn Sampler,
+    ) -> (Spectrum, Option<Point2f>) {
+        // a camera subpath that left the scene can only be taken as it is
+        if t > 1 && s != 0 && matches!(camera_path[t - 1].kind, VertexKind::Light(_)) {
+            return (Spectrum::new(0.), None);
+        }
+        let mut l = Spectrum::new(0.);
+        let mut sampled = None;
+        let mut raster = None;
+        if s == 0 {
+            let pt = &camera_path[t - 1];
+            if pt.is_light() {
+                l = pt.le(scene, &camera_path[t - 2]) * pt.beta;
+            }
+        } else if t == 1 {
+            let qs = &light_path[s - 1];
+            if let (true, Some(camera)) = (qs.is_connectible(), camera) {
+                sampler.start_slot(DimensionSlot::ConnectCamera(s));
+                if let Some((wi, we, pdf, p_raster, lens)) = camera.sample_wi(&qs.point, &sampler.get_2d()) {
+                    let vertex = Vertex::new(VertexKind::Camera, lens, true, Spectrum::new(we / pdf));
+                    l = qs.beta * qs.f(&vertex) * vertex.beta;
+                    if qs.on_surface {
+                        l *= wi.dot(&qs.point.n).abs();
+                    }
+                    if !l.is_black() && !VisibilityTester::new(&qs.point, &lens).unoccluded(scene) {
+                        l = Spectrum::new(0.);
+                    }
+                    raster = Some(p_raster);
+                    sampled = Some(vertex);
+                }
+            }
+        } else if s == 1 {
+            let pt = &camera_path[t - 1];
+            let lights = scene.get_lights();
+            if pt.is_connectible() && !lights.is_empty() {
+                sampler.start_slot(DimensionSlot::ConnectLight(t));
+                let light = &lights[sampler.get_usize(lights.len())];
+                let light_pdf = 1. / lights.len() as Float;
+                let u = sampler.get_2d();
+                if let (wi, Some(li), pdf, visibility_tester) = light.sample_li(&pt.point, &u) {
+                    if pdf != 0. && !li.is_black() {
+                        // lights at infinity are only a direction
+                        let (point, on_surface) = match visibility_tester.to() {
+                            Some(to) => (*to, !light.is_delta()),
+                            None => (ShapePoint::new_p_normal(pt.p() + wi, Normal3f::from(-wi)), false),
+                        };
+                        let mut vertex =
+                            Vertex::new(VertexKind::Light(Some(light.clone())), point, on_surface, li / (pdf * light_pdf));
+                        vertex.pdf_fwd = vertex.pdf_light_origin(scene, pt);
+                        l = pt.beta * pt.f(&vertex) * vertex.beta;
+                        if pt.on_surface {
+                            l *= wi.dot(&pt.point.n).abs();
+                        }
+                        if !l.is_black() && !visibility_tester.unoccluded(scene) {
+                            l = Spectrum::new(0.);
+                        }
+                        sampled = Some(vertex);
+                    }
+                }
+            }
+        } else {
+            let qs = &light_path[s - 1];
+            let pt = &camera_path[t - 1];
+            if qs.is_connectible() && pt.is_connectible() {
+                l = qs.beta * qs.f(pt) * pt.f(qs) * pt.beta;
+                if !l.is_black() {
+                    l *= g(scene, qs, pt);
+                }
+            }
+        }
+        if l.is_black() {
+            return (l, raster);
+        }
+        let weight = mis_weight(scene, camera, light_path, camera_path, sampled.as_ref(), s, t);
+        (l * weight, raster)
+    }
+    fn li_bidirectional(
+        &self,
+        ray: &Ray,
+        scene: &Scene,
+        sampler: &mut dyn Sampler,
+        camera: Option<&dyn Camera>,
+        splats: &mut Vec<(Point2f, Spectrum)>,
+    ) -> Spectrum {
+        let camera_path = self.camera_subpath(ray, scene, sampler, camera, self.max_depth + 1);
+        let light_path = self.light_subpath(scene, sampler, ray.time, self.max_depth);
+        let mut l = Spectrum::new(0.);
+        for t in 1..=camera_path.len() {
+            for s in 0..=light_path.len() {
+                if s + t < 2 || s + t - 2 > self.max_depth || (s == 1 && t == 1) {
+                    continue;
+                }
+                let (l_path, raster) = self.connect(scene, &light_path, &camera_path, s, t, camera, sampler);
+                if l_path.has_nan() || l_path.is_black() {
+                    continue;
+                }
+                match raster {
+                    Some(raster) => splats.push((raster, l_path)),
+                    None => l += l_path,
+                }
+            }
+        }
+        l
+    }
+    // one strategy chosen by the sampler for paths of depth bounces, times the number of strategies
+    // so it estimates them all, and the raster point if it connected to the lens
+    pub fn li_strategy(
+        &self,
+        ray: &Ray,
+        scene: &Scene,
+        sampler: &mut dyn Sampler,
+        camera: &dyn Camera,
+        depth: usize,
+    ) -> (Spectrum, Option<Point2f>) {
+        // paths without bounces are only seen by the camera
+        let (strategies, s) = if depth == 0 {
+            (1, 0)
+        } else {
+            sampler.start_slot(DimensionSlot::PathStrategy);
+            (depth + 2, sampler.get_usize(depth + 2))
+        };
+        let t = depth + 2 - s;
+        let camera_path = self.camera_subpath(ray, scene, sampler, Some(camera), t - 1);
+        if camera_path.len() != t {
+            return (Spectrum::new(0.), None);
+        }
+        let light_path = if s == 0 {
+            Vec::new()
+        } else {
+            self.light_subpath(scene, sampler, ray.time, s - 1)
+        };
+        if light_path.len() != s {
+            return (Spectrum::new(0.), None);
+        }
+        let (l, raster) = self.connect(scene, &light_path, &camera_path, s, t, Some(camera), sampler);
+        (l * strategies as Float, raster)
+    }
+}
+
+// the weight of the strategy with s light and t camera subpath vertices, the end point the strategy
+// sampled itself replaces the first vertex of its subpath
+fn mis_weight(
+    scene: &Scene,
+    camera: Option<&dyn Camera>,
+    light_path: &[Vertex],
+    camera_path: &[Vertex],
+    sampled: Option<&Vertex>,
+    s: usize,
+    t: usize,
+) -> Float {
+    if s + t == 2 {
+        return 1.;
+    }
+    let light_vertex = |i: usize| match sampled {
+        Some(sampled) if s == 1 && i == 0 => sampled,
+        _ => &light_path[i],
+    };
+    let camera_vertex = |i: usize| match sampled {
+        Some(sampled) if t == 1 && i == 0 => sampled,
+        _ => &camera_path[i],
+    };
+    let mut light_mis: Vec<MisVertex> = (0..s).map(|i| light_vertex(i).into()).collect();
+    let mut camera_mis: Vec<MisVertex> = (0..t).map(|i| camera_vertex(i).into()).collect();
+    let qs = if s > 0 { Some(light_vertex(s - 1)) } else { None };
+    let pt = camera_vertex(t - 1);
+    let qs_minus = if s > 1 { Some(light_vertex(s - 2)) } else { None };
+    let pt_minus = if t > 1 { Some(camera_vertex(t - 2)) } else { None };
+    // the connected end points are not delta even if the subpaths sampled them so
+    camera_mis[t - 1].delta = false;
+    // light subpaths cannot reach a camera without importance, the strategies connecting to its lens are left out
+    if t > 1 && (camera.is_none() || camera_mis[1].pdf_fwd == 0.) {
+        camera_mis[0].delta = true;
+    }
+    camera_mis[t - 1].pdf_rev = match (qs, pt_minus) {
+        (Some(qs), _) => qs.pdf(scene, camera, qs_minus, pt),
+        (None, Some(pt_minus)) => pt.pdf_light_origin(scene, pt_minus),
+        (None, None) => 0.,
+    };
+    if let Some(pt_minus) = pt_minus {
+        camera_mis[t - 2].pdf_rev = match qs {
+            Some(qs) => pt.pdf(scene, camera, Some(qs), pt_minus),
+            None => pt.pdf_light(scene, pt_minus),
+        };
+    }
+    if let Some(qs) = qs {
+        light_mis[s - 1].delta = false;
+        light_mis[s - 1].pdf_rev = pt.pdf(scene, camera, pt_minus, qs);
+        if let Some(qs_minus) = qs_minus {
+            light_mis[s - 2].pdf_rev = qs.pdf(scene, camera, Some(pt), qs_minus);
+        }
+    }
+    // a zero pdf belongs to a delta vertex, those are skipped but must not break the ratios
+    let remap0 = |f: Float| if f != 0. { f } else { 1. };
+    let mut sum_ri = 0.;
+    let mut ri = 1.;
+    for i in (1..t).rev() {
+        ri *= remap0(camera_mis[i].pdf_rev) / remap0(camera_mis[i].pdf_fwd);
+        if !camera_mis[i].delta && !camera_mis[i - 1].delta {
+            sum_ri += ri;
+        }
+    }
+    let mut ri = 1.;
+    for i in (0..s).rev() {
+        ri *= remap0(light_mis[i].pdf_rev) / remap0(light_mis[i].pdf_fwd);
+        let delta_light_vertex = if i > 0 {
+            light_mis[i - 1].delta
+        } else {
+            light_vertex(0).is_delta_light()
+        };
+        if !light_mis[i].delta && !delta_light_vertex {
+            sum_ri += ri;
+        }
+    }
+    1. / (1. + sum_ri)
+}
+
+impl Integrator for BDPTIntegrator {
+    // without a camera the strategies that connect to the lens are left out
+    fn li(&self, ray: &Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Spectrum {
+        self.li_bidirectional(ray, scene, sampler, None, &mut Vec::new())
+    }
+    fn li_with_splats(
+        &self,
+        ray: &Ray,
+        scene: &Scene,
+        sampler: &mut dyn Sampler,
+        camera: &dyn Camera,
+        splats: &mut Vec<(Point2f, Spectrum)>,
+    ) -> Spectrum {
+        self.li_bidirectional(ray, scene, sampler, Some(camera), splats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_scene::*;
+    // a camera subpath that ended on the light, with the pdfs of leaving the light filled in as a light
+    // subpath would have them, importance is the camera the integrator is given
+    fn camera_path_to_light(
+        integrator: &BDPTIntegrator,
+        scene: &Scene,
+        camera: &dyn Camera,
+        importance: Option<&dyn Camera>,
+        pixel: Point2u,
+        index: usize,
+    ) -> Option<Vec<Vertex>> {
+        let mut sampler = RandomSampler::new(1);
+        sampler.set_pixel(&pixel);
+        sampler.set_sample_index(index);
+        let film = Point2f::new(pixel.x as Float + 0.5, pixel.y as Float + 0.5);
+        let ray = camera.generate_ray(film, &mut sampler);
+        let mut path = integrator.camera_subpath(&ray, scene, &mut sampler, importance, 4);
+        let n = path.len();
+        if n < 3 || !matches!(path[n - 1].kind, VertexKind::Emitter(_)) {
+            return None;
+        }
+        path[n - 2].pdf_rev = path[n - 1].pdf_light(scene, &path[n - 2]);
+        path[n - 1].pdf_rev = path[n - 1].pdf_light_origin(scene, &path[n - 2]);
+        Some(path)
+    }
+    // the weights of the strategies that can produce a path sum to one, those connecting to the lens only
+    // count when the camera has importance
+    fn check_weights(camera_line: &str, with_camera: bool, lens_strategies: bool) {
+        let scene = box_scene(r#"Sampler "random""#, r#"Integrator "bdpt""#)
+            .replace(r#"Camera "perspective" "float fov" [40]"#, camera_line);
+        let loaded = load_test_scene("bdpt-weights", &scene, &RenderOptions::default()).unwrap();
+        let (scene, camera) = (&loaded.scene, loaded.cameras[0].as_ref());
+        let importance = if with_camera { Some(camera) } else { None };
+        let integrator = BDPTIntegrator::new(5);
+        let mut checked = 0;
+        for index in 0..256 {
+            for pixel in [Point2u::new(4, 4), Point2u::new(8, 10), Point2u::new(12, 6)] {
+                let n = match camera_path_to_light(&integrator, scene, camera, importance, pixel, index) {
+                    Some(path) => path.len(),
+                    None => continue,
+                };
+                // every split of the same path into a light and a camera subpath
+                let mut sum = 0.;
+                let max_s = if lens_strategies { n - 1 } else { n - 2 };
+                for s in 0..=max_s {
+                    let t = n - s;
+                    let mut camera_path =
+                        camera_path_to_light(&integrator, scene, camera, importance, pixel, index).unwrap();
+                    let mut light_path = camera_path.split_off(t);
+                    light_path.reverse();
+                    for vertex in &mut light_path {
+                        std::mem::swap(&mut vertex.pdf_fwd, &mut vertex.pdf_rev);
+                    }
+                    sum += mis_weight(scene, importance, &light_path, &camera_path, None, s, t);
+                }
+                assert!((sum - 1.).abs() < 1e-3, "path of {} vertices weighted {}", n, sum);
+                checked += 1;
+            }
+        }
+        assert!(checked > 10, "only {} paths reached the light", checked);
+    }
+    #[test]
+    fn strategy_weights_sum_to_one() {
+        let perspective = r#"Camera "perspective" "float fov" [40]"#;
+        check_weights(perspective, true, true);
+        // li leaves out the strategies connecting to the lens
+        check_weights(perspective, false, false);
+        // as does a camera without importance
+        check_weights(r#"Camera "orthographic""#, true, false);
+    }
+    #[test]
+    fn bdpt_matches_path_tracer() {
+        let sampler = r#"Sampler "random" "integer pixelsamples" [16]"#;
+        let render = |integrator: &str| {
+            let scene = box_scene(sampler, &format!("Integrator \"{}\" \"integer maxdepth\" [3]", integrator));
+            mean(&render_scene(integrator, &scene, &RenderOptions::default()).unwrap())
+        };
+        let (bdpt, path) = (render("bdpt"), render("path"));
+        for c in 0..3 {
+            assert!((bdpt[c] - path[c]).abs() < 0.05 * path[c], "bdpt {:?} path {:?}", bdpt, path);
+        }
+    }
+}
@@ -1,13 +1,20 @@
 use super::Integrator;
 use crate::*;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightStrategy {
+    UniformSampleAll,
+    UniformSampleOne,
+}
+
 pub struct DirectLightIntegrator {
     max_depth: usize,
+    strategy: LightStrategy,
 }
 
 impl DirectLightIntegrator {
-    pub fn new(max_depth: usize) -> Self {
-        Self { max_depth }
+    pub fn new(max_depth: usize, strategy: LightStrategy) -> Self {
+        Self { max_depth, strategy }
     }
     fn li_depth(
         &self,
@@ -22,7 +29,14 @@ impl DirectLightIntegrator {
                 let wo = -ray.d.normalize();
                 let shape_point = &intersect.get_shape_intersect().get_shape_point();
                 let n = &shape_point.n;
-                l += uniform_sample_one_light(shape_point, &bsdf, &wo, scene, sampler);
+                l += match self.strategy {
+                    LightStrategy::UniformSampleAll => {
+                        uniform_sample_all_lights(shape_point, &bsdf, &wo, scene, sampler, depth)
+                    }
+                    LightStrategy::UniformSampleOne => {
+                        uniform_sample_one_light(shape_point, &bsdf, &wo, scene, sampler, depth)
+                    }
+                };
                 if depth + 1 < self.max_depth {
                     for (wi, s) in bsdf.sample_all_delta_f(&wo) {
                         let ray = Ray::new_shape_point_d(&shape_point, wi);
@@ -45,4 +59,9 @@ impl Integrator for DirectLightIntegrator {
     fn li(&self, ray: &Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Spectrum {
         self.li_depth(ray, scene, sampler, 0)
     }
+    fn preprocess(&self, scene: &Scene, sampler: &mut dyn Sampler) {
+        if self.strategy == LightStrategy::UniformSampleAll {
+            request_light_arrays(scene, sampler, self.max_depth);
+        }
+    }
 }
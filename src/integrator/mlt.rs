@@ -0,0 +1,196 @@
+use crate::*;
+use rayon::prelude::*;
+
+// metropolis light transport in primary sample space, markov chains mutate the random numbers
+// of bidirectional paths and spend their samples where the light is
+pub struct MLTIntegrator {
+    bdpt: BDPTIntegrator,
+    max_depth: usize,
+    bootstrap_samples: usize,
+    chains: usize,
+    mutations_per_pixel: Option<usize>,
+    large_step_probability: Float,
+    sigma: Float,
+}
+
+impl MLTIntegrator {
+    pub fn new(
+        max_depth: usize,
+        bootstrap_samples: usize,
+        chains: usize,
+        mutations_per_pixel: Option<usize>,
+        large_step_probability: Float,
+        sigma: Float,
+    ) -> Self {
+        Self {
+            bdpt: BDPTIntegrator::new(max_depth),
+            max_depth,
+            bootstrap_samples,
+            chains,
+            mutations_per_pixel,
+            large_step_probability,
+            sigma,
+        }
+    }
+    fn new_sampler(&self, mutations_per_pixel: usize, seed: usize) -> MLTSampler {
+        MLTSampler::new(mutations_per_pixel, seed as u64, self.sigma, self.large_step_probability)
+    }
+    // a path of depth bounces through a raster point anywhere in the sample bound
+    fn l(
+        &self,
+        scene: &Scene,
+        camera: &dyn Camera,
+        sampler: &mut MLTSampler,
+        depth: usize,
+        sample_bound: &Bounds2f,
+    ) -> (Spectrum, Point2f) {
+        sampler.start_slot(DimensionSlot::Film);
+        let p_raster = sample_bound.lerp(sampler.get_2d());
+        let (ray, weight) = camera.generate_ray_with_weight(p_raster, sampler);
+        if weight == 0. {
+            return (Spectrum::new(0.), p_raster);
+        }
+        let (l, raster) = self.bdpt.li_strategy(&ray, scene, sampler, camera, depth);
+        let l = if l.has_nan() { Spectrum::new(0.) } else { l };
+        match raster {
+            Some(raster) => (l, raster),
+            None => (l * weight, p_raster),
+        }
+    }
+}
+
+impl Integrator for MLTIntegrator {
+    fn li(&self, ray: &Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Spectrum {
+        self.bdpt.li(ray, scene, sampler)
+    }
+    fn render(
+        &self,
+        scene: &Scene,
+        sampler: &dyn Sampler,
+        film: &Film,
+        camera: &dyn Camera,
+        quiet: bool,
+    ) -> Option<Film> {
+        let mut film = film.clone();
+        let pixel_bound = film.sample_bound();
+        let sample_bound = Bounds2f::new(
+            &Point2f::new(pixel_bound.min.x as Float, pixel_bound.min.y as Float),
+            &Point2f::new(pixel_bound.max.x as Float, pixel_bound.max.y as Float),
+        );
+        let mutations_per_pixel = self.mutations_per_pixel.unwrap_or_else(|| sampler.get_sample_per_pixel());
+        let depths = self.max_depth + 1;
+        // the bootstrap paths estimate the image brightness and where the chains start
+        let progress_bar = new_progress_bar(self.bootstrap_samples as u64, quiet, "mlt bootstrap");
+        let weights: Vec<Float> = (0..self.bootstrap_samples * depths)
+            .into_par_iter()
+            .map(|index| {
+                let mut sampler = self.new_sampler(mutations_per_pixel, index);
+                let y = self.l(scene, camera, &mut sampler, index % depths, &sample_bound).0.y();
+                if index % depths == 0 {
+                    progress_bar.inc(1);
+                }
+                if y > 0. && y.is_finite() {
+                    y
+                } else {
+                    0.
+                }
+            })
+            .collect();
+        progress_bar.finish_and_clear();
+        if weights.is_empty() {
+            return Some(film);
+        }
+        let bootstrap = Distribution1D::from(weights);
+        let b = bootstrap.f_sum() * depths as Float;
+        if b == 0. {
+            return Some(film);
+        }
+        let total_mutations = mutations_per_pixel * pixel_bound.area();
+        let chains = max(min(self.chains, total_mutations), 1);
+        let progress_bar = new_progress_bar(chains as u64, quiet, "mlt");
+        let chain_indices: Vec<usize> = (0..chains).collect();
+        // chains are merged in a fixed order so the result does not depend on thread scheduling
+        for chunk in chain_indices.chunks(rayon::current_num_threads() * 8) {
+            let chain_splats: Vec<_> = chunk
+                .par_iter()
+                .map(|&chain| {
+                    let mutations = min((chain + 1) * total_mutations / chains, total_mutations)
+                        - chain * total_mutations / chains;
+                    let mut rng = Rng::new(hash_values(&[chain as u64, chains as u64]));
+                    let (index, _, _) = bootstrap.sample_remap(rng.uniform_float());
+                    let depth = index % depths;
+                    let mut sampler = self.new_sampler(mutations_per_pixel, index);
+                    let (mut l_current, mut p_current) = self.l(scene, camera, &mut sampler, depth, &sample_bound);
+                    let mut splats = Vec::new();
+                    for _ in 0..mutations {
+                        sampler.start_iteration();
+                        let (l_proposed, p_proposed) = self.l(scene, camera, &mut sampler, depth, &sample_bound);
+                        let (y_current, y_proposed) = (l_current.y(), l_proposed.y());
+                        let accept = if y_current > 0. {
+                            min(1., y_proposed / y_current)
+                        } else {
+                            1.
+                        };
+                        // both states are splatted by their chances, which keeps the image smooth
+                        if accept > 0. && y_proposed > 0. {
+                            splats.push((p_proposed, l_proposed * (accept * b / y_proposed)));
+                        }
+                        if y_current > 0. {
+                            splats.push((p_current, l_current * ((1. - accept) * b / y_current)));
+                        }
+                        if rng.uniform_float() < accept {
+                            p_current = p_proposed;
+                            l_current = l_proposed;
+                            sampler.accept();
+                        } else {
+                            sampler.reject();
+                        }
+                    }
+                    progress_bar.inc(1);
+                    (splats, mutations)
+                })
+                .collect();
+            for (splats, mutations) in chain_splats {
+                film.add_splats(&splats, mutations);
+            }
+        }
+        progress_bar.finish_and_clear();
+        Some(film)
+    }
+    fn renders_whole_film(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_scene::*;
+    #[test]
+    fn parameters_must_be_positive() {
+        for parameter in &["integer bootstrapsamples", "integer chains", "integer mutationsperpixel", "float sigma"] {
+            for value in &[0, -1] {
+                let integrator = format!("Integrator \"mlt\" \"{}\" [{}]", parameter, value);
+                let scene = box_scene(r#"Sampler "random""#, &integrator);
+                let error = load_test_scene("mlt-parameters", &scene, &RenderOptions::default()).err().unwrap();
+                assert!(error.message.contains("must be positive"), "{}", error.message);
+            }
+        }
+    }
+    #[test]
+    fn mlt_matches_path_tracer() {
+        let render = |integrator: &str, sampler: &str| {
+            let scene = box_scene(sampler, integrator);
+            mean(&render_scene("mlt-brightness", &scene, &RenderOptions::default()).unwrap())
+        };
+        let mlt = render(
+            r#"Integrator "mlt" "integer maxdepth" [3] "integer bootstrapsamples" [4096] "integer chains" [64] "integer mutationsperpixel" [16]"#,
+            r#"Sampler "random""#,
+        );
+        let path = render(r#"Integrator "path" "integer maxdepth" [3]"#, r#"Sampler "random" "integer pixelsamples" [16]"#);
+        // the image brightness comes from the bootstrap paths, the chains only spread it over the pixels
+        for c in 0..3 {
+            assert!((mlt[c] - path[c]).abs() < 0.1 * path[c], "mlt {:?} path {:?}", mlt, path);
+        }
+    }
+}
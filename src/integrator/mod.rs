@@ -1,114 +1,319 @@
 use crate::*;
+mod bdpt;
 mod direct_light;
+mod mlt;
 mod path;
+mod sppm;
+mod volpath;
+pub use bdpt::*;
 pub use direct_light::*;
+pub use mlt::*;
 pub use path::*;
+pub use sppm::*;
+pub use volpath::*;
 use std::sync::Arc;
 
 pub trait Integrator: Sync {
     fn li(&self, ray: &Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Spectrum;
+    // integrators that trace paths from the lights also reach other pixels through the camera,
+    // that light is pushed to splats with its raster point
+    fn li_with_splats(
+        &self,
+        ray: &Ray,
+        scene: &Scene,
+        sampler: &mut dyn Sampler,
+        _camera: &dyn Camera,
+        _splats: &mut Vec<(Point2f, Spectrum)>,
+    ) -> Spectrum {
+        self.li(ray, scene, sampler)
+    }
+    // integrators that need the whole film at once, like photon mapping, render it here instead of
+    // one camera sample at a time
+    fn render(
+        &self,
+        _scene: &Scene,
+        _sampler: &dyn Sampler,
+        _film: &Film,
+        _camera: &dyn Camera,
+        _quiet: bool,
+    ) -> Option<Film> {
+        None
+    }
+    fn preprocess(&self, _scene: &Scene, _sampler: &mut dyn Sampler) {}
+    // integrators that render the whole film themselves cant be stopped, resumed or refined by pixels
+    fn renders_whole_film(&self) -> bool {
+        false
+    }
+    // integrators that only see surfaces would render the scene as if its media were vacuum
+    fn supports_media(&self) -> bool {
+        false
+    }
+    fn first_hit(&self, ray: &Ray, scene: &Scene) -> AovSample {
+        AovSample::first_hit(ray, scene)
+    }
 }
 
 #[allow(clippy::vtable_address_comparisons)]
-pub fn uniform_sample_one_light(
+pub fn estimate_direct(
     shape_point: &ShapePoint,
     bsdf: &BSDF,
     wo: &Vector3f,
+    light: &Arc<dyn Light>,
     scene: &Scene,
-    sampler: &mut dyn Sampler,
+    u_light: &Point2f,
+    u_scattering: &Point2f,
 ) -> Spectrum {
     let mut l = Spectrum::new(0.);
-    let lights = scene.get_lights();
     let n = &shape_point.n;
-    if !lights.is_empty() {
-        let light = &lights[(sampler.get_usize(lights.len())) as usize];
-        let point = shape_point.p;
-        {
-            // sample light
-            if let (wi, Some(li), li_pdf, visibility_tester) =
-                light.sample_li(&shape_point, sampler)
-            {
-                if li_pdf != 0. {
-                    trace!("Sample light Get li {} pdf {}", li, li_pdf);
-                    if let (Some(f), f_pdf) = bsdf.no_delta_f_pdf(&wo, &wi) {
-                        if f_pdf != 0. {
-                            if visibility_tester.unoccluded(scene) {
-                                let ld = if light.is_delta() {
-                                    li * f * n.dot(&wi).abs() / li_pdf
-                                } else {
-                                    li * f * n.dot(&wi).abs() * power_heuristic(li_pdf, f_pdf)
-                                        / li_pdf
-                                };
-                                if ld.has_nan() {
-                                    debug!("li_pdf {}", li_pdf);
-                                    debug!("f_pdf {}", f_pdf);
-                                }
-                                l += ld;
+    let point = shape_point.p;
+    {
+        // sample light
+        if let (wi, Some(li), li_pdf, visibility_tester) = light.sample_li(&shape_point, u_light) {
+            if li_pdf != 0. {
+                trace!("Sample light Get li {} pdf {}", li, li_pdf);
+                if let (Some(f), f_pdf) = bsdf.no_delta_f_pdf(&wo, &wi) {
+                    if f_pdf != 0. {
+                        if visibility_tester.unoccluded(scene) {
+                            let ld = if light.is_delta() {
+                                li * f * n.dot(&wi).abs() / li_pdf
+                            } else {
+                                li * f * n.dot(&wi).abs() * power_heuristic(li_pdf, f_pdf)
+                                    / li_pdf
+                            };
+                            if ld.has_nan() {
+                                debug!("li_pdf {}", li_pdf);
+                                debug!("f_pdf {}", f_pdf);
                             }
-                            trace!("Sample light Get f {} {}", f, f_pdf);
+                            l += ld;
                         }
+                        trace!("Sample light Get f {} {}", f, f_pdf);
                     }
                 }
             }
         }
-        if !light.is_delta() {
-            // sample brdf
-            if let (wi, Some(f), f_pdf) = bsdf.sample_no_delta_f(&wo, &sampler.get_2d()) {
-                trace!("Sample bsdf Get f {} pdf {}", f, f_pdf);
-                if f_pdf != 0. {
-                    let ray = Ray::new_shape_point_d(&shape_point, wi);
-                    let mut ld = Spectrum::new(0.);
-                    if let Some(intersect) = scene.intersect(&ray) {
-                        if let Some(intersect_light) = intersect.get_light() {
-                            if Arc::ptr_eq(light, &intersect_light) {
-                                if let (Some(li), li_pdf) =
-                                    light.le_pdf(&point, intersect.get_shape_point())
-                                {
-                                    if li_pdf != 0. {
-                                        trace!("Sample bsdf Get li {} pdf {}", li, f_pdf);
-                                        ld = li
-                                            * f
-                                            * n.dot(&wi).abs()
-                                            * power_heuristic(f_pdf, li_pdf)
-                                            / f_pdf;
-                                        if ld.has_nan() {
-                                            debug!("li_pdf {}", li_pdf);
-                                            debug!("f_pdf {}", f_pdf);
-                                        }
+    }
+    if !light.is_delta() {
+        // sample brdf
+        if let (wi, Some(f), f_pdf) = bsdf.sample_no_delta_f(&wo, u_scattering) {
+            trace!("Sample bsdf Get f {} pdf {}", f, f_pdf);
+            if f_pdf != 0. {
+                let ray = Ray::new_shape_point_d(&shape_point, wi);
+                let mut ld = Spectrum::new(0.);
+                if let Some(intersect) = scene.intersect(&ray) {
+                    if let Some(intersect_light) = intersect.get_light() {
+                        if Arc::ptr_eq(light, &intersect_light) {
+                            if let (Some(li), li_pdf) =
+                                light.le_pdf(&point, intersect.get_shape_point())
+                            {
+                                if li_pdf != 0. {
+                                    trace!("Sample bsdf Get li {} pdf {}", li, f_pdf);
+                                    ld = li
+                                        * f
+                                        * n.dot(&wi).abs()
+                                        * power_heuristic(f_pdf, li_pdf)
+                                        / f_pdf;
+                                    if ld.has_nan() {
+                                        debug!("li_pdf {}", li_pdf);
+                                        debug!("f_pdf {}", f_pdf);
                                     }
                                 }
                             }
                         }
-                    } else if let (Some(le), le_pdf) = light.le_out_scene_pdf(&ray) {
-                        if le_pdf != 0. {
-                            ld = le * f * n.dot(&wi).abs() * power_heuristic(f_pdf, le_pdf) / f_pdf;
-                        }
                     }
-                    l += ld;
+                } else if let (Some(le), le_pdf) = light.le_out_scene_pdf(&ray) {
+                    if le_pdf != 0. {
+                        ld = le * f * n.dot(&wi).abs() * power_heuristic(f_pdf, le_pdf) / f_pdf;
+                    }
                 }
+                l += ld;
             }
         }
     }
-    l *= lights.len() as Float;
+    l
+}
+
+pub fn uniform_sample_one_light(
+    shape_point: &ShapePoint,
+    bsdf: &BSDF,
+    wo: &Vector3f,
+    scene: &Scene,
+    sampler: &mut dyn Sampler,
+    bounce: usize,
+) -> Spectrum {
+    let slots = [
+        DimensionSlot::LightChoice(bounce),
+        DimensionSlot::Light(bounce),
+        DimensionSlot::LightBsdf(bounce),
+    ];
+    uniform_sample_one_light_in_slots(shape_point, bsdf, wo, scene, sampler, slots)
+}
+
+// the light choice, light and bsdf samples come from the given slots
+pub fn uniform_sample_one_light_in_slots(
+    shape_point: &ShapePoint,
+    bsdf: &BSDF,
+    wo: &Vector3f,
+    scene: &Scene,
+    sampler: &mut dyn Sampler,
+    slots: [DimensionSlot; 3],
+) -> Spectrum {
+    let lights = scene.get_lights();
+    if lights.is_empty() {
+        return Spectrum::new(0.);
+    }
+    sampler.start_slot(slots[0]);
+    let light = &lights[sampler.get_usize(lights.len())];
+    sampler.start_slot(slots[1]);
+    let u_light = sampler.get_2d();
+    sampler.start_slot(slots[2]);
+    let u_scattering = sampler.get_2d();
+    let l = estimate_direct(shape_point, bsdf, wo, light, scene, &u_light, &u_scattering)
+        * lights.len() as Float;
     if l.has_nan() {
         debug!("uniform sample one light has nan");
     }
     l
 }
+
+// every light with its own sample count, the samples come from the arrays requested by request_light_arrays
+pub fn uniform_sample_all_lights(
+    shape_point: &ShapePoint,
+    bsdf: &BSDF,
+    wo: &Vector3f,
+    scene: &Scene,
+    sampler: &mut dyn Sampler,
+    bounce: usize,
+) -> Spectrum {
+    let mut l = Spectrum::new(0.);
+    for light in scene.get_lights() {
+        let n = sampler.round_count(light.sample_count());
+        let u_lights = sampler.get_2d_array(n);
+        let u_scatterings = sampler.get_2d_array(n);
+        match (u_lights, u_scatterings) {
+            (Some(u_lights), Some(u_scatterings)) => {
+                let mut ld = Spectrum::new(0.);
+                for (u_light, u_scattering) in u_lights.iter().zip(u_scatterings.iter()) {
+                    ld += estimate_direct(shape_point, bsdf, wo, light, scene, u_light, u_scattering);
+                }
+                l += ld / n as Float;
+            }
+            _ => {
+                sampler.start_slot(DimensionSlot::Light(bounce));
+                let u_light = sampler.get_2d();
+                sampler.start_slot(DimensionSlot::LightBsdf(bounce));
+                let u_scattering = sampler.get_2d();
+                l += estimate_direct(shape_point, bsdf, wo, light, scene, &u_light, &u_scattering);
+            }
+        }
+    }
+    l
+}
+
+pub fn request_light_arrays(scene: &Scene, sampler: &mut dyn Sampler, max_depth: usize) {
+    for _ in 0..max_depth {
+        for light in scene.get_lights() {
+            let n = sampler.round_count(light.sample_count());
+            sampler.request_2d_array(n);
+            sampler.request_2d_array(n);
+        }
+    }
+}
+
+// deeper paths would run out of sampler dimensions and share them between decisions
+fn parse_max_depth(property_set: &PropertySet) -> SceneResult<usize> {
+    let max_depth = property_set.get_value("maxdepth")?.unwrap_or(5);
+    if max_depth > MAX_DEPTH {
+        return Err(property_set.error(&format!("maxdepth can be at most {}", MAX_DEPTH)));
+    }
+    Ok(max_depth)
+}
+
 impl ParseFromBlockSegment<'_> for Box<dyn Integrator> {
     type T = Box<dyn Integrator>;
-    fn parse_from_segment(segment: &BlockSegment) -> Option<Self::T> {
-        let property_set = segment.get_object_by_type("Integrator")?;
-        match property_set.get_name().unwrap() {
+    fn parse_from_segment(segment: &BlockSegment) -> SceneResult<Option<Self::T>> {
+        let property_set = match segment.get_object_by_type("Integrator") {
+            Some(property_set) => property_set,
+            None => return Ok(None),
+        };
+        match property_set.get_name()? {
             "directlighting" => {
-                let max_depth = property_set.get_value("maxdepth").unwrap_or(5);
-                Some(Box::new(DirectLightIntegrator::new(max_depth)))
+                let max_depth = parse_max_depth(property_set)?;
+                let strategy = match property_set.get_value::<String>("strategy")?.as_deref() {
+                    None | Some("one") => LightStrategy::UniformSampleOne,
+                    Some("all") => LightStrategy::UniformSampleAll,
+                    Some(strategy) => {
+                        return Err(property_set.error(&format!("Unknown light strategy '{}'", strategy)));
+                    }
+                };
+                Ok(Some(Box::new(DirectLightIntegrator::new(max_depth, strategy))))
             }
             "path" => {
-                let max_depth = property_set.get_value("maxdepth").unwrap_or(5);
-                Some(Box::new(PathIntegrator::new(max_depth)))
+                let max_depth = parse_max_depth(property_set)?;
+                Ok(Some(Box::new(PathIntegrator::new(max_depth))))
+            }
+            "volpath" => {
+                let max_depth = parse_max_depth(property_set)?;
+                Ok(Some(Box::new(VolPathIntegrator::new(max_depth))))
+            }
+            "bdpt" => {
+                let max_depth = parse_max_depth(property_set)?;
+                Ok(Some(Box::new(BDPTIntegrator::new(max_depth))))
+            }
+            "sppm" => {
+                let max_depth = parse_max_depth(property_set)?;
+                let iterations = property_set.get_value::<Integer>("numiterations")?;
+                if matches!(iterations, Some(iterations) if iterations <= 0) {
+                    return Err(property_set.error("SPPM numiterations must be positive"));
+                }
+                let photons_per_iteration = property_set.get_value::<Integer>("photonsperiteration")?;
+                if matches!(photons_per_iteration, Some(photons) if photons <= 0) {
+                    return Err(property_set.error("SPPM photonsperiteration must be positive"));
+                }
+                let radius = property_set.get_value("radius")?.unwrap_or(1.);
+                if radius <= 0. {
+                    return Err(property_set.error("SPPM radius must be positive"));
+                }
+                Ok(Some(Box::new(SPPMIntegrator::new(
+                    max_depth,
+                    iterations.map(|iterations| iterations as usize),
+                    photons_per_iteration.map(|photons| photons as usize),
+                    radius,
+                ))))
+            }
+            "mlt" => {
+                let max_depth = parse_max_depth(property_set)?;
+                let bootstrap_samples = property_set.get_value::<Integer>("bootstrapsamples")?.unwrap_or(100000);
+                if bootstrap_samples <= 0 {
+                    return Err(property_set.error("MLT bootstrapsamples must be positive"));
+                }
+                let chains = property_set.get_value::<Integer>("chains")?.unwrap_or(1000);
+                if chains <= 0 {
+                    return Err(property_set.error("MLT chains must be positive"));
+                }
+                let mutations_per_pixel = property_set.get_value::<Integer>("mutationsperpixel")?;
+                if matches!(mutations_per_pixel, Some(mutations) if mutations <= 0) {
+                    return Err(property_set.error("MLT mutationsperpixel must be positive"));
+                }
+                let large_step_probability = property_set.get_value("largestepprobability")?.unwrap_or(0.3);
+                let sigma = property_set.get_value("sigma")?.unwrap_or(0.01);
+                if !(0. ..=1.).contains(&large_step_probability) {
+                    return Err(property_set.error("MLT largestepprobability must be between 0 and 1"));
+                }
+                if sigma <= 0. {
+                    return Err(property_set.error("MLT sigma must be positive"));
+                }
+                Ok(Some(Box::new(MLTIntegrator::new(
+                    max_depth,
+                    bootstrap_samples as usize,
+                    chains as usize,
+                    mutations_per_pixel.map(|mutations| mutations as usize),
+                    large_step_probability,
+                    sigma,
+                ))))
+            }
+            integrator_type => {
+                Err(property_set.error(&format!("Unknown integrator '{}'", integrator_type)))
             }
-            _ => panic!(),
         }
     }
 }
@@ -36,16 +36,50 @@ impl Integrator for PathIntegrator {
                     let wo = -ray.d;
                     if !bsdf.is_all_delta() {
                         l += beta
-                            * uniform_sample_one_light(shape_point, &bsdf, &wo, scene, sampler);
+                            * uniform_sample_one_light(shape_point, &bsdf, &wo, scene, sampler, bounce);
                     }
+                    sampler.start_slot(DimensionSlot::Bsdf(bounce));
                     if let (wi, Some(f), f_pdf, is_delta) = bsdf.sample_f(&wo, sampler) {
                         if f_pdf != 0. {
                             beta *= f * wi.dot(&shape_point.n).abs() / f_pdf;
                             ray = Ray::new_shape_point_d(shape_point, wi);
                             specular_bounce = is_delta;
 
+                            // light entering a subsurface material leaves it again somewhere nearby
+                            let transmit = wo.dot(&shape_point.n) * wi.dot(&shape_point.n) < 0.;
+                            if let Some(bssrdf) = intersect.compute_bssrdf().filter(|_| transmit) {
+                                sampler.start_slot(DimensionSlot::Subsurface(bounce));
+                                let u1 = sampler.get_1d();
+                                let u2 = sampler.get_2d();
+                                let (s, pdf, exit) = match bssrdf.sample_s(scene, &intersect, u1, &u2) {
+                                    Some((s, pdf, exit)) if pdf != 0. && !s.is_black() => (s, pdf, exit),
+                                    _ => break,
+                                };
+                                beta *= s / pdf;
+                                let exit_point = exit.get_shape_point();
+                                let exit_bsdf = bssrdf.exit_bsdf(exit_point);
+                                let wo = exit_point.n.0;
+                                let slots = [
+                                    DimensionSlot::SubsurfaceLightChoice(bounce),
+                                    DimensionSlot::SubsurfaceLight(bounce),
+                                    DimensionSlot::SubsurfaceLightBsdf(bounce),
+                                ];
+                                l += beta
+                                    * uniform_sample_one_light_in_slots(exit_point, &exit_bsdf, &wo, scene, sampler, slots);
+                                sampler.start_slot(DimensionSlot::SubsurfaceBsdf(bounce));
+                                match exit_bsdf.sample_f(&wo, sampler) {
+                                    (wi, Some(f), f_pdf, _) if f_pdf != 0. => {
+                                        beta *= f * wi.dot(&exit_point.n).abs() / f_pdf;
+                                        ray = Ray::new_shape_point_d(exit_point, wi);
+                                        specular_bounce = false;
+                                    }
+                                    _ => break,
+                                }
+                            }
+
                             if bounce > 3 {
                                 let q = max(0.05, 1. - beta.y());
+                                sampler.start_slot(DimensionSlot::RussianRoulette(bounce));
                                 if sampler.get_1d() < q {
                                     break;
                                 }
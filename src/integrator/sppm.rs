@@ -0,0 +1,462 @@
+use crate::*;
+use rayon::prelude::*;
+
+// the first diffuse or glossy point a camera path reaches, photons arriving near it are its indirect light
+struct VisiblePoint {
+    p: Point3f,
+    wo: Vector3f,
+    bsdf: BSDF,
+    beta: Spectrum,
+}
+
+struct SPPMPixel {
+    radius: Float,
+    // light reached directly by the camera paths, summed over the iterations
+    ld: Spectrum,
+    visible_point: Option<VisiblePoint>,
+    // photons of the current iteration
+    phi: Spectrum,
+    m: usize,
+    // photons of the past iterations, with the radius reduced after each
+    n: Float,
+    tau: Spectrum,
+}
+
+impl SPPMPixel {
+    fn new(radius: Float) -> Self {
+        Self {
+            radius,
+            ld: Spectrum::new(0.),
+            visible_point: None,
+            phi: Spectrum::new(0.),
+            m: 0,
+            n: 0.,
+            tau: Spectrum::new(0.),
+        }
+    }
+    // the photons of an iteration shrink the radius, keeping a fixed share of them
+    fn update(&mut self) {
+        const GAMMA: Float = 2. / 3.;
+        if self.m > 0 {
+            let m = self.m as Float;
+            let n = self.n + GAMMA * m;
+            let radius = self.radius * (n / (self.n + m)).sqrt();
+            let beta = self.visible_point.as_ref().map_or(Spectrum::new(0.), |vp| vp.beta);
+            self.tau = (self.tau + beta * self.phi) * (radius * radius) / (self.radius * self.radius);
+            self.n = n;
+            self.radius = radius;
+            self.m = 0;
+            self.phi = Spectrum::new(0.);
+        }
+        self.visible_point = None;
+    }
+}
+
+// the pixels whose visible points are near a cell, cells are hashed into as many buckets as there are pixels
+struct VisiblePointGrid {
+    bound: Bounds3f,
+    resolution: Vector3i,
+    buckets: Vec<Vec<usize>>,
+}
+
+impl VisiblePointGrid {
+    fn new(pixels: &[SPPMPixel]) -> Self {
+        let mut bound: Option<Bounds3f> = None;
+        let mut max_radius: Float = 0.;
+        for pixel in pixels {
+            if let Some(vp) = pixel.visible_point.as_ref().filter(|vp| !vp.beta.is_black()) {
+                let radius = Vector3f::new(pixel.radius, pixel.radius, pixel.radius);
+                let vp_bound = Bounds3f::new(&(vp.p - radius), &(vp.p + radius));
+                bound = Some(match bound {
+                    Some(bound) => bound | &vp_bound,
+                    None => vp_bound,
+                });
+                max_radius = max(max_radius, pixel.radius);
+            }
+        }
+        let mut grid = Self {
+            bound: bound.unwrap_or_else(|| Bounds3f::new(&Point3f::new(0., 0., 0.), &Point3f::new(0., 0., 0.))),
+            resolution: Vector3i::new(1, 1, 1),
+            buckets: (0..pixels.len()).map(|_| Vec::new()).collect(),
+        };
+        if max_radius == 0. {
+            return grid;
+        }
+        let diagonal = grid.bound.diagonal();
+        let max_diagonal = max(diagonal.x, max(diagonal.y, diagonal.z));
+        let base_resolution = max_diagonal / max_radius;
+        grid.resolution = diagonal.map(|d| max((base_resolution * d / max_diagonal) as Integer, 1));
+        for (i, pixel) in pixels.iter().enumerate() {
+            if let Some(vp) = pixel.visible_point.as_ref().filter(|vp| !vp.beta.is_black()) {
+                let radius = Vector3f::new(pixel.radius, pixel.radius, pixel.radius);
+                let min = grid.cell(&(vp.p - radius));
+                let max = grid.cell(&(vp.p + radius));
+                for z in min.z..=max.z {
+                    for y in min.y..=max.y {
+                        for x in min.x..=max.x {
+                            let bucket = grid.bucket(&Point3i::new(x, y, z));
+                            grid.buckets[bucket].push(i);
+                        }
+                    }
+                }
+            }
+        }
+        grid
+    }
+    fn cell(&self, p: &Point3f) -> Point3i {
+        let offset = self.bound.offset(*p);
+        let cell = |i: usize| clamp((offset[i] * self.resolution[i] as Float) as Integer, 0, self.resolution[i] - 1);
+        Point3i::new(cell(0), cell(1), cell(2))
+    }
+    fn bucket(&self, cell: &Point3i) -> usize {
+        let hash = (cell.x.wrapping_mul(73_856_093)) ^ (cell.y.wrapping_mul(19_349_663)) ^ (cell.z.wrapping_mul(83_492_791));
+        hash as u32 as usize % self.buckets.len()
+    }
+    // the pixels that may have a visible point within their radius of p
+    fn pixels(&self, p: &Point3f) -> &[usize] {
+        if !self.bound.inside(p) {
+            return &[];
+        }
+        &self.buckets[self.bucket(&self.cell(p))]
+    }
+}
+
+// stochastic progressive photon mapping, every iteration finds a visible point for each pixel, then traces
+// photons from the lights and gathers them at the visible points within a radius that shrinks over the iterations
+pub struct SPPMIntegrator {
+    max_depth: usize,
+    iterations: Option<usize>,
+    photons_per_iteration: Option<usize>,
+    initial_radius: Float,
+}
+
+impl SPPMIntegrator {
+    pub fn new(
+        max_depth: usize,
+        iterations: Option<usize>,
+        photons_per_iteration: Option<usize>,
+        initial_radius: Float,
+    ) -> Self {
+        Self {
+            max_depth,
+            iterations,
+            photons_per_iteration,
+            initial_radius,
+        }
+    }
+    // the light the camera path sees directly and the visible point it stops at
+    fn camera_path(&self, ray: &Ray, scene: &Scene, sampler: &mut dyn Sampler) -> (Spectrum, Option<VisiblePoint>) {
+        let mut l = Spectrum::new(0.);
+        let mut beta = Spectrum::new(1.);
+        let mut ray = *ray;
+        let mut specular_bounce = false;
+        let mut depth = 0;
+        while depth < self.max_depth {
+            let intersect = match scene.intersect(&ray) {
+                Some(intersect) => intersect,
+                None => {
+                    for light in scene.get_lights() {
+                        l += beta * light.le_out_scene(&ray);
+                    }
+                    break;
+                }
+            };
+            let shape_point = intersect.get_shape_point();
+            // scenes with media are rejected, so interfaces only separate vacuum from vacuum
+            if intersect.is_medium_interface() {
+                ray = Ray::new_shape_point_d(shape_point, ray.d);
+                continue;
+            }
+            if depth == 0 || specular_bounce {
+                l += beta * intersect.le();
+            }
+            let bsdf = match intersect.compute_scattering_functions() {
+                Some(bsdf) => bsdf,
+                None => break,
+            };
+            let wo = -ray.d.normalize();
+            if !bsdf.is_all_delta() {
+                l += beta * uniform_sample_one_light(shape_point, &bsdf, &wo, scene, sampler, depth);
+                let visible_point = VisiblePoint {
+                    p: shape_point.p,
+                    wo,
+                    bsdf,
+                    beta,
+                };
+                return (l, Some(visible_point));
+            }
+            sampler.start_slot(DimensionSlot::Bsdf(depth));
+            let (wi, f, f_pdf, is_delta) = bsdf.sample_f(&wo, sampler);
+            let f = match f {
+                Some(f) if f_pdf != 0. && !f.is_black() => f,
+                _ => break,
+            };
+            beta *= f * wi.dot(&shape_point.n).abs() / f_pdf;
+            specular_bounce = is_delta;
+            if beta.y() < 0.25 {
+                let continue_probability = min(1., beta.y());
+                sampler.start_slot(DimensionSlot::RussianRoulette(depth));
+                if sampler.get_1d() > continue_probability {
+                    break;
+                }
+                beta /= continue_probability;
+            }
+            ray = Ray::new_shape_point_d(shape_point, wi);
+            depth += 1;
+        }
+        (l, None)
+    }
+    // the photons a photon path leaves at the visible points, as pixel indices and flux
+    fn photon_path(
+        &self,
+        scene: &Scene,
+        sampler: &mut dyn Sampler,
+        time: Float,
+        pixels: &[SPPMPixel],
+        grid: &VisiblePointGrid,
+        photons: &mut Vec<(usize, Spectrum)>,
+    ) {
+        let lights = scene.get_lights();
+        if lights.is_empty() {
+            return;
+        }
+        sampler.start_slot(DimensionSlot::LightPathChoice);
+        let light = &lights[sampler.get_usize(lights.len())];
+        let light_pdf = 1. / lights.len() as Float;
+        sampler.start_slot(DimensionSlot::LightPathOrigin);
+        let u1 = sampler.get_2d();
+        sampler.start_slot(DimensionSlot::LightPathDirection);
+        let u2 = sampler.get_2d();
+        let (mut ray, n, le, pdf_pos, pdf_dir) = light.sample_le(&u1, &u2, time, &scene.world_bound());
+        let le = match le {
+            Some(le) if pdf_pos != 0. && pdf_dir != 0. && !le.is_black() => le,
+            _ => return,
+        };
+        let mut beta = le * n.dot(&ray.d).abs() / (light_pdf * pdf_pos * pdf_dir);
+        let mut depth = 0;
+        while depth < self.max_depth && !beta.is_black() {
+            let intersect = match scene.intersect(&ray) {
+                Some(intersect) => intersect,
+                None => break,
+            };
+            let shape_point = intersect.get_shape_point();
+            if intersect.is_medium_interface() {
+                ray = Ray::new_shape_point_d(shape_point, ray.d);
+                continue;
+            }
+            // the light of the first hit is already in the camera paths
+            if depth > 0 {
+                let wi = -ray.d.normalize();
+                for &i in grid.pixels(&shape_point.p) {
+                    let pixel = &pixels[i];
+                    if let Some(vp) = &pixel.visible_point {
+                        if (vp.p - shape_point.p).magnitude_squared() <= pixel.radius * pixel.radius {
+                            let phi = beta * vp.bsdf.f(&vp.wo, &wi);
+                            if !phi.is_black() {
+                                photons.push((i, phi));
+                            }
+                        }
+                    }
+                }
+            }
+            let bsdf = match intersect.compute_scattering_functions() {
+                Some(bsdf) => bsdf,
+                None => break,
+            };
+            let wo = -ray.d.normalize();
+            sampler.start_slot(DimensionSlot::LightPathBsdf(depth));
+            let (wi, f, f_pdf, _) = bsdf.sample_f(&wo, sampler);
+            let f = match f {
+                Some(f) if f_pdf != 0. && !f.is_black() => f,
+                _ => break,
+            };
+            let new_beta = beta * f * wi.dot(&shape_point.n).abs() / f_pdf;
+            // photons keep about the same flux, the ones losing it are terminated
+            let q = max(0., 1. - new_beta.y() / beta.y());
+            sampler.start_slot(DimensionSlot::LightPathRussianRoulette(depth));
+            if sampler.get_1d() < q {
+                break;
+            }
+            beta = new_beta / (1. - q);
+            ray = Ray::new_shape_point_d(shape_point, wi);
+            depth += 1;
+        }
+    }
+}
+
+impl Integrator for SPPMIntegrator {
+    // a single camera sample only gets the light its path reaches directly
+    fn li(&self, ray: &Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Spectrum {
+        self.camera_path(ray, scene, sampler).0
+    }
+    fn render(
+        &self,
+        scene: &Scene,
+        sampler: &dyn Sampler,
+        film: &Film,
+        camera: &dyn Camera,
+        quiet: bool,
+    ) -> Option<Film> {
+        let mut film = film.clone();
+        let crop_bound = film.crop_bound().clone();
+        let points = crop_bound.index_inside();
+        let iterations = self.iterations.unwrap_or_else(|| sampler.get_sample_per_pixel());
+        let photons_per_iteration = self.photons_per_iteration.unwrap_or(points.len());
+        // photons take their samples from the pixels in turn, rounds more samples when there are more photons
+        let photon_rounds = (photons_per_iteration + points.len() - 1) / max(points.len(), 1);
+        let (shutter_open, shutter_close) = camera.shutter();
+        let mut pixels: Vec<SPPMPixel> = points.iter().map(|_| SPPMPixel::new(self.initial_radius)).collect();
+        let progress_bar = new_progress_bar(iterations as u64, quiet, "sppm");
+        for iteration in 0..iterations {
+            pixels.par_iter_mut().zip(points.par_iter()).for_each_init(
+                || sampler.box_clone(),
+                |sampler, (pixel, point)| {
+                    sampler.set_pixel(point);
+                    sampler.set_sample_index(iteration);
+                    sampler.start_slot(DimensionSlot::Film);
+                    let pixel_center = Point2f::new(point.x as Float + 0.5, point.y as Float + 0.5);
+                    let offset = sampler.get_2d() - Point2f::new(0.5, 0.5);
+                    let (ray, weight) = camera.generate_ray_with_weight(pixel_center + offset, sampler.as_mut());
+                    if weight == 0. {
+                        return;
+                    }
+                    let (l, visible_point) = self.camera_path(&ray, scene, sampler.as_mut());
+                    if !l.has_nan() {
+                        pixel.ld += l * weight;
+                    }
+                    pixel.visible_point = visible_point.map(|mut vp| {
+                        vp.beta *= weight;
+                        vp
+                    });
+                },
+            );
+            let grid = VisiblePointGrid::new(&pixels);
+            let chunk_size = max(photons_per_iteration / (rayon::current_num_threads() * 8), 1);
+            let photon_chunks: Vec<_> = (0..photons_per_iteration).collect();
+            // photons are gathered in a fixed order so the result does not depend on thread scheduling
+            let photons: Vec<Vec<(usize, Spectrum)>> = photon_chunks
+                .par_chunks(chunk_size)
+                .map(|chunk| {
+                    let mut sampler = sampler.box_clone();
+                    let mut photons = Vec::new();
+                    for &photon in chunk {
+                        sampler.set_pixel(&points[photon % points.len()]);
+                        sampler.set_sample_index(iteration * photon_rounds + photon / points.len());
+                        sampler.start_slot(DimensionSlot::Time);
+                        let u = sampler.get_1d();
+                        let time = shutter_open * (1. - u) + shutter_close * u;
+                        self.photon_path(scene, sampler.as_mut(), time, &pixels, &grid, &mut photons);
+                    }
+                    photons
+                })
+                .collect();
+            for (i, phi) in photons.into_iter().flatten() {
+                if !phi.has_nan() {
+                    pixels[i].phi += phi;
+                    pixels[i].m += 1;
+                }
+            }
+            pixels.par_iter_mut().for_each(SPPMPixel::update);
+            progress_bar.inc(1);
+        }
+        progress_bar.finish_and_clear();
+        let photons = (iterations * photons_per_iteration) as Float;
+        for (pixel, point) in pixels.iter().zip(points.iter()) {
+            let mut l = pixel.ld / iterations as Float;
+            if photons > 0. {
+                l += pixel.tau / (photons * PI * pixel.radius * pixel.radius);
+            }
+            film.add_sample(point, l, 1.);
+        }
+        Some(film)
+    }
+    fn renders_whole_film(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_scene::*;
+    fn visible_point(p: Point3f, beta: Float) -> VisiblePoint {
+        let n = Normal3f::from(Vector3f::new(0., 0., 1.));
+        VisiblePoint {
+            p,
+            wo: Vector3f::new(0., 0., 1.),
+            bsdf: BSDF::new(n, n),
+            beta: Spectrum::new(beta),
+        }
+    }
+    #[test]
+    fn update_shrinks_radius_and_keeps_photons() {
+        let mut pixel = SPPMPixel::new(1.);
+        pixel.visible_point = Some(visible_point(Point3f::new(0., 0., 0.), 0.5));
+        pixel.phi = Spectrum::new(4.);
+        pixel.m = 3;
+        pixel.update();
+        // two thirds of the three photons are kept, the area shrinks by as much
+        assert!((pixel.n - 2.).abs() < 1e-5);
+        assert!((pixel.radius * pixel.radius - 2. / 3.).abs() < 1e-5);
+        assert!((pixel.tau[0] - 0.5 * 4. * 2. / 3.).abs() < 1e-5);
+        assert_eq!(pixel.m, 0);
+        assert!(pixel.phi.is_black() && pixel.visible_point.is_none());
+        // an iteration without photons changes nothing
+        let (radius, tau) = (pixel.radius, pixel.tau[0]);
+        pixel.update();
+        assert_eq!((pixel.radius, pixel.tau[0]), (radius, tau));
+        pixel.visible_point = Some(visible_point(Point3f::new(0., 0., 0.), 1.));
+        pixel.phi = Spectrum::new(3.);
+        pixel.m = 6;
+        pixel.update();
+        assert!((pixel.n - 6.).abs() < 1e-5);
+        assert!((pixel.radius * pixel.radius - 2. / 3. * 6. / 8.).abs() < 1e-5);
+        assert!((pixel.tau[0] - (tau + 3.) * 6. / 8.).abs() < 1e-5);
+    }
+    #[test]
+    fn grid_finds_every_visible_point_in_reach() {
+        let mut rng = Rng::default();
+        let mut point = || Point3f::new(rng.uniform_float() * 4., rng.uniform_float() * 2., rng.uniform_float());
+        let mut pixels: Vec<SPPMPixel> = (0..64)
+            .map(|i| {
+                let mut pixel = SPPMPixel::new(0.1 + 0.05 * (i % 4) as Float);
+                // pixels whose camera paths carry nothing take no photons
+                pixel.visible_point = Some(visible_point(point(), if i % 8 == 0 { 0. } else { 1. }));
+                pixel
+            })
+            .collect();
+        pixels[1].visible_point = None;
+        let grid = VisiblePointGrid::new(&pixels);
+        let mut reached = 0;
+        for _ in 0..1000 {
+            let p = point();
+            let found = grid.pixels(&p);
+            for (i, pixel) in pixels.iter().enumerate() {
+                let in_reach = match &pixel.visible_point {
+                    Some(vp) => !vp.beta.is_black() && (vp.p - p).magnitude() < pixel.radius,
+                    None => false,
+                };
+                if in_reach {
+                    assert!(found.contains(&i), "pixel {} missing at {:?}", i, p);
+                    reached += 1;
+                }
+                if i % 8 == 0 || i == 1 {
+                    assert!(!found.contains(&i));
+                }
+            }
+        }
+        assert!(reached > 100);
+        assert!(grid.pixels(&Point3f::new(10., 10., 10.)).is_empty());
+    }
+    #[test]
+    fn parameters_must_be_positive() {
+        for parameter in &["integer numiterations", "integer photonsperiteration", "float radius"] {
+            for value in &[0, -1] {
+                let integrator = format!("Integrator \"sppm\" \"{}\" [{}]", parameter, value);
+                let scene = box_scene(r#"Sampler "random""#, &integrator);
+                let error = load_test_scene("sppm-parameters", &scene, &RenderOptions::default()).err().unwrap();
+                assert!(error.message.contains("must be positive"), "{}", error.message);
+            }
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use crate::*;
+use std::sync::Arc;
+
+// what scatters the light at a path vertex, a surface or a point inside a medium
+enum Scattering<'a> {
+    Surface(&'a BSDF, Normal3f),
+    Medium(HenyeyGreenstein),
+}
+
+impl Scattering<'_> {
+    // the scattered light factor including the cosine at surfaces, and its pdf
+    fn f_pdf(&self, wo: &Vector3f, wi: &Vector3f) -> (Spectrum, Float) {
+        match self {
+            Self::Surface(bsdf, n) => {
+                let (f, pdf) = bsdf.no_delta_f_pdf(wo, wi);
+                (Spectrum::from(f) * n.dot(wi).abs(), pdf)
+            }
+            Self::Medium(phase) => {
+                let p = phase.p(wo, wi);
+                (Spectrum::new(p), p)
+            }
+        }
+    }
+    fn sample_f(&self, wo: &Vector3f, u: &Point2f) -> (Vector3f, Spectrum, Float) {
+        match self {
+            Self::Surface(bsdf, n) => {
+                let (wi, f, pdf) = bsdf.sample_no_delta_f(wo, u);
+                (wi, Spectrum::from(f) * n.dot(&wi).abs(), pdf)
+            }
+            Self::Medium(phase) => {
+                let (wi, p) = phase.sample_p(wo, u);
+                (wi, Spectrum::new(p), p)
+            }
+        }
+    }
+}
+
+// like estimate_direct, but the light is attenuated by the media it passes
+#[allow(clippy::too_many_arguments)]
+fn estimate_direct_tr(
+    point: &ShapePoint,
+    scattering: &Scattering,
+    wo: &Vector3f,
+    medium: &dyn Fn(&Vector3f) -> Option<Arc<dyn Medium>>,
+    light: &Arc<dyn Light>,
+    scene: &Scene,
+    sampler: &mut dyn Sampler,
+    u_light: &Point2f,
+    u_scattering: &Point2f,
+) -> Spectrum {
+    let mut l = Spectrum::new(0.);
+    if let (wi, Some(li), li_pdf, visibility_tester) = light.sample_li(point, u_light) {
+        if li_pdf != 0. {
+            let (f, f_pdf) = scattering.f_pdf(wo, &wi);
+            if !f.is_black() {
+                let tr = visibility_tester.tr(scene, medium(&wi), sampler);
+                if light.is_delta() {
+                    l += li * f * tr / li_pdf;
+                } else {
+                    l += li * f * tr * power_heuristic(li_pdf, f_pdf) / li_pdf;
+                }
+            }
+        }
+    }
+    if !light.is_delta() {
+        let (wi, f, f_pdf) = scattering.sample_f(wo, u_scattering);
+        if f_pdf != 0. && !f.is_black() {
+            let ray = Ray::new_shape_point_d(point, wi);
+            let (intersect, tr) = scene.intersect_tr(&ray, medium(&wi), sampler);
+            let (li, li_pdf) = match intersect {
+                Some(intersect) => match intersect.get_light() {
+                    Some(intersect_light) if Arc::ptr_eq(light, &intersect_light) => {
+                        light.le_pdf(&point.p, intersect.get_shape_point())
+                    }
+                    _ => (None, 0.),
+                },
+                None => light.le_out_scene_pdf(&ray),
+            };
+            if let Some(li) = li {
+                if li_pdf != 0. {
+                    l += li * f * tr * power_heuristic(f_pdf, li_pdf) / f_pdf;
+                }
+            }
+        }
+    }
+    l
+}
+
+#[allow(clippy::too_many_arguments)]
+fn sample_one_light_tr(
+    point: &ShapePoint,
+    scattering: &Scattering,
+    wo: &Vector3f,
+    medium: &dyn Fn(&Vector3f) -> Option<Arc<dyn Medium>>,
+    scene: &Scene,
+    sampler: &mut dyn Sampler,
+    bounce: usize,
+) -> Spectrum {
+    let lights = scene.get_lights();
+    if lights.is_empty() {
+        return Spectrum::new(0.);
+    }
+    sampler.start_slot(DimensionSlot::LightChoice(bounce));
+    let light = &lights[sampler.get_usize(lights.len())];
+    sampler.start_slot(DimensionSlot::Light(bounce));
+    let u_light = sampler.get_2d();
+    sampler.start_slot(DimensionSlot::LightBsdf(bounce));
+    let u_scattering = sampler.get_2d();
+    // the shadow rays of heterogeneous media seed their tracking from here
+    sampler.start_slot(DimensionSlot::Transmittance(bounce));
+    estimate_direct_tr(point, scattering, wo, medium, light, scene, sampler, &u_light, &u_scattering)
+        * lights.len() as Float
+}
+
+// path tracing through participating media, rays start in the camera medium
+// and change medium when they cross a surface with a medium interface
+pub struct VolPathIntegrator {
+    max_depth: usize,
+}
+
+impl VolPathIntegrator {
+    pub fn new(max_depth: usize) -> Self {
+        Self { max_depth }
+    }
+}
+
+impl Integrator for VolPathIntegrator {
+    fn li(&self, ray: &Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Spectrum {
+        let mut l = Spectrum::new(0.);
+        let mut beta = Spectrum::new(1.);
+        let mut ray = *ray;
+        let mut medium = scene.camera_medium();
+        let mut bounce = 0;
+        let mut specular_bounce = false;
+        loop {
+            let intersect = scene.intersect(&ray);
+            let mut medium_interaction = None;
+            if let Some(medium) = &medium {
+                let t_max = intersect.as_ref().map_or(ray.t_max, |i| i.get_shape_intersect().get_t());
+                sampler.start_slot(DimensionSlot::Medium(bounce));
+                let (weight, interaction) = medium.sample(&Ray { t_max, ..ray }, sampler);
+                beta *= weight;
+                medium_interaction = interaction;
+            }
+            if beta.is_black() {
+                break;
+            }
+            if let Some(interaction) = medium_interaction {
+                if bounce >= self.max_depth {
+                    break;
+                }
+                let wo = -ray.d.normalize();
+                let point = interaction.shape_point();
+                let scattering = Scattering::Medium(interaction.phase);
+                l += beta
+                    * sample_one_light_tr(&point, &scattering, &wo, &|_| medium.clone(), scene, sampler, bounce);
+                sampler.start_slot(DimensionSlot::Phase(bounce));
+                let (wi, _) = interaction.phase.sample_p(&wo, &sampler.get_2d());
+                ray = Ray::new_shape_point_d(&point, wi);
+                specular_bounce = false;
+            } else {
+                if bounce == 0 || specular_bounce {
+                    if let Some(intersect) = &intersect {
+                        l += beta * intersect.le();
+                    } else {
+                        for light in scene.get_lights() {
+                            l += beta * light.le_out_scene(&ray);
+                        }
+                    }
+                }
+                let intersect = match intersect {
+                    Some(intersect) => intersect,
+                    None => break,
+                };
+                // a medium boundary does not count as a bounce
+                if intersect.is_medium_interface() {
+                    medium = intersect.medium_after(&ray.d, medium);
+                    ray = Ray::new_shape_point_d(intersect.get_shape_point(), ray.d);
+                    continue;
+                }
+                if bounce >= self.max_depth {
+                    break;
+                }
+                let bsdf = match intersect.compute_scattering_functions() {
+                    Some(bsdf) => bsdf,
+                    None => break,
+                };
+                let shape_point = intersect.get_shape_point();
+                let wo = -ray.d;
+                if !bsdf.is_all_delta() {
+                    let scattering = Scattering::Surface(&bsdf, shape_point.n);
+                    let medium_after = |wi: &Vector3f| intersect.medium_after(wi, medium.clone());
+                    l += beta
+                        * sample_one_light_tr(shape_point, &scattering, &wo, &medium_after, scene, sampler, bounce);
+                }
+                sampler.start_slot(DimensionSlot::Bsdf(bounce));
+                match bsdf.sample_f(&wo, sampler) {
+                    (wi, Some(f), f_pdf, is_delta) if f_pdf != 0. => {
+                        beta *= f * wi.dot(&shape_point.n).abs() / f_pdf;
+                        medium = intersect.medium_after(&wi, medium);
+                        ray = Ray::new_shape_point_d(shape_point, wi);
+                        specular_bounce = is_delta;
+                    }
+                    _ => break,
+                }
+            }
+            // long random walks in dense media keep beta near one, roulette would only make it grow
+            if bounce > 3 && beta.y() < 1. {
+                let q = max(0.05, 1. - beta.y());
+                sampler.start_slot(DimensionSlot::RussianRoulette(bounce));
+                if sampler.get_1d() < q {
+                    break;
+                }
+                beta /= 1. - q;
+            }
+            bounce += 1;
+        }
+        l
+    }
+    fn supports_media(&self) -> bool {
+        true
+    }
+}
@@ -9,12 +9,18 @@ extern crate lazy_static;
 
 #[allow(clippy::excessive_precision)]
 
+pub mod aov;
+pub use aov::*;
 pub mod camera;
 pub use camera::*;
+pub mod checkpoint;
+pub use checkpoint::*;
 pub mod def;
 pub use def::*;
 pub mod film;
 pub use film::*;
+pub mod filter;
+pub use filter::*;
 pub mod geometry;
 pub use geometry::*;
 pub mod integrator;
@@ -25,6 +31,8 @@ pub mod material;
 pub use material::*;
 pub mod math;
 pub use math::*;
+pub mod medium;
+pub use medium::*;
 pub mod primitive;
 pub use primitive::*;
 pub mod render;
@@ -39,6 +47,8 @@ pub mod spectrum;
 pub use spectrum::*;
 pub mod texture;
 pub use texture::*;
+#[cfg(test)]
+pub mod test_scene;
 pub mod aggregate;
 pub use aggregate::*;
 pub mod utility;
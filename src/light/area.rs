@@ -6,11 +6,16 @@ use std::sync::Arc;
 pub struct AreaLight {
     shape: Arc<dyn Shape>,
     le: Spectrum,
+    samples: usize,
 }
 
 impl AreaLight {
     pub fn new(shape: Arc<dyn Shape>, le: Spectrum) -> Self {
-        Self { shape, le }
+        Self { shape, le, samples: 1 }
+    }
+    pub fn with_samples(mut self, samples: usize) -> Self {
+        self.samples = samples;
+        self
     }
 }
 
@@ -24,15 +29,40 @@ impl Light for AreaLight {
     fn is_delta(&self) -> bool {
         false
     }
+    fn sample_count(&self) -> usize {
+        self.samples
+    }
+    // the light leaves both sides of the shape, the side is chosen with the first dimension of u2
+    fn sample_le(
+        &self,
+        u1: &Point2f,
+        u2: &Point2f,
+        time: Float,
+        _: &Bounds3f,
+    ) -> (Ray, Normal3f, Option<Spectrum>, Float, Float) {
+        let (mut light_point, pdf_pos) = self.shape.sample(u1);
+        light_point.time = time;
+        let (side, remap) = sample_usize_remap(u2.x, 2);
+        let (w, pdf_dir) = cosine_sample_hemisphere(Point2f::new(remap, u2.y));
+        let n = if side == 0 { light_point.n.0 } else { -light_point.n.0 };
+        let (nx, ny) = coordinate_system(&n);
+        let d = nx * w.x + ny * w.y + n * w.z;
+        let ray = Ray::new_shape_point_d(&light_point, d);
+        (ray, Normal3f::from(n), self.le(&light_point), pdf_pos, pdf_dir / 2.)
+    }
+    fn pdf_le(&self, ray: &Ray, n: &Normal3f, _: &Bounds3f) -> (Float, Float) {
+        let light_point = ShapePoint::new_p_normal(ray.o, *n);
+        (self.shape.pdf(&light_point), n.dot(&ray.d.normalize()).abs() * INV_PI / 2.)
+    }
     fn pdf(&self, point: &Point3f, shape_point: &ShapePoint) -> Float {
         self.shape.by_point_pdf(point, shape_point)
     }
     fn sample_li(
         &self,
         point: &ShapePoint,
-        sampler: &mut dyn Sampler,
+        u: &Point2f,
     ) -> (Vector3f, Option<Spectrum>, Float, VisibilityTester) {
-        let (light_point, pdf) = self.shape.sample_by_point(&point.p, sampler);
+        let (light_point, pdf) = self.shape.sample_by_point(&point.p, u);
         if (light_point.p - point.p).magnitude_squared() == 0. || pdf == 0. {
             return (
                 Vector3f::new(0., 0., 0.),
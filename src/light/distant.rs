@@ -32,4 +32,20 @@ impl DeltaLight for DistantLight {
     fn sample_li(&self, point: &ShapePoint) -> (Vector3f, Option<Spectrum>, VisibilityTester) {
         (-self.w, Some(self.i), VisibilityTester::new_od(point, &-self.w))
     }
+    fn is_infinite(&self) -> bool {
+        true
+    }
+    // the rays start on a disk facing the light that covers the whole scene
+    fn sample_le(&self, u: &Point2f, world_bound: &Bounds3f) -> (Ray, Normal3f, Spectrum, Float, Float) {
+        let (center, radius) = world_bound.bounding_sphere();
+        let (v1, v2) = coordinate_system(&self.w);
+        let disk = concentric_sample_disk(*u);
+        let o = center + (v1 * disk.x + v2 * disk.y - self.w) * radius;
+        let pdf_pos = 1. / (PI * radius * radius);
+        (Ray::new_od(o, self.w), Normal3f::from(self.w), self.i, pdf_pos, 1.)
+    }
+    fn pdf_le(&self, _: &Ray, world_bound: &Bounds3f) -> (Float, Float) {
+        let (_, radius) = world_bound.bounding_sphere();
+        (1. / (PI * radius * radius), 0.)
+    }
 }
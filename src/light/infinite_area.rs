@@ -3,7 +3,8 @@ use std::sync::Arc;
 #[derive(Debug, Clone)]
 pub struct InfiniteAreaLight {
     map: Arc<dyn Texture<Spectrum>>,
-    map_distribution: Distribution2D
+    map_distribution: Distribution2D,
+    samples: usize,
 }
 
 impl InfiniteAreaLight {
@@ -13,16 +14,41 @@ impl InfiniteAreaLight {
             let size = pixels.size();
             let mut f = Vec::new();
             for (i, s) in pixels.enumerate() {
-                let theta = (i.y as Float + 0.5) / size.y as Float;
+                let theta = (i.y as Float + 0.5) / size.y as Float * PI;
                 f.push(s.y() * theta.sin());
             }
             let f_2d = FixedVec2D::from_vec(f, size.x);
             Distribution2D::from(f_2d)
         };
         Self {
-            map, map_distribution
+            map, map_distribution, samples: 1
         }
     }
+    pub fn with_samples(mut self, samples: usize) -> Self {
+        self.samples = samples;
+        self
+    }
+    // the rows of the distribution are the rows of the map, so it is sampled as (theta, phi)
+    fn sample_phi_theta(&self, u: &Point2f) -> (Point2f, Float) {
+        let (_, pdf, theta_phi) = self.map_distribution.sample_continuous(*u);
+        let phi_theta = Point2f::new(theta_phi.y, theta_phi.x);
+        (phi_theta, self.solid_angle_pdf(pdf, &phi_theta))
+    }
+    fn phi_theta_pdf(&self, phi_theta: &Point2f) -> Float {
+        let pdf = self.map_distribution.continuous_pdf(Point2f::new(phi_theta.y, phi_theta.x));
+        self.solid_angle_pdf(pdf, phi_theta)
+    }
+    fn solid_angle_pdf(&self, pdf: Float, phi_theta: &Point2f) -> Float {
+        let sin_theta = (phi_theta.y * PI).sin();
+        if sin_theta != 0. {
+            pdf / (2. * PI * PI * sin_theta)
+        } else {
+            0.
+        }
+    }
+    fn phi_theta_le(&self, phi_theta: &Point2f) -> Spectrum {
+        self.map.evaluate(&Point2f::new(phi_theta.x, 1. - phi_theta.y))
+    }
 }
 
 impl Light for InfiniteAreaLight {
@@ -32,42 +58,88 @@ impl Light for InfiniteAreaLight {
     fn is_delta(&self) -> bool {
         false
     }
+    fn sample_count(&self) -> usize {
+        self.samples
+    }
+    fn is_infinite(&self) -> bool {
+        true
+    }
+    // the direction is sampled like sample_li, the ray starts on a disk facing it outside the scene
+    fn sample_le(
+        &self,
+        u1: &Point2f,
+        u2: &Point2f,
+        time: Float,
+        world_bound: &Bounds3f,
+    ) -> (Ray, Normal3f, Option<Spectrum>, Float, Float) {
+        let (phi_theta, pdf_dir) = self.sample_phi_theta(u1);
+        let wi = normalize_phi_theta_to_spherical(&phi_theta);
+        if pdf_dir == 0. {
+            return (Ray::new_od(Point3f::new(0., 0., 0.), -wi), Normal3f::from(-wi), None, 0., 0.);
+        }
+        let (center, radius) = world_bound.bounding_sphere();
+        let (v1, v2) = coordinate_system(&-wi);
+        let disk = concentric_sample_disk(*u2);
+        let o = center + (wi + v1 * disk.x + v2 * disk.y) * radius;
+        let ray = Ray::new_od(o, -wi).with_time(time);
+        let pdf_pos = 1. / (PI * radius * radius);
+        (ray, Normal3f::from(-wi), Some(self.phi_theta_le(&phi_theta)), pdf_pos, pdf_dir)
+    }
+    fn pdf_le(&self, ray: &Ray, _: &Normal3f, world_bound: &Bounds3f) -> (Float, Float) {
+        let (_, radius) = world_bound.bounding_sphere();
+        let pdf_dir = self.out_scene_pdf(&Ray::new_od(ray.o, -ray.d));
+        (1. / (PI * radius * radius), pdf_dir)
+    }
     fn le_out_scene(&self, ray: &Ray) -> Option<Spectrum> {
-        let mut uv = spherical_to_normalize_phi_theta(&ray.d.normalize());
-        uv.y = 1. - uv.y;
-        Some(self.map.evaluate(&uv))
+        Some(self.phi_theta_le(&spherical_to_normalize_phi_theta(&ray.d.normalize())))
     }
     fn out_scene_pdf(&self, ray: &Ray) -> Float {
-        let mut uv = spherical_to_normalize_phi_theta(&ray.d.normalize());
-        uv.y = 1. - uv.y;
-        let mut pdf = self.map_distribution.continuous_pdf(uv);
-        let sin_theta = (uv.y * PI).sin();
-        pdf = if sin_theta != 0. {
-            pdf / (2. * PI * PI * sin_theta)
-        }
-        else {
-            0.
-        };
-        pdf
+        self.phi_theta_pdf(&spherical_to_normalize_phi_theta(&ray.d.normalize()))
     }
     fn sample_li(
         &self,
         point: &ShapePoint,
-        sampler: &mut dyn Sampler,
+        u: &Point2f,
     ) -> (Vector3f, Option<Spectrum>, Float, VisibilityTester) {
-        let u = sampler.get_2d();
-        let (_, mut pdf, mut uv) = self.map_distribution.sample_continuous(u);
-        uv.y = 1. - uv.y;
-        let wi = normalize_phi_theta_to_spherical(&uv);
-        let sin_theta = (uv.y * PI).sin();
-        pdf = if sin_theta != 0. {
-            pdf / (2. * PI * PI * sin_theta)
-        }
-        else {
-            0.
-        };
-        let le = self.map.evaluate(&uv);
+        let (phi_theta, pdf) = self.sample_phi_theta(u);
+        let wi = normalize_phi_theta_to_spherical(&phi_theta);
+        let le = self.phi_theta_le(&phi_theta);
         let visibility_tester = VisibilityTester::new_od(point, &wi);
         (wi, Some(le), pdf, visibility_tester)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn infinite_area_samples_its_map() {
+        // a black map but for one texel, rows go down from theta 0 and columns around in phi
+        let (width, height, column, row) = (16, 8, 5, 2);
+        let mut pixels = vec![Spectrum::new(0.); width * height];
+        pixels[row * width + column] = Spectrum::new(1.);
+        let map = Arc::new(ImageTexture::from(FixedVec2D::from_vec(pixels, width)));
+        let light = InfiniteAreaLight::new(map);
+        let point = ShapePoint::new_p_normal(Point3f::new(0., 0., 0.), Normal3f::from(Vector3f::new(0., 0., 1.)));
+        let (theta_range, phi_range) = (PI / height as Float, 2. * PI / width as Float);
+        let mut integral = 0.;
+        let n = 64;
+        for i in 0..n {
+            let u = Point2f::new((i as Float + 0.5) / n as Float, radical_inverse(i, 1));
+            let (wi, le, pdf, _) = light.sample_li(&point, &u);
+            let le = le.unwrap();
+            assert_eq!(le[0], 1.);
+            let ray = Ray::new_od(point.p, wi);
+            assert_eq!(light.le_out_scene(&ray).unwrap()[0], 1.);
+            assert!((light.out_scene_pdf(&ray) - pdf).abs() < 1e-4 * pdf);
+            let theta = wi.z.acos();
+            let phi = wi.y.atan2(wi.x).rem_euclid(2. * PI);
+            assert!(theta >= row as Float * theta_range - 1e-4 && theta <= (row + 1) as Float * theta_range + 1e-4);
+            assert!(phi >= column as Float * phi_range - 1e-4 && phi <= (column + 1) as Float * phi_range + 1e-4);
+            integral += le[0] / pdf / n as Float;
+        }
+        // the estimate of the radiance over the sphere is the solid angle of the texel
+        let solid_angle = phi_range * ((row as Float * theta_range).cos() - ((row + 1) as Float * theta_range).cos());
+        assert!((integral - solid_angle).abs() < 1e-3 * solid_angle, "{} {}", integral, solid_angle);
+    }
+}
@@ -14,7 +14,7 @@ pub trait Light: Sync + Send + std::fmt::Debug {
     fn sample_li(
         &self,
         point: &ShapePoint,
-        sampler: &mut dyn Sampler,
+        u: &Point2f,
     ) -> (Vector3f, Option<Spectrum>, Float, VisibilityTester);
     fn le_out_scene(&self, _: &Ray) -> Option<Spectrum> {
         None
@@ -38,44 +38,76 @@ pub trait Light: Sync + Send + std::fmt::Debug {
     fn le_pdf(&self, point: &Point3f, shape_point: &ShapePoint) -> (Option<Spectrum>, Float) {
         (self.le(shape_point), self.pdf(point, shape_point))
     }
+    fn sample_count(&self) -> usize {
+        1
+    }
+    // lights that surround the scene, their light comes from outside the world bound
+    fn is_infinite(&self) -> bool {
+        false
+    }
+    // a ray of light leaving the light with the normal at its origin, the radiance along it
+    // and the pdfs of the origin by area and of the direction by solid angle
+    fn sample_le(
+        &self,
+        u1: &Point2f,
+        u2: &Point2f,
+        time: Float,
+        world_bound: &Bounds3f,
+    ) -> (Ray, Normal3f, Option<Spectrum>, Float, Float);
+    fn pdf_le(&self, ray: &Ray, n: &Normal3f, world_bound: &Bounds3f) -> (Float, Float);
 }
 
-pub fn parse_light(property_set: &PropertySet) -> Box<dyn Light> {
-    match property_set.get_name().unwrap() {
+pub fn parse_light(property_set: &PropertySet) -> SceneResult<Box<dyn Light>> {
+    Ok(match property_set.get_name()? {
         "point" => {
-            let i = property_set.get_default("I");
+            let i = property_set.get_default("I")?;
             Box::new(PointLight::new(i))
         }
         "distant" => {
-            let i = property_set.get_default("L");
-            let w = property_set
-                .get_value::<Point3f>("from")
-                .map_or(Vector3f::new(0., 0., -1.), |from| {
-                    property_set.get_value::<Point3f>("to").unwrap() - from
-                });
+            let i = property_set.get_default("L")?;
+            let w = match property_set.get_value::<Point3f>("from")? {
+                Some(from) => property_set.get_required::<Point3f>("to")? - from,
+                None => Vector3f::new(0., 0., -1.),
+            };
             Box::new(DistantLight::new(w, i))
         }
         "infinite" => {
-            let map = property_set.get_value("mapname").unwrap_or_else(|| constant_texture(Spectrum::new(1.)));
-            Box::new(InfiniteAreaLight::new(map))
+            let map = property_set.get_value("mapname")?.unwrap_or_else(|| constant_texture(Spectrum::new(1.)));
+            Box::new(InfiniteAreaLight::new(map).with_samples(parse_light_samples(property_set)?))
         }
-        _ => panic!(),
-    }
+        light_type => {
+            return Err(property_set.error(&format!("Unknown light '{}'", light_type)));
+        }
+    })
+}
+fn parse_light_samples(property_set: &PropertySet) -> SceneResult<usize> {
+    let samples = match property_set.get_value("samples")? {
+        Some(samples) => samples,
+        None => property_set.get_value("nsamples")?.unwrap_or(1),
+    };
+    Ok(max(samples, 1))
 }
+
 pub type AreaLightFactory = Arc<dyn Fn(Arc<dyn Shape>) -> Box<dyn Light>>;
-pub fn parse_area_light(property_set: &PropertySet) -> AreaLightFactory {
-    match property_set.get_name().unwrap() {
+pub fn parse_area_light(property_set: &PropertySet) -> SceneResult<AreaLightFactory> {
+    match property_set.get_name()? {
         "diffuse" => {
-            let l = property_set.get_default("L");
-            Arc::new(move |shape| Box::new(AreaLight::new(shape, l)))
+            let l = property_set.get_default("L")?;
+            let samples = parse_light_samples(property_set)?;
+            Ok(Arc::new(move |shape| Box::new(AreaLight::new(shape, l).with_samples(samples))))
         }
-        _ => panic!(),
+        light_type => Err(property_set.error(&format!("Unknown area light '{}'", light_type))),
     }
 }
 
 pub trait DeltaLight: Clone + Transformable {
     fn sample_li(&self, point: &ShapePoint) -> (Vector3f, Option<Spectrum>, VisibilityTester);
     fn visibility_test_ray(&self, point: &Point3f, wi: &Vector3f) -> Ray;
+    fn is_infinite(&self) -> bool {
+        false
+    }
+    fn sample_le(&self, u: &Point2f, world_bound: &Bounds3f) -> (Ray, Normal3f, Spectrum, Float, Float);
+    fn pdf_le(&self, ray: &Ray, world_bound: &Bounds3f) -> (Float, Float);
 }
 impl<T: DeltaLight + 'static + Sync + Send + std::fmt::Debug> Light for T {
     fn box_apply(&self, transform: &Transform) -> Box<dyn Light> {
@@ -90,26 +122,70 @@ impl<T: DeltaLight + 'static + Sync + Send + std::fmt::Debug> Light for T {
     fn sample_li(
         &self,
         point: &ShapePoint,
-        sampler: &mut dyn Sampler,
+        _: &Point2f,
     ) -> (Vector3f, Option<Spectrum>, Float, VisibilityTester) {
-        sampler.get_2d();
         let (wi, s, visibility_tester) = self.sample_li(point);
         (wi, s, 1., visibility_tester)
     }
+    fn is_infinite(&self) -> bool {
+        DeltaLight::is_infinite(self)
+    }
+    // the position or the direction of a delta light is fixed, only the other one is sampled
+    fn sample_le(
+        &self,
+        u1: &Point2f,
+        _: &Point2f,
+        time: Float,
+        world_bound: &Bounds3f,
+    ) -> (Ray, Normal3f, Option<Spectrum>, Float, Float) {
+        let (ray, n, le, pdf_pos, pdf_dir) = DeltaLight::sample_le(self, u1, world_bound);
+        (ray.with_time(time), n, Some(le), pdf_pos, pdf_dir)
+    }
+    fn pdf_le(&self, ray: &Ray, _: &Normal3f, world_bound: &Bounds3f) -> (Float, Float) {
+        DeltaLight::pdf_le(self, ray, world_bound)
+    }
 }
 
-pub struct VisibilityTester(Ray);
+// the point tested against is kept, it is None for lights at infinity
+pub struct VisibilityTester(Ray, Option<ShapePoint>);
 impl VisibilityTester {
     pub fn new(from: &ShapePoint, to: &ShapePoint) -> Self {
-        let from = from.point_offset_by_error(&(to.p - from.p));
-        let to = to.point_offset_by_error(&(from - to.p));
-        Self(Ray::from_to(from, to))
+        let from_point = from.point_offset_by_error(&(to.p - from.p));
+        let to_point = to.point_offset_by_error(&(from_point - to.p));
+        Self(Ray::from_to(from_point, to_point).with_time(from.time), Some(*to))
     }
     pub fn new_od(o: &ShapePoint, d: &Vector3f) -> Self {
-        Self(Ray::new_od(o.point_offset_by_error(d), *d))
+        Self(Ray::new_od(o.point_offset_by_error(d), *d).with_time(o.time), None)
+    }
+    pub fn to(&self) -> Option<&ShapePoint> {
+        self.1.as_ref()
     }
 
     pub fn unoccluded(&self, scene: &Scene) -> bool {
         !scene.intersect_predicate(&self.0)
     }
+    // transmittance through the media between the two points, zero if a surface blocks them
+    pub fn tr(&self, scene: &Scene, medium: Option<Arc<dyn Medium>>, sampler: &mut dyn Sampler) -> Spectrum {
+        let mut ray = self.0;
+        let mut medium = medium;
+        let mut tr = Spectrum::new(1.);
+        loop {
+            let intersect = scene.intersect(&ray);
+            if let Some(medium) = &medium {
+                let t_max = intersect.as_ref().map_or(ray.t_max, |i| i.get_shape_intersect().get_t());
+                tr *= medium.tr(&Ray { t_max, ..ray }, sampler);
+            }
+            match intersect {
+                Some(intersect) if intersect.is_medium_interface() => {
+                    medium = intersect.medium_after(&ray.d, medium);
+                    // the rest of the same segment, starting at the boundary
+                    let t_hit = intersect.get_shape_intersect().get_t();
+                    let o = intersect.get_shape_point().point_offset_by_error(&ray.d);
+                    ray = Ray::new(o, ray.d, ray.t_max - t_hit).with_time(ray.time);
+                }
+                Some(_) => return Spectrum::new(0.),
+                None => return tr,
+            }
+        }
+    }
 }
@@ -36,4 +36,11 @@ impl DeltaLight for PointLight {
         let to = ShapePoint::new_p_normal(self.point, Normal3f::from(-wi));
         (wi.normalize(), Some(i), VisibilityTester::new(point, &to))
     }
+    fn sample_le(&self, u: &Point2f, _: &Bounds3f) -> (Ray, Normal3f, Spectrum, Float, Float) {
+        let d = uniform_sample_sphere(*u);
+        (Ray::new_od(self.point, d), Normal3f::from(d), self.i, 1., uniform_sphere_pdf())
+    }
+    fn pdf_le(&self, _: &Ray, _: &Bounds3f) -> (Float, Float) {
+        (0., uniform_sphere_pdf())
+    }
 }
@@ -0,0 +1,276 @@
+use clap::{App, Arg, ArgMatches};
+use curry_pbrt::*;
+use log::LevelFilter;
+use std::path::Path;
+use std::process::exit;
+use std::str::FromStr;
+use std::time::Duration;
+
+fn parse_arg<T: FromStr>(matches: &ArgMatches, name: &str) -> Option<T> {
+    let value = matches.value_of(name)?;
+    match value.parse() {
+        Ok(t) => Some(t),
+        Err(_) => {
+            eprintln!("Invalid value \"{}\" for --{}", value, name);
+            exit(2);
+        }
+    }
+}
+
+fn parse_resolution(matches: &ArgMatches) -> Option<Vector2u> {
+    let value = matches.value_of("resolution")?;
+    let xy = value
+        .split('x')
+        .map(|s| s.parse::<usize>())
+        .collect::<Result<Vec<_>, _>>();
+    match xy {
+        Ok(xy) if xy.len() == 2 && xy[0] > 0 && xy[1] > 0 => Some(Vector2u::new(xy[0], xy[1])),
+        _ => {
+            eprintln!("Invalid value \"{}\" for --resolution, expected WIDTHxHEIGHT", value);
+            exit(2);
+        }
+    }
+}
+
+fn parse_crop_window(matches: &ArgMatches) -> Option<Bounds2f> {
+    let values = matches.values_of("cropwindow")?;
+    let floats = values.map(|s| s.parse::<Float>()).collect::<Result<Vec<_>, _>>();
+    match floats {
+        Ok(f) if f.iter().all(|f| *f >= 0. && *f <= 1.) && f[0] < f[1] && f[2] < f[3] => Some(
+            Bounds2f::new(&Point2f::new(f[0], f[2]), &Point2f::new(f[1], f[3])),
+        ),
+        _ => {
+            eprintln!("Invalid value for --cropwindow, expected X0 X1 Y0 Y1 in [0, 1]");
+            exit(2);
+        }
+    }
+}
+
+fn parse_pixel_bounds(matches: &ArgMatches) -> Option<Bounds2u> {
+    let values = matches.values_of("pixelbounds")?;
+    let integers = values.map(|s| s.parse::<usize>()).collect::<Result<Vec<_>, _>>();
+    match integers {
+        Ok(u) if u[0] < u[1] && u[2] < u[3] => Some(Bounds2u::new(
+            &Point2u::new(u[0], u[2]),
+            &Point2u::new(u[1], u[3]),
+        )),
+        _ => {
+            eprintln!("Invalid value for --pixelbounds, expected X0 X1 Y0 Y1 in pixels");
+            exit(2);
+        }
+    }
+}
+
+fn parse_progressive(matches: &ArgMatches) -> Option<ProgressiveOptions> {
+    let pass_samples = parse_arg::<usize>(matches, "progressive")?;
+    if pass_samples == 0 {
+        eprintln!("Invalid value \"0\" for --progressive");
+        exit(2);
+    }
+    Some(ProgressiveOptions {
+        pass_samples,
+        time_limit: parse_arg::<f64>(matches, "timelimit").map(Duration::from_secs_f64),
+        noise_threshold: parse_arg(matches, "noise"),
+    })
+}
+
+fn parse_adaptive(matches: &ArgMatches) -> Option<AdaptiveOptions> {
+    Some(AdaptiveOptions {
+        error_threshold: parse_arg(matches, "adaptive")?,
+        max_samples: parse_arg(matches, "maxsamples"),
+    })
+}
+
+fn parse_checkpoint(matches: &ArgMatches) -> Option<CheckpointOptions> {
+    Some(CheckpointOptions {
+        path: matches.value_of("checkpoint")?.into(),
+        interval: Duration::from_secs_f64(parse_arg(matches, "checkpointinterval").unwrap_or(300.)),
+        resume: matches.is_present("resume"),
+    })
+}
+
+fn init_logger(matches: &ArgMatches) {
+    let mut builder = pretty_env_logger::formatted_builder();
+    if matches.is_present("quiet") {
+        builder.filter_level(LevelFilter::Error);
+    } else {
+        builder.filter_level(match matches.occurrences_of("verbose") {
+            0 => LevelFilter::Warn,
+            1 => LevelFilter::Info,
+            2 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
+        });
+        if let Ok(filters) = std::env::var("RUST_LOG") {
+            builder.parse_filters(&filters);
+        }
+    }
+    builder.init();
+}
+
+fn main() {
+    let matches = App::new("curry-pbrt")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("A pbrt renderer implemented in rust")
+        .arg(Arg::with_name("scene").required(true).help("Scene file to render"))
+        .arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .takes_value(true)
+                .help("Overrides the film filename"),
+        )
+        .arg(
+            Arg::with_name("resolution")
+                .short("r")
+                .long("resolution")
+                .takes_value(true)
+                .value_name("WIDTHxHEIGHT")
+                .help("Overrides the film resolution"),
+        )
+        .arg(
+            Arg::with_name("pixelsamples")
+                .short("s")
+                .long("pixelsamples")
+                .takes_value(true)
+                .help("Overrides the sampler pixelsamples"),
+        )
+        .arg(
+            Arg::with_name("maxdepth")
+                .short("d")
+                .long("maxdepth")
+                .takes_value(true)
+                .help("Overrides the integrator maxdepth"),
+        )
+        .arg(
+            Arg::with_name("threads")
+                .short("t")
+                .long("threads")
+                .takes_value(true)
+                .help("Number of render threads, defaults to the number of cores"),
+        )
+        .arg(
+            Arg::with_name("cropwindow")
+                .long("cropwindow")
+                .number_of_values(4)
+                .value_names(&["X0", "X1", "Y0", "Y1"])
+                .allow_hyphen_values(true)
+                .help("Overrides the film cropwindow in NDC"),
+        )
+        .arg(
+            Arg::with_name("pixelbounds")
+                .long("pixelbounds")
+                .number_of_values(4)
+                .value_names(&["X0", "X1", "Y0", "Y1"])
+                .conflicts_with("cropwindow")
+                .help("Overrides the film crop region in pixels"),
+        )
+        .arg(
+            Arg::with_name("fullframe")
+                .long("fullframe")
+                .help("Writes the crop region into a full resolution image"),
+        )
+        .arg(
+            Arg::with_name("progressive")
+                .long("progressive")
+                .takes_value(true)
+                .value_name("SAMPLES")
+                .help("Renders in passes of SAMPLES per pixel, writing the image after each pass"),
+        )
+        .arg(
+            Arg::with_name("timelimit")
+                .long("time-limit")
+                .takes_value(true)
+                .value_name("SECONDS")
+                .requires("progressive")
+                .help("Stops progressive rendering before the next pass would exceed SECONDS"),
+        )
+        .arg(
+            Arg::with_name("noise")
+                .long("noise")
+                .takes_value(true)
+                .value_name("ERROR")
+                .requires("progressive")
+                .help("Stops progressive rendering once the mean relative error drops below ERROR"),
+        )
+        .arg(
+            Arg::with_name("adaptive")
+                .long("adaptive")
+                .takes_value(true)
+                .value_name("ERROR")
+                .conflicts_with("progressive")
+                .help("Adds samples to pixels whose relative error is above ERROR"),
+        )
+        .arg(
+            Arg::with_name("maxsamples")
+                .long("max-samples")
+                .takes_value(true)
+                .value_name("SAMPLES")
+                .requires("adaptive")
+                .help("Caps adaptive sampling per pixel, defaults to 8 times pixelsamples"),
+        )
+        .arg(
+            Arg::with_name("checkpoint")
+                .long("checkpoint")
+                .takes_value(true)
+                .value_name("FILE")
+                .conflicts_with("adaptive")
+                .help("Periodically saves the accumulated film to FILE"),
+        )
+        .arg(
+            Arg::with_name("checkpointinterval")
+                .long("checkpoint-interval")
+                .takes_value(true)
+                .value_name("SECONDS")
+                .requires("checkpoint")
+                .help("Seconds between checkpoints, defaults to 300"),
+        )
+        .arg(
+            Arg::with_name("resume")
+                .long("resume")
+                .requires("checkpoint")
+                .help("Continues the render from the checkpoint file if it exists"),
+        )
+        .arg(
+            Arg::with_name("quiet")
+                .short("q")
+                .long("quiet")
+                .conflicts_with("verbose")
+                .help("Only print errors"),
+        )
+        .arg(
+            Arg::with_name("verbose")
+                .short("v")
+                .long("verbose")
+                .multiple(true)
+                .help("Increases logging verbosity, can be repeated"),
+        )
+        .get_matches();
+    init_logger(&matches);
+    if let Some(threads) = parse_arg::<usize>(&matches, "threads") {
+        if let Err(e) = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+        {
+            eprintln!("{}", e);
+            exit(2);
+        }
+    }
+    let options = RenderOptions {
+        output: matches.value_of("output").map(String::from),
+        resolution: parse_resolution(&matches),
+        pixel_samples: parse_arg(&matches, "pixelsamples"),
+        max_depth: parse_arg(&matches, "maxdepth"),
+        crop_window: parse_crop_window(&matches),
+        pixel_bounds: parse_pixel_bounds(&matches),
+        full_frame: matches.is_present("fullframe"),
+        progressive: parse_progressive(&matches),
+        adaptive: parse_adaptive(&matches),
+        checkpoint: parse_checkpoint(&matches),
+        quiet: matches.is_present("quiet"),
+    };
+    let scene = matches.value_of("scene").unwrap();
+    if let Err(e) = render_from_file_with_options(Path::new(scene), &options) {
+        eprintln!("{}", e);
+        exit(1);
+    }
+}
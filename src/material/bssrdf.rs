@@ -0,0 +1,372 @@
+use crate::*;
+use rayon::prelude::*;
+use std::sync::Arc;
+
+fn fresnel_moment1(eta: Float) -> Float {
+    let (eta2, eta3, eta4, eta5) = (eta * eta, eta * eta * eta, eta.powi(4), eta.powi(5));
+    if eta < 1. {
+        0.45966 - 1.73965 * eta + 3.37668 * eta2 - 3.904945 * eta3 + 2.49277 * eta4 - 0.68441 * eta5
+    } else {
+        -4.61686 + 11.1136 * eta - 10.4646 * eta2 + 5.11455 * eta3 - 1.27198 * eta4 + 0.12746 * eta5
+    }
+}
+
+fn fresnel_moment2(eta: Float) -> Float {
+    let (eta2, eta3, eta4, eta5) = (eta * eta, eta * eta * eta, eta.powi(4), eta.powi(5));
+    if eta < 1. {
+        0.27614 - 0.87350 * eta + 1.12077 * eta2 - 0.65095 * eta3 + 0.07883 * eta4 + 0.04860 * eta5
+    } else {
+        let (r_eta, r_eta2, r_eta3) = (1. / eta, 1. / eta2, 1. / eta3);
+        -547.033 + 45.3087 * r_eta3 - 218.725 * r_eta2 + 458.843 * r_eta + 404.557 * eta - 189.519 * eta2
+            + 54.9327 * eta3
+            - 9.00603 * eta4
+            + 0.63942 * eta5
+    }
+}
+
+// multiple scattering of the photon beam diffusion profile at radius r
+fn beam_diffusion_ms(sigma_s: Float, sigma_a: Float, g: Float, eta: Float, r: Float) -> Float {
+    let n_samples = 100;
+    let sigmap_s = sigma_s * (1. - g);
+    let sigmap_t = sigma_a + sigmap_s;
+    let rhop = sigmap_s / sigmap_t;
+    let d_g = (2. * sigma_a + sigmap_s) / (3. * sigmap_t * sigmap_t);
+    let sigma_tr = max(0., sigma_a / d_g).sqrt();
+    let (fm1, fm2) = (fresnel_moment1(eta), fresnel_moment2(eta));
+    let ze = -2. * d_g * (1. + 3. * fm2) / (1. - 2. * fm1);
+    let (c_phi, c_e) = (0.25 * (1. - 2. * fm1), 0.5 * (1. - 3. * fm2));
+    let mut ed = 0.;
+    for i in 0..n_samples {
+        // sample the depth of the real point source exponentially
+        let zr = -(1. - (i as Float + 0.5) / n_samples as Float).ln() / sigmap_t;
+        let zv = -zr + 2. * ze;
+        let (dr, dv) = ((r * r + zr * zr).sqrt(), (r * r + zv * zv).sqrt());
+        let phi_d = INV_PI / 4. / d_g * ((-sigma_tr * dr).exp() / dr - (-sigma_tr * dv).exp() / dv);
+        let ed_n = INV_PI / 4.
+            * (zr * (1. + sigma_tr * dr) * (-sigma_tr * dr).exp() / (dr * dr * dr)
+                - zv * (1. + sigma_tr * dv) * (-sigma_tr * dv).exp() / (dv * dv * dv));
+        let e = phi_d * c_phi + ed_n * c_e;
+        let kappa = 1. - (-2. * sigmap_t * (dr + zr)).exp();
+        ed += kappa * rhop * rhop * e;
+    }
+    ed / n_samples as Float
+}
+
+// single scattering of the photon beam diffusion profile at radius r
+fn beam_diffusion_ss(sigma_s: Float, sigma_a: Float, g: Float, eta: Float, r: Float) -> Float {
+    let n_samples = 100;
+    let sigma_t = sigma_a + sigma_s;
+    let rho = sigma_s / sigma_t;
+    let t_crit = r * max(0., eta * eta - 1.).sqrt();
+    let fresnel = FresnelDielectric::new(1., eta);
+    let mut ess = 0.;
+    for i in 0..n_samples {
+        let ti = t_crit - (1. - (i as Float + 0.5) / n_samples as Float).ln() / sigma_t;
+        let d = (r * r + ti * ti).sqrt();
+        let cos_theta_o = ti / d;
+        ess += rho * (-sigma_t * (d + t_crit)).exp() / (d * d)
+            * henyey_greenstein(cos_theta_o, g)
+            * (1. - fresnel.evaluate(-cos_theta_o))
+            * cos_theta_o.abs();
+    }
+    ess / n_samples as Float
+}
+
+// radial scattering profiles of a unit extinction medium over albedo and optical radius
+#[derive(Debug)]
+pub struct BSSRDFTable {
+    rho_samples: Vec<Float>,
+    radius_samples: Vec<Float>,
+    profile: Vec<Float>,
+    rho_eff: Vec<Float>,
+    profile_cdf: Vec<Float>,
+}
+
+impl BSSRDFTable {
+    pub fn beam_diffusion(g: Float, eta: Float) -> Self {
+        let (n_rho, n_radius) = (100, 64);
+        let mut radius_samples = vec![0., 2.5e-3];
+        for i in 2..n_radius {
+            radius_samples.push(radius_samples[i - 1] * 1.2);
+        }
+        let rho_samples: Vec<Float> = (0..n_rho)
+            .map(|i| (1. - (-8. * i as Float / (n_rho - 1) as Float).exp()) / (1. - (-8. as Float).exp()))
+            .collect();
+        let rows: Vec<(Vec<Float>, Vec<Float>, Float)> = rho_samples
+            .par_iter()
+            .map(|&rho| {
+                let profile: Vec<Float> = radius_samples
+                    .iter()
+                    .map(|&r| {
+                        2. * PI
+                            * r
+                            * (beam_diffusion_ss(rho, 1. - rho, g, eta, r) + beam_diffusion_ms(rho, 1. - rho, g, eta, r))
+                    })
+                    .collect();
+                let mut cdf = vec![0.; n_radius];
+                let rho_eff = integrate_catmull_rom(&radius_samples, &profile, &mut cdf);
+                (profile, cdf, rho_eff)
+            })
+            .collect();
+        let mut table = Self {
+            rho_samples,
+            radius_samples,
+            profile: Vec::new(),
+            rho_eff: Vec::new(),
+            profile_cdf: Vec::new(),
+        };
+        for (profile, cdf, rho_eff) in rows {
+            table.profile.extend(profile);
+            table.profile_cdf.extend(cdf);
+            table.rho_eff.push(rho_eff);
+        }
+        table
+    }
+    fn eval_profile(&self, rho_index: isize, radius_index: isize) -> Float {
+        self.profile[rho_index as usize * self.radius_samples.len() + radius_index as usize]
+    }
+    // the albedo giving the effective albedo rho_eff of a semi infinite slab
+    fn invert_rho_eff(&self, rho_eff: Float) -> Float {
+        invert_catmull_rom(&self.rho_samples, &self.rho_eff, rho_eff)
+    }
+}
+
+// the sigma_a and sigma_s reproducing the diffuse reflectance kd with mean free path mfp
+pub fn subsurface_from_diffuse(table: &BSSRDFTable, kd: Spectrum, mfp: Spectrum) -> (Spectrum, Spectrum) {
+    let mut sigma_a = [0.; 3];
+    let mut sigma_s = [0.; 3];
+    for c in 0..3 {
+        let rho = table.invert_rho_eff(kd[c]);
+        sigma_s[c] = rho / mfp[c];
+        sigma_a[c] = (1. - rho) / mfp[c];
+    }
+    (Spectrum::from(sigma_a), Spectrum::from(sigma_s))
+}
+
+// the scattering of light leaving the surface at the exit point, with the normal of the exit point as wo
+struct SeparableBSSRDFAdapter {
+    eta: Float,
+}
+
+impl BxDF for SeparableBSSRDFAdapter {
+    fn f(&self, _wo: &Vector3f, wi: &Vector3f) -> Option<Spectrum> {
+        let c = 1. - 2. * fresnel_moment1(1. / self.eta);
+        let sw = (1. - FresnelDielectric::new(1., self.eta).evaluate(cos_theta(wi))) / (c * PI);
+        // the radiance leaving a denser medium is scaled by eta^2
+        Some(Spectrum::new(sw * self.eta * self.eta))
+    }
+}
+
+// a separable bssrdf with the radial profile looked up in a BSSRDFTable
+pub struct TabulatedBSSRDF {
+    po: Point3f,
+    ns: Vector3f,
+    ss: Vector3f,
+    ts: Vector3f,
+    eta: Float,
+    sigma_t: Spectrum,
+    rho: Spectrum,
+    table: Arc<BSSRDFTable>,
+}
+
+impl TabulatedBSSRDF {
+    pub fn new(
+        shape_intersect: &ShapeIntersect,
+        eta: Float,
+        sigma_a: Spectrum,
+        sigma_s: Spectrum,
+        table: Arc<BSSRDFTable>,
+    ) -> Self {
+        let ns = shape_intersect.get_normal().0;
+        let (ss, ts) = coordinate_system(&ns);
+        let sigma_t = sigma_a + sigma_s;
+        let rho = Spectrum::from([0, 1, 2].map(|c| if sigma_t[c] != 0. { sigma_s[c] / sigma_t[c] } else { 0. }));
+        Self {
+            po: *shape_intersect.get_point(),
+            ns,
+            ss,
+            ts,
+            eta,
+            sigma_t,
+            rho,
+            table,
+        }
+    }
+    // the profile of channel ch at radius r without the sigma_t^2 scale, and the effective albedo
+    fn profile(&self, ch: usize, r: Float) -> Option<(Float, Float)> {
+        let r_optical = r * self.sigma_t[ch];
+        let (rho_offset, rho_weights) = catmull_rom_weights(&self.table.rho_samples, self.rho[ch])?;
+        let (radius_offset, radius_weights) = catmull_rom_weights(&self.table.radius_samples, r_optical)?;
+        let mut sr = 0.;
+        let mut rho_eff = 0.;
+        for (i, rho_weight) in rho_weights.iter().enumerate() {
+            if *rho_weight == 0. {
+                continue;
+            }
+            let rho_index = rho_offset + i as isize;
+            rho_eff += self.table.rho_eff[rho_index as usize] * rho_weight;
+            for (j, radius_weight) in radius_weights.iter().enumerate() {
+                if *radius_weight != 0. {
+                    sr += rho_weight * radius_weight * self.table.eval_profile(rho_index, radius_offset + j as isize);
+                }
+            }
+        }
+        // the table holds the profile times 2 pi r
+        if r_optical != 0. {
+            sr /= 2. * PI * r_optical;
+        }
+        Some((sr, rho_eff))
+    }
+    fn sr(&self, r: Float) -> Spectrum {
+        let sr = [0, 1, 2].map(|ch| {
+            let sigma_t = self.sigma_t[ch];
+            self.profile(ch, r).map_or(0., |(sr, _)| max(0., sr * sigma_t * sigma_t))
+        });
+        Spectrum::from(sr)
+    }
+    fn sample_sr(&self, ch: usize, u: Float) -> Option<Float> {
+        if self.sigma_t[ch] == 0. {
+            return None;
+        }
+        let table = &self.table;
+        let (r, _) = sample_catmull_rom_2d(
+            &table.rho_samples,
+            &table.radius_samples,
+            &table.profile,
+            &table.profile_cdf,
+            self.rho[ch],
+            u,
+        )?;
+        Some(r / self.sigma_t[ch])
+    }
+    fn pdf_sr(&self, ch: usize, r: Float) -> Float {
+        let sigma_t = self.sigma_t[ch];
+        self.profile(ch, r)
+            .map_or(0., |(sr, rho_eff)| max(0., sr * sigma_t * sigma_t / rho_eff))
+    }
+    // the pdf of sampling pi over all projection axes and channels
+    fn pdf_sp(&self, pi: &ShapePoint) -> Float {
+        let d = self.po - pi.p;
+        let d_local = Vector3f::new(self.ss.dot(&d), self.ts.dot(&d), self.ns.dot(&d));
+        let n_local = Vector3f::new(self.ss.dot(&pi.n.0), self.ts.dot(&pi.n.0), self.ns.dot(&pi.n.0));
+        let r_proj = [
+            (d_local.y * d_local.y + d_local.z * d_local.z).sqrt(),
+            (d_local.z * d_local.z + d_local.x * d_local.x).sqrt(),
+            (d_local.x * d_local.x + d_local.y * d_local.y).sqrt(),
+        ];
+        let axis_prob = [0.25, 0.25, 0.5];
+        let mut pdf = 0.;
+        for axis in 0..3 {
+            for ch in 0..3 {
+                pdf += self.pdf_sr(ch, r_proj[axis]) * n_local[axis].abs() * axis_prob[axis] / 3.;
+            }
+        }
+        pdf
+    }
+    // samples an exit point on the surfaces entry shares its material with, the probe ray is
+    // cast along a projection axis through a disk around the entry point,
+    // returns the spatial profile, its pdf and the exit intersection
+    pub fn sample_s(
+        &self,
+        scene: &Scene,
+        entry: &PrimitiveIntersect,
+        u1: Float,
+        u2: &Point2f,
+    ) -> Option<(Spectrum, Float, PrimitiveIntersect)> {
+        let (vx, vy, vz, u1) = if u1 < 0.5 {
+            (self.ss, self.ts, self.ns, u1 * 2.)
+        } else if u1 < 0.75 {
+            (self.ts, self.ns, self.ss, (u1 - 0.5) * 4.)
+        } else {
+            (self.ns, self.ss, self.ts, (u1 - 0.75) * 4.)
+        };
+        let (ch, u1) = sample_usize_remap(u1, 3);
+        let r = self.sample_sr(ch, u2.x)?;
+        if r < 0. {
+            return None;
+        }
+        let phi = 2. * PI * u2.y;
+        let r_max = self.sample_sr(ch, 0.999)?;
+        if r >= r_max {
+            return None;
+        }
+        let l = 2. * (r_max * r_max - r * r).sqrt();
+        let p_start = self.po + (vx * phi.cos() + vy * phi.sin()) * r - vz * (l / 2.);
+        let p_target = p_start + vz * l;
+        // every hit with the same material along the probe segment is a candidate
+        let mut chain = Vec::new();
+        let mut ray = Ray::from_to(p_start, p_target);
+        while let Some(intersect) = scene.intersect(&ray) {
+            let o = intersect.get_shape_point().point_offset_by_error(&ray.d);
+            if intersect.same_material(entry) {
+                chain.push(intersect);
+            }
+            ray = Ray::from_to(o, p_target);
+        }
+        if chain.is_empty() {
+            return None;
+        }
+        let n_found = chain.len();
+        let selected = min((u1 * n_found as Float) as usize, n_found - 1);
+        let pi = chain.swap_remove(selected);
+        let pdf = self.pdf_sp(pi.get_shape_point()) / n_found as Float;
+        let sp = self.sr((self.po - pi.get_shape_point().p).magnitude());
+        Some((sp, pdf, pi))
+    }
+    // the bsdf at the exit point, light leaves it with the directional profile of the bssrdf
+    pub fn exit_bsdf(&self, exit: &ShapePoint) -> BSDF {
+        let mut bsdf = BSDF::new(exit.n, exit.n);
+        bsdf.add_bxdf(Arc::new(SeparableBSSRDFAdapter { eta: self.eta }));
+        bsdf
+    }
+}
+
+// measured sigma_s and sigma_a in mm^-1 (Jensen et al. 2001), the scattering is the reduced one so g is 0
+const MEASURED_SUBSURFACE: [(&str, [Float; 3], [Float; 3]); 12] = [
+    ("Apple", [2.29, 2.39, 1.97], [0.0030, 0.0034, 0.046]),
+    ("Chicken1", [0.15, 0.21, 0.38], [0.015, 0.077, 0.19]),
+    ("Chicken2", [0.19, 0.25, 0.32], [0.018, 0.088, 0.20]),
+    ("Cream", [7.38, 5.47, 3.15], [0.0002, 0.0028, 0.0163]),
+    ("Ketchup", [0.18, 0.07, 0.03], [0.061, 0.97, 1.45]),
+    ("Marble", [2.19, 2.62, 3.00], [0.0021, 0.0041, 0.0071]),
+    ("Potato", [0.68, 0.70, 0.55], [0.0024, 0.0090, 0.12]),
+    ("Skimmilk", [0.70, 1.22, 1.90], [0.0014, 0.0025, 0.0142]),
+    ("Skin1", [0.74, 0.88, 1.01], [0.032, 0.17, 0.48]),
+    ("Skin2", [1.09, 1.59, 1.79], [0.013, 0.070, 0.145]),
+    ("Spectralon", [11.6, 20.4, 14.9], [0.00, 0.00, 0.00]),
+    ("Wholemilk", [2.55, 3.21, 3.77], [0.0011, 0.0024, 0.014]),
+];
+
+// sigma_a and sigma_s of a named measured material
+pub fn measured_subsurface(name: &str) -> Option<(Spectrum, Spectrum)> {
+    MEASURED_SUBSURFACE
+        .iter()
+        .find(|(measured, _, _)| *measured == name)
+        .map(|(_, sigma_s, sigma_a)| (Spectrum::from(*sigma_a), Spectrum::from(*sigma_s)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn diffuse_reflectance_round_trips() {
+        let table = BSSRDFTable::beam_diffusion(0., 1.33);
+        // the effective albedo grows with the albedo
+        for i in 1..table.rho_eff.len() {
+            assert!(table.rho_eff[i] >= table.rho_eff[i - 1]);
+        }
+        let kd = Spectrum::from([0.2, 0.5, 0.8]);
+        let (sigma_a, sigma_s) = subsurface_from_diffuse(&table, kd, Spectrum::new(1.));
+        for c in 0..3 {
+            let rho = sigma_s[c] / (sigma_a[c] + sigma_s[c]);
+            let (offset, weights) = catmull_rom_weights(&table.rho_samples, rho).unwrap();
+            let mut rho_eff = 0.;
+            for (i, weight) in weights.iter().enumerate() {
+                if *weight != 0. {
+                    rho_eff += table.rho_eff[(offset + i as isize) as usize] * weight;
+                }
+            }
+            assert!((rho_eff - kd[c]).abs() < 1e-3);
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use crate::*;
 
-pub trait MicrofacetDistribution {
+pub trait MicrofacetDistribution: Send + Sync {
     fn d(&self, wh: &Vector3f) -> Float;
     fn lambda(&self, w: &Vector3f) -> Float;
     fn g(&self, wo: &Vector3f, wi: &Vector3f) -> Float {
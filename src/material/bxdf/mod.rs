@@ -15,7 +15,7 @@ pub enum BxDFType {
     Transmit,
 }
 
-pub trait BxDF {
+pub trait BxDF: Send + Sync {
     fn f(&self, wo: &Vector3f, wi: &Vector3f) -> Option<Spectrum>;
     fn sample_f(&self, wo: &Vector3f, u: &Point2f) -> (Vector3f, Option<Spectrum>, Float) {
         let (mut wi, pdf) = cosine_sample_hemisphere(*u);
@@ -35,8 +35,13 @@ pub trait BxDF {
         let f = self.f(wo, &wi);
         (wi, f, pdf)
     }
-    fn pdf(&self, _wo: &Vector3f, wi: &Vector3f) -> Float {
-        wi.z * INV_PI
+    // sample_f keeps wi on the side of wo when reflecting and moves it to the other side when transmitting
+    fn pdf(&self, wo: &Vector3f, wi: &Vector3f) -> Float {
+        let same_hemisphere = wo.z * wi.z > 0.;
+        match (self.bxdf_type(), same_hemisphere) {
+            (BxDFType::Reflect, true) | (BxDFType::Transmit, false) => wi.z.abs() * INV_PI,
+            _ => 0.,
+        }
     }
     fn f_pdf(&self, wo: &Vector3f, wi: &Vector3f) -> (Option<Spectrum>, Float) {
         (self.f(wo, wi), self.pdf(wo, wi))
@@ -212,6 +217,17 @@ impl BSDF {
         };
         (wi, f, pdf * internal_pdf, is_delta)
     }
+    // the non delta part, with the pdf sample_f picks wi with when it picks a non delta bxdf
+    pub fn f(&self, wo: &Vector3f, wi: &Vector3f) -> Spectrum {
+        self.no_delta_f_pdf(wo, wi).0.unwrap_or_else(|| Spectrum::new(0.))
+    }
+    pub fn pdf(&self, wo: &Vector3f, wi: &Vector3f) -> Float {
+        if self.bxdfs.is_empty() {
+            return 0.;
+        }
+        let no_delta = self.bxdfs.len() as Float / (self.bxdfs.len() + self.delta_bxdfs.len()) as Float;
+        self.no_delta_f_pdf(wo, wi).1 * no_delta
+    }
     pub fn is_all_delta(&self) -> bool {
         self.bxdfs.is_empty()
     }
@@ -269,7 +285,7 @@ impl BSDF {
     }
 }
 
-pub trait DeltaBxDF {
+pub trait DeltaBxDF: Send + Sync {
     fn sample_f(&self, wo: &Vector3f) -> Option<(Vector3f, Spectrum)>;
 }
 
@@ -298,3 +314,24 @@ impl DeltaBxDF for ScaledDeltaBxDF {
         self.0.sample_f(wo).map(|(wi, f)| (wi, f * self.1))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn bxdf_pdf_matches_sample_f() {
+        let reflection = LambertianReflection::new(Spectrum::new(0.5));
+        let transmission = LambertianTransmission::new(Spectrum::new(0.5));
+        let bxdfs: [&dyn BxDF; 2] = [&reflection, &transmission];
+        for wo in &[Vector3f::new(0.3, 0.2, 0.8).normalize(), Vector3f::new(-0.1, 0.4, -0.6).normalize()] {
+            for bxdf in bxdfs.iter() {
+                for u in &[Point2f::new(0.2, 0.7), Point2f::new(0.9, 0.1)] {
+                    let (wi, _, pdf) = bxdf.sample_f(wo, u);
+                    assert!((bxdf.pdf(wo, &wi) - pdf).abs() < 1e-5);
+                    // the mirrored direction is never sampled
+                    assert_eq!(bxdf.pdf(wo, &Vector3f::new(wi.x, wi.y, -wi.z)), 0.);
+                }
+            }
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use crate::*;
-pub trait Fresnel {
+pub trait Fresnel: Send + Sync {
     fn evaluate(&self, cos_i: Float) -> Float;
 }
 pub struct FresnelDielectric {
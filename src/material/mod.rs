@@ -1,6 +1,7 @@
 use crate::*;
 use std::collections::HashMap;
 use std::sync::Arc;
+mod bssrdf;
 mod bxdf;
 mod glass;
 mod matte;
@@ -9,7 +10,9 @@ mod translucent;
 mod uber;
 mod plastic;
 mod mix;
+mod subsurface;
 
+pub use bssrdf::*;
 pub use bxdf::*;
 pub use glass::*;
 pub use matte::*;
@@ -19,9 +22,14 @@ pub use translucent::*;
 pub use uber::*;
 pub use plastic::*;
 pub use mix::*;
+pub use subsurface::*;
 
 pub trait Material: Debug + Sync + Send {
     fn compute_scattering_functions(&self, shape_intersect: &ShapeIntersect) -> BSDF;
+    // materials with light traveling beneath the surface
+    fn compute_bssrdf(&self, _shape_intersect: &ShapeIntersect) -> Option<TabulatedBSSRDF> {
+        None
+    }
     fn box_clone(&self) -> Box<dyn Material>;
 }
 
@@ -29,9 +37,9 @@ pub fn parse_material<M: TextureMap>(
     property_set: &PropertySet,
     texture_map: &M,
     material_map: &HashMap<String, Arc<dyn Material>>,
-) -> Box<dyn Material> {
+) -> SceneResult<Box<dyn Material>> {
     parse_material_with_type(
-        property_set.get_name().unwrap(),
+        property_set.get_name()?,
         property_set,
         texture_map,
         material_map,
@@ -41,48 +49,48 @@ pub fn parse_make_named_material<'a, M: TextureMap>(
     property_set: &'a PropertySet,
     map: &M,
     material_map: &HashMap<String, Arc<dyn Material>>,
-) -> (&'a str, Box<dyn Material>) {
-    let material_type: String = property_set.get_value("type").unwrap();
-    let name = property_set.get_name().unwrap();
-    (
+) -> SceneResult<(&'a str, Box<dyn Material>)> {
+    let material_type: String = property_set.get_required("type")?;
+    let name = property_set.get_name()?;
+    Ok((
         name,
-        parse_material_with_type(&material_type, property_set, map, material_map),
-    )
+        parse_material_with_type(&material_type, property_set, map, material_map)?,
+    ))
 }
 pub fn parse_material_with_type<M: TextureMap>(
     material_type: &str,
     property_set: &PropertySet,
     texture_map: &M,
     material_map: &HashMap<String, Arc<dyn Material>>,
-) -> Box<dyn Material> {
-    match material_type {
+) -> SceneResult<Box<dyn Material>> {
+    Ok(match material_type {
         "matte" => {
-            let kd = get_texture(property_set, "Kd", texture_map)
+            let kd = get_texture(property_set, "Kd", texture_map)?
                 .unwrap_or_else(|| constant_texture(Spectrum::new(0.5)));
-            let sigma = get_texture(property_set, "sigma", texture_map)
+            let sigma = get_texture(property_set, "sigma", texture_map)?
                 .unwrap_or_else(|| constant_texture(0.));
             Box::new(MatteMaterial::new(kd, sigma))
         }
         "glass" => {
-            let r = get_texture(property_set, "Kr", texture_map)
+            let r = get_texture(property_set, "Kr", texture_map)?
                 .unwrap_or_else(|| constant_texture(Spectrum::new(0.5)));
-            let t = get_texture(property_set, "Kt", texture_map)
+            let t = get_texture(property_set, "Kt", texture_map)?
                 .unwrap_or_else(|| constant_texture(Spectrum::new(1.)));
-            let eta = get_texture(property_set, "index", texture_map)
+            let eta = get_texture(property_set, "index", texture_map)?
                 .unwrap_or_else(|| constant_texture(1.5));
             Box::new(GlassMaterial::new(r, t, eta))
         }
         "mirror" => {
-            let r = get_texture(property_set, "Kr", texture_map)
+            let r = get_texture(property_set, "Kr", texture_map)?
                 .unwrap_or_else(|| constant_texture(Spectrum::new(1.)));
             Box::new(MirrorMaterial::new(r))
         }
         "plastic" => {
-            let kd = get_texture(property_set, "Kd", texture_map)
+            let kd = get_texture(property_set, "Kd", texture_map)?
                 .unwrap_or_else(|| constant_texture(Spectrum::new(0.25)));
-            let ks = get_texture(property_set, "Ks", texture_map)
+            let ks = get_texture(property_set, "Ks", texture_map)?
                 .unwrap_or_else(|| constant_texture(Spectrum::new(0.25)));
-            let roughness = get_texture(property_set, "roughness", texture_map)
+            let roughness = get_texture(property_set, "roughness", texture_map)?
                 .unwrap_or_else(|| constant_texture(0.1));
             Box::new(PlasticMaterial {
                 kd,
@@ -91,23 +99,24 @@ pub fn parse_material_with_type<M: TextureMap>(
             })
         }
         "uber" => {
-            let kd = get_texture(property_set, "Kd", texture_map)
+            let kd = get_texture(property_set, "Kd", texture_map)?
                 .unwrap_or_else(|| constant_texture(Spectrum::new(0.25)));
-            let ks = get_texture(property_set, "Ks", texture_map)
+            let ks = get_texture(property_set, "Ks", texture_map)?
                 .unwrap_or_else(|| constant_texture(Spectrum::new(0.25)));
-            let kr = get_texture(property_set, "Kr", texture_map)
+            let kr = get_texture(property_set, "Kr", texture_map)?
                 .unwrap_or_else(|| constant_texture(Spectrum::new(0.)));
-            let kt = get_texture(property_set, "Kt", texture_map)
+            let kt = get_texture(property_set, "Kt", texture_map)?
                 .unwrap_or_else(|| constant_texture(Spectrum::new(0.)));
-            let roughness = get_texture(property_set, "roughness", texture_map)
+            let roughness = get_texture(property_set, "roughness", texture_map)?
                 .unwrap_or_else(|| constant_texture(0.1));
-            let uroughness = get_texture(property_set, "uroughness", texture_map);
-            let vroughness = get_texture(property_set, "uroughness", texture_map);
-            let eta = get_texture(property_set, "eta", texture_map).unwrap_or_else(|| {
-                get_texture(property_set, "index", texture_map)
-                    .unwrap_or_else(|| constant_texture(1.5))
-            });
-            let opacity = get_texture(property_set, "opacity", texture_map)
+            let uroughness = get_texture(property_set, "uroughness", texture_map)?;
+            let vroughness = get_texture(property_set, "uroughness", texture_map)?;
+            let eta = match get_texture(property_set, "eta", texture_map)? {
+                Some(eta) => eta,
+                None => get_texture(property_set, "index", texture_map)?
+                    .unwrap_or_else(|| constant_texture(1.5)),
+            };
+            let opacity = get_texture(property_set, "opacity", texture_map)?
                 .unwrap_or_else(|| constant_texture(Spectrum::new(1.)));
             Box::new(UberMaterial {
                 kd,
@@ -122,15 +131,15 @@ pub fn parse_material_with_type<M: TextureMap>(
             })
         }
         "translucent" => {
-            let kd = get_texture(property_set, "Kd", texture_map)
+            let kd = get_texture(property_set, "Kd", texture_map)?
                 .unwrap_or_else(|| constant_texture(Spectrum::new(0.25)));
-            let ks = get_texture(property_set, "Ks", texture_map)
+            let ks = get_texture(property_set, "Ks", texture_map)?
                 .unwrap_or_else(|| constant_texture(Spectrum::new(0.25)));
-            let reflect = get_texture(property_set, "reflect", texture_map)
+            let reflect = get_texture(property_set, "reflect", texture_map)?
                 .unwrap_or_else(|| constant_texture(Spectrum::new(0.5)));
-            let transmit = get_texture(property_set, "transmit", texture_map)
+            let transmit = get_texture(property_set, "transmit", texture_map)?
                 .unwrap_or_else(|| constant_texture(Spectrum::new(0.5)));
-            let roughness = get_texture(property_set, "roughness", texture_map)
+            let roughness = get_texture(property_set, "roughness", texture_map)?
                 .unwrap_or_else(|| constant_texture(0.1));
             Box::new(TranslucentMaterial {
                 kd,
@@ -141,14 +150,93 @@ pub fn parse_material_with_type<M: TextureMap>(
             })
         }
         "mix" => {
-            let scale = get_texture(property_set, "amount", texture_map)
+            let scale = get_texture(property_set, "amount", texture_map)?
                 .unwrap_or_else(|| constant_texture(Spectrum::new(0.)));
-            let namedmaterial1: String = property_set.get_value("namedmaterial1").unwrap();
-            let m1 = material_map.get(&namedmaterial1).unwrap().clone();
-            let namedmaterial2: String = property_set.get_value("namedmaterial2").unwrap();
-            let m2 = material_map.get(&namedmaterial2).unwrap().clone();
+            let namedmaterial1: String = property_set.get_required("namedmaterial1")?;
+            let m1 = get_named_material(property_set, material_map, &namedmaterial1)?;
+            let namedmaterial2: String = property_set.get_required("namedmaterial2")?;
+            let m2 = get_named_material(property_set, material_map, &namedmaterial2)?;
             Box::new(MixMaterial{m1, m2, scale})
         }
-        _ => panic!(),
-    }
+        "subsurface" => {
+            let mut g = property_set.get_value("g")?.unwrap_or(0.);
+            let (sigma_a, sigma_s) = match property_set.get_value::<String>("name")? {
+                Some(name) => {
+                    // the measured scattering is already reduced
+                    g = 0.;
+                    measured_subsurface(&name)
+                        .ok_or_else(|| property_set.error(&format!("Unknown measured subsurface '{}'", name)))?
+                }
+                None => (Spectrum::from([0.0011, 0.0024, 0.014]), Spectrum::from([2.55, 3.21, 3.77])),
+            };
+            let eta = property_set.get_value("eta")?.unwrap_or(1.33);
+            let (kr, kt, uroughness, vroughness, remap_roughness) = parse_dielectric(property_set, texture_map)?;
+            Box::new(SubsurfaceMaterial {
+                scale: property_set.get_value("scale")?.unwrap_or(1.),
+                eta,
+                sigma_a: get_texture(property_set, "sigma_a", texture_map)?
+                    .unwrap_or_else(|| constant_texture(sigma_a)),
+                sigma_s: get_texture(property_set, "sigma_s", texture_map)?
+                    .unwrap_or_else(|| constant_texture(sigma_s)),
+                kr,
+                kt,
+                uroughness,
+                vroughness,
+                remap_roughness,
+                table: Arc::new(BSSRDFTable::beam_diffusion(g, eta)),
+            })
+        }
+        "kdsubsurface" => {
+            let g = property_set.get_value("g")?.unwrap_or(0.);
+            let eta = property_set.get_value("eta")?.unwrap_or(1.33);
+            let (kr, kt, uroughness, vroughness, remap_roughness) = parse_dielectric(property_set, texture_map)?;
+            Box::new(KdSubsurfaceMaterial {
+                scale: property_set.get_value("scale")?.unwrap_or(1.),
+                eta,
+                kd: get_texture(property_set, "Kd", texture_map)?
+                    .unwrap_or_else(|| constant_texture(Spectrum::new(0.5))),
+                mfp: get_texture(property_set, "mfp", texture_map)?
+                    .unwrap_or_else(|| constant_texture(Spectrum::new(1.))),
+                kr,
+                kt,
+                uroughness,
+                vroughness,
+                remap_roughness,
+                table: Arc::new(BSSRDFTable::beam_diffusion(g, eta)),
+            })
+        }
+        _ => {
+            return Err(property_set.error(&format!("Unknown material '{}'", material_type)));
+        }
+    })
+}
+
+type DielectricTextures = (
+    Arc<dyn Texture<Spectrum>>,
+    Arc<dyn Texture<Spectrum>>,
+    Arc<dyn Texture<Float>>,
+    Arc<dyn Texture<Float>>,
+    bool,
+);
+
+// Kr, Kt, uroughness, vroughness and remaproughness of the subsurface materials
+fn parse_dielectric<M: TextureMap>(property_set: &PropertySet, texture_map: &M) -> SceneResult<DielectricTextures> {
+    Ok((
+        get_texture(property_set, "Kr", texture_map)?.unwrap_or_else(|| constant_texture(Spectrum::new(1.))),
+        get_texture(property_set, "Kt", texture_map)?.unwrap_or_else(|| constant_texture(Spectrum::new(1.))),
+        get_texture(property_set, "uroughness", texture_map)?.unwrap_or_else(|| constant_texture(0.)),
+        get_texture(property_set, "vroughness", texture_map)?.unwrap_or_else(|| constant_texture(0.)),
+        property_set.get_value("remaproughness")?.unwrap_or(true),
+    ))
+}
+
+pub fn get_named_material(
+    property_set: &PropertySet,
+    material_map: &HashMap<String, Arc<dyn Material>>,
+    name: &str,
+) -> SceneResult<Arc<dyn Material>> {
+    material_map
+        .get(name)
+        .cloned()
+        .ok_or_else(|| property_set.error(&format!("Unknown named material '{}'", name)))
 }
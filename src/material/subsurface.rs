@@ -0,0 +1,123 @@
+use crate::*;
+use std::sync::Arc;
+
+// a dielectric boundary over a scattering medium, the light below the surface is carried by the bssrdf
+#[derive(Debug, Clone)]
+pub struct SubsurfaceMaterial {
+    pub scale: Float,
+    pub eta: Float,
+    pub sigma_a: Arc<dyn Texture<Spectrum>>,
+    pub sigma_s: Arc<dyn Texture<Spectrum>>,
+    pub kr: Arc<dyn Texture<Spectrum>>,
+    pub kt: Arc<dyn Texture<Spectrum>>,
+    pub uroughness: Arc<dyn Texture<Float>>,
+    pub vroughness: Arc<dyn Texture<Float>>,
+    pub remap_roughness: bool,
+    pub table: Arc<BSSRDFTable>,
+}
+
+// like SubsurfaceMaterial, but the scattering coefficients follow from the diffuse reflectance kd
+// and the mean free path mfp
+#[derive(Debug, Clone)]
+pub struct KdSubsurfaceMaterial {
+    pub scale: Float,
+    pub eta: Float,
+    pub kd: Arc<dyn Texture<Spectrum>>,
+    pub mfp: Arc<dyn Texture<Spectrum>>,
+    pub kr: Arc<dyn Texture<Spectrum>>,
+    pub kt: Arc<dyn Texture<Spectrum>>,
+    pub uroughness: Arc<dyn Texture<Float>>,
+    pub vroughness: Arc<dyn Texture<Float>>,
+    pub remap_roughness: bool,
+    pub table: Arc<BSSRDFTable>,
+}
+
+fn dielectric_bsdf(
+    intersect: &ShapeIntersect,
+    eta: Float,
+    kr: &Arc<dyn Texture<Spectrum>>,
+    kt: &Arc<dyn Texture<Spectrum>>,
+    uroughness: &Arc<dyn Texture<Float>>,
+    vroughness: &Arc<dyn Texture<Float>>,
+    remap_roughness: bool,
+) -> BSDF {
+    let uv = intersect.get_uv();
+    let mut bsdf = BSDF::new(*intersect.get_normal(), *intersect.get_normal());
+    let r = kr.evaluate(uv);
+    let t = kt.evaluate(uv);
+    let mut roughu = uroughness.evaluate(uv);
+    let mut roughv = vroughness.evaluate(uv);
+    if roughu == 0. && roughv == 0. {
+        if !r.is_black() {
+            bsdf.add_delta_bxdf(Arc::new(SpecularReflection::new(r, Box::new(FresnelDielectric::new(1., eta)))));
+        }
+        if !t.is_black() {
+            // the specular transmission keeps radiance, the bssrdf expects it compressed by 1 / eta^2
+            bsdf.add_delta_bxdf(Arc::new(SpecularTransmission::new(t / (eta * eta), 1., eta)));
+        }
+    } else {
+        if remap_roughness {
+            roughu = TrowbridgeReitzDistribution::roughness_to_alpha(roughu);
+            roughv = TrowbridgeReitzDistribution::roughness_to_alpha(roughv);
+        }
+        let distribution = TrowbridgeReitzDistribution::new(roughu, roughv);
+        if !r.is_black() {
+            bsdf.add_bxdf(Arc::new(MicrofacetReflection::new(
+                r,
+                distribution,
+                FresnelDielectric::new(1., eta),
+            )));
+        }
+        if !t.is_black() {
+            bsdf.add_bxdf(Arc::new(MicrofacetTransmission::new(t, distribution, 1., eta)));
+        }
+    }
+    bsdf
+}
+
+impl Material for SubsurfaceMaterial {
+    fn compute_scattering_functions(&self, intersect: &ShapeIntersect) -> BSDF {
+        dielectric_bsdf(
+            intersect,
+            self.eta,
+            &self.kr,
+            &self.kt,
+            &self.uroughness,
+            &self.vroughness,
+            self.remap_roughness,
+        )
+    }
+    fn compute_bssrdf(&self, intersect: &ShapeIntersect) -> Option<TabulatedBSSRDF> {
+        let uv = intersect.get_uv();
+        let sigma_a = self.sigma_a.evaluate(uv) * self.scale;
+        let sigma_s = self.sigma_s.evaluate(uv) * self.scale;
+        Some(TabulatedBSSRDF::new(intersect, self.eta, sigma_a, sigma_s, self.table.clone()))
+    }
+    fn box_clone(&self) -> Box<dyn Material> {
+        Box::new(self.clone())
+    }
+}
+
+impl Material for KdSubsurfaceMaterial {
+    fn compute_scattering_functions(&self, intersect: &ShapeIntersect) -> BSDF {
+        dielectric_bsdf(
+            intersect,
+            self.eta,
+            &self.kr,
+            &self.kt,
+            &self.uroughness,
+            &self.vroughness,
+            self.remap_roughness,
+        )
+    }
+    fn compute_bssrdf(&self, intersect: &ShapeIntersect) -> Option<TabulatedBSSRDF> {
+        let uv = intersect.get_uv();
+        let kd = self.kd.evaluate(uv).map_move(|f| clamp(f, 0., 1.));
+        let mfp = self.mfp.evaluate(uv) * self.scale;
+        let (sigma_a, sigma_s) = subsurface_from_diffuse(&self.table, kd, mfp);
+        Some(TabulatedBSSRDF::new(intersect, self.eta, sigma_a, sigma_s, self.table.clone()))
+    }
+    fn box_clone(&self) -> Box<dyn Material> {
+        Box::new(self.clone())
+    }
+}
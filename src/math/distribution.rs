@@ -24,8 +24,14 @@ impl From<Vec<Float>> for Distribution1D {
         let f_sum = *cdf.last().unwrap();
         let mut pdf: Vec<Float> = Vec::new();
         for i in 0..f.len() {
-            cdf[i] /= f_sum;
-            pdf.push(f[i] / (n * f_sum));
+            // all zero, say a black row of an environment map, is sampled uniformly instead of giving nan
+            if f_sum == 0. {
+                cdf[i] = (i + 1) as Float / n;
+                pdf.push(1. / n);
+            } else {
+                cdf[i] /= f_sum;
+                pdf.push(f[i] / (n * f_sum));
+            }
         }
         Self { pdf, cdf, f_sum, n }
     }
@@ -42,14 +48,17 @@ impl Distribution1D {
     pub fn f_sum(&self) -> Float {
         self.f_sum
     }
+    // the remap is where u falls in its bucket, in [0, 1) so a continuous sample stays in the bucket,
+    // buckets without weight are never picked
     pub fn sample_remap(&self, u: Float) -> (usize, Float, Float) {
         for (i, cdf) in self.cdf.iter().enumerate() {
-            if u <= *cdf {
+            if u < *cdf {
                 let pdf = self.pdf[i];
-                return (i, pdf, (cdf - u) / pdf);
+                return (i, pdf, min((u - (cdf - pdf)) / pdf, ONE_MINUS_EPSILON).max(0.));
             }
         }
-        (self.pdf.len() - 1, *self.pdf.last().unwrap(), 1.)
+        let i = self.pdf.iter().rposition(|pdf| *pdf > 0.).unwrap_or(self.pdf.len() - 1);
+        (i, self.pdf[i], ONE_MINUS_EPSILON)
     }
     pub fn sample_continuous(&self, u: Float) -> (usize, Float, Float) {
         let (i, pdf, remap) = self.sample_remap(u);
@@ -0,0 +1,175 @@
+use crate::*;
+
+// the last index i in [0, size - 2] with pred(i) true, pred must be true then false over the range
+pub fn find_interval(size: usize, pred: &dyn Fn(usize) -> bool) -> usize {
+    let mut first = 0;
+    let mut len = size;
+    while len > 0 {
+        let half = len >> 1;
+        let middle = first + half;
+        if pred(middle) {
+            first = middle + 1;
+            len -= half + 1;
+        } else {
+            len = half;
+        }
+    }
+    clamp(first as isize - 1, 0, size as isize - 2) as usize
+}
+
+// the offset of the first of the four spline nodes around x and their weights,
+// a weight is zero where its node would be out of range so the offset can be -1
+pub fn catmull_rom_weights(nodes: &[Float], x: Float) -> Option<(isize, [Float; 4])> {
+    let size = nodes.len();
+    if x.is_nan() || x < nodes[0] || x > nodes[size - 1] {
+        return None;
+    }
+    let idx = find_interval(size, &|i| nodes[i] <= x);
+    let (x0, x1) = (nodes[idx], nodes[idx + 1]);
+    let t = (x - x0) / (x1 - x0);
+    let (t2, t3) = (t * t, t * t * t);
+    let mut weights = [0., 2. * t3 - 3. * t2 + 1., -2. * t3 + 3. * t2, 0.];
+    if idx > 0 {
+        let w0 = (t3 - 2. * t2 + t) * (x1 - x0) / (x1 - nodes[idx - 1]);
+        weights[0] = -w0;
+        weights[2] += w0;
+    } else {
+        let w0 = t3 - 2. * t2 + t;
+        weights[1] -= w0;
+        weights[2] += w0;
+    }
+    if idx + 2 < size {
+        let w3 = (t3 - t2) * (x1 - x0) / (nodes[idx + 2] - x0);
+        weights[1] -= w3;
+        weights[3] = w3;
+    } else {
+        let w3 = t3 - t2;
+        weights[1] -= w3;
+        weights[2] += w3;
+    }
+    Some((idx as isize - 1, weights))
+}
+
+// the derivatives at both ends of the spline segment i
+fn catmull_rom_derivatives(x: &[Float], values: &dyn Fn(usize) -> Float, i: usize) -> (Float, Float) {
+    let n = x.len();
+    let (f0, f1) = (values(i), values(i + 1));
+    let width = x[i + 1] - x[i];
+    let d0 = if i > 0 {
+        width * (f1 - values(i - 1)) / (x[i + 1] - x[i - 1])
+    } else {
+        f1 - f0
+    };
+    let d1 = if i + 2 < n {
+        width * (values(i + 2) - f0) / (x[i + 2] - x[i])
+    } else {
+        f1 - f0
+    };
+    (d0, d1)
+}
+
+// samples the second dimension of a spline over a 2d grid of values, the first dimension is fixed at alpha,
+// returns the sample and its pdf
+pub fn sample_catmull_rom_2d(
+    nodes1: &[Float],
+    nodes2: &[Float],
+    values: &[Float],
+    cdf: &[Float],
+    alpha: Float,
+    u: Float,
+) -> Option<(Float, Float)> {
+    let size2 = nodes2.len();
+    let (offset, weights) = catmull_rom_weights(nodes1, alpha)?;
+    let interpolate = |array: &[Float], idx: usize| {
+        let mut value = 0.;
+        for (i, weight) in weights.iter().enumerate() {
+            if *weight != 0. {
+                value += array[(offset + i as isize) as usize * size2 + idx] * weight;
+            }
+        }
+        value
+    };
+    let maximum = interpolate(cdf, size2 - 1);
+    let u = u * maximum;
+    let idx = find_interval(size2, &|i| interpolate(cdf, i) <= u);
+    let (f0, f1) = (interpolate(values, idx), interpolate(values, idx + 1));
+    let (x0, x1) = (nodes2[idx], nodes2[idx + 1]);
+    let width = x1 - x0;
+    let (d0, d1) = catmull_rom_derivatives(nodes2, &|i| interpolate(values, i), idx);
+    let u = (u - interpolate(cdf, idx)) / width;
+    // invert the integral over the segment with newton bisection
+    let mut t = if f0 != f1 {
+        (f0 - max(0., f0 * f0 + 2. * u * (f1 - f0)).sqrt()) / (f0 - f1)
+    } else {
+        u / f0
+    };
+    let (mut a, mut b) = (0., 1.);
+    let fhat = loop {
+        if t.is_nan() || t < a || t > b {
+            t = 0.5 * (a + b);
+        }
+        let big_fhat = t
+            * (f0
+                + t * (0.5 * d0
+                    + t * ((1. / 3.) * (-2. * d0 - d1) + f1 - f0 + t * (0.25 * (d0 + d1) + 0.5 * (f0 - f1)))));
+        let fhat = f0 + t * (d0 + t * (-2. * d0 - d1 + 3. * (f1 - f0) + t * (d0 + d1 + 2. * (f0 - f1))));
+        if (big_fhat - u).abs() < 1e-6 || b - a < 1e-6 {
+            break fhat;
+        }
+        if big_fhat - u < 0. {
+            a = t;
+        } else {
+            b = t;
+        }
+        t -= (big_fhat - u) / fhat;
+    };
+    Some((x0 + width * t, fhat / maximum))
+}
+
+// fills cdf with the running integral of the spline and returns the whole integral
+pub fn integrate_catmull_rom(x: &[Float], values: &[Float], cdf: &mut [Float]) -> Float {
+    let mut sum = 0.;
+    cdf[0] = 0.;
+    for i in 0..x.len() - 1 {
+        let (f0, f1) = (values[i], values[i + 1]);
+        let width = x[i + 1] - x[i];
+        let (d0, d1) = catmull_rom_derivatives(x, &|i| values[i], i);
+        sum += ((d0 - d1) * (1. / 12.) + (f0 + f1) * 0.5) * width;
+        cdf[i + 1] = sum;
+    }
+    sum
+}
+
+// the x where a monotonic spline takes the value u
+pub fn invert_catmull_rom(x: &[Float], values: &[Float], u: Float) -> Float {
+    let n = x.len();
+    if u.is_nan() || u <= values[0] {
+        return x[0];
+    } else if u >= values[n - 1] {
+        return x[n - 1];
+    }
+    let i = find_interval(n, &|i| values[i] <= u);
+    let (f0, f1) = (values[i], values[i + 1]);
+    let width = x[i + 1] - x[i];
+    let (d0, d1) = catmull_rom_derivatives(x, &|i| values[i], i);
+    let (mut a, mut b, mut t): (Float, Float, Float) = (0., 1., 0.5);
+    loop {
+        if t.is_nan() || t <= a || t >= b {
+            t = 0.5 * (a + b);
+        }
+        let (t2, t3) = (t * t, t * t * t);
+        let big_fhat = (2. * t3 - 3. * t2 + 1.) * f0 + (-2. * t3 + 3. * t2) * f1 + (t3 - 2. * t2 + t) * d0 + (t3 - t2) * d1;
+        let fhat = (6. * t2 - 6. * t) * f0 + (-6. * t2 + 6. * t) * f1 + (3. * t2 - 4. * t + 1.) * d0 + (3. * t2 - 2. * t) * d1;
+        if (big_fhat - u).abs() < 1e-6 || b - a < 1e-6 {
+            break;
+        }
+        if big_fhat - u < 0. {
+            a = t;
+        } else {
+            b = t;
+        }
+        t -= (big_fhat - u) / fhat;
+    }
+    x[i] + t * width
+}
+
@@ -4,6 +4,8 @@ use num_traits::FromPrimitive;
 
 mod distribution;
 pub use distribution::*;
+mod interpolation;
+pub use interpolation::*;
 
 pub fn lerp<T: FromPrimitive + ClosedMul + ClosedAdd + ClosedSub + Copy>(
     t: T,
@@ -114,6 +116,15 @@ pub fn uniform_sample_hemisphere(u: Point2f) -> Vector3f {
     let phi = 2. * PI * u.y;
     Vector3f::new(r * phi.cos(), r * phi.sin(), z)
 }
+pub fn uniform_sample_sphere(u: Point2f) -> Vector3f {
+    let z = 1. - 2. * u.x;
+    let r = max(0., 1. - z * z).sqrt();
+    let phi = 2. * PI * u.y;
+    Vector3f::new(r * phi.cos(), r * phi.sin(), z)
+}
+pub fn uniform_sphere_pdf() -> Float {
+    INV_PI / 4.
+}
 pub fn cosine_sample_hemisphere(u: Point2f) -> (Vector3f, Float) {
     let d = concentric_sample_disk(u);
     let z = (1. - d.coords.magnitude_squared()).sqrt();
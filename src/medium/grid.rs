@@ -0,0 +1,167 @@
+use crate::*;
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+// density on a regular grid spanning the bounds p0 p1 of the medium space, sampled with
+// delta tracking and measured with ratio tracking against the majorant max density
+#[derive(Debug, Clone)]
+pub struct GridDensityMedium {
+    sigma_s: Spectrum,
+    sigma_t: Float,
+    g: Float,
+    resolution: [usize; 3],
+    density: Vec<Float>,
+    max_density: Float,
+    bounds: Bounds3f,
+    world_to_medium: Transform,
+}
+
+impl GridDensityMedium {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        sigma_a: Spectrum,
+        sigma_s: Spectrum,
+        g: Float,
+        resolution: [usize; 3],
+        density: Vec<Float>,
+        bounds: Bounds3f,
+        medium_to_world: &Transform,
+    ) -> Self {
+        let max_density = density.iter().cloned().fold(0., max);
+        Self {
+            sigma_s,
+            // like pbrt, the extinction is gray and taken from the first channel
+            sigma_t: (sigma_a + sigma_s)[0],
+            g,
+            resolution,
+            density,
+            max_density,
+            bounds,
+            world_to_medium: medium_to_world.clone().inverse(),
+        }
+    }
+    fn d(&self, x: Integer, y: Integer, z: Integer) -> Float {
+        let [nx, ny, nz] = self.resolution;
+        if x < 0 || y < 0 || z < 0 || x as usize >= nx || y as usize >= ny || z as usize >= nz {
+            return 0.;
+        }
+        self.density[(z as usize * ny + y as usize) * nx + x as usize]
+    }
+    // trilinear interpolation of the grid, p is in [0, 1]^3
+    fn density(&self, p: &Point3f) -> Float {
+        let samples: [Float; 3] = [0, 1, 2].map(|i| p[i] * self.resolution[i] as Float - 0.5);
+        let (x, y, z) = (samples[0].floor(), samples[1].floor(), samples[2].floor());
+        let (dx, dy, dz) = (samples[0] - x, samples[1] - y, samples[2] - z);
+        let (x, y, z) = (x as Integer, y as Integer, z as Integer);
+        // lerp(t, a, b) weights a by t
+        let d00 = lerp(dx, self.d(x + 1, y, z), self.d(x, y, z));
+        let d10 = lerp(dx, self.d(x + 1, y + 1, z), self.d(x, y + 1, z));
+        let d01 = lerp(dx, self.d(x + 1, y, z + 1), self.d(x, y, z + 1));
+        let d11 = lerp(dx, self.d(x + 1, y + 1, z + 1), self.d(x, y + 1, z + 1));
+        let d0 = lerp(dy, d10, d00);
+        let d1 = lerp(dy, d11, d01);
+        lerp(dz, d1, d0)
+    }
+    // the ray in medium space with a normalized world direction, so t is the world distance,
+    // and the range of t inside the grid
+    fn medium_ray(&self, ray: &Ray) -> Option<(Ray, Float, Float)> {
+        if self.max_density <= 0. || self.sigma_t <= 0. {
+            return None;
+        }
+        let length = ray.d.magnitude();
+        let ray = Ray::new(ray.o, ray.d / length, ray.t_max * length)
+            .with_time(ray.time)
+            .apply(&self.world_to_medium);
+        let (t_min, t_max) = self.bounds.intersect(&ray)?;
+        Some((ray, t_min, t_max))
+    }
+    fn step(&self, t: Float, rng: &mut Rng) -> Float {
+        t - (1. - rng.uniform_float()).ln() / (self.max_density * self.sigma_t)
+    }
+}
+
+// tracking needs an unbounded number of random numbers, they come from a generator
+// seeded by one dimension of the sampler
+fn tracking_rng(sampler: &mut dyn Sampler) -> Rng {
+    Rng::new(u64::from(sampler.get_1d().to_bits()))
+}
+
+impl Medium for GridDensityMedium {
+    fn tr(&self, ray: &Ray, sampler: &mut dyn Sampler) -> Spectrum {
+        let mut rng = tracking_rng(sampler);
+        let (medium_ray, t_min, t_max) = match self.medium_ray(ray) {
+            Some(medium_ray) => medium_ray,
+            None => return Spectrum::new(1.),
+        };
+        let mut tr = 1.;
+        let mut t = t_min;
+        loop {
+            t = self.step(t, &mut rng);
+            if t >= t_max {
+                break;
+            }
+            let density = self.density(&self.bounds.offset(medium_ray.eval(t)));
+            tr *= 1. - max(0., density / self.max_density);
+            // russian roulette once the transmittance gets low
+            if tr < 0.1 {
+                let q = max(0.05, 1. - tr);
+                if rng.uniform_float() < q {
+                    return Spectrum::new(0.);
+                }
+                tr /= 1. - q;
+            }
+        }
+        Spectrum::new(tr)
+    }
+    fn sample(&self, ray: &Ray, sampler: &mut dyn Sampler) -> (Spectrum, Option<MediumInteraction>) {
+        let mut rng = tracking_rng(sampler);
+        let (medium_ray, t_min, t_max) = match self.medium_ray(ray) {
+            Some(medium_ray) => medium_ray,
+            None => return (Spectrum::new(1.), None),
+        };
+        let length = ray.d.magnitude();
+        let mut t = t_min;
+        loop {
+            t = self.step(t, &mut rng);
+            if t >= t_max {
+                return (Spectrum::new(1.), None);
+            }
+            let density = self.density(&self.bounds.offset(medium_ray.eval(t)));
+            if density / self.max_density > rng.uniform_float() {
+                let interaction = MediumInteraction::new(ray.eval(t / length), ray.time, HenyeyGreenstein::new(self.g));
+                return (self.sigma_s / self.sigma_t, Some(interaction));
+            }
+        }
+    }
+}
+
+// nx * ny * nz little endian 32 bit floats, x varies fastest
+pub fn read_density_file(path: &Path, count: usize) -> std::io::Result<Vec<Float>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    (0..count).map(|_| reader.read_f32::<LittleEndian>()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn ratio_tracking_matches_optical_depth() {
+        let sigma_a = Spectrum::new(0.3);
+        let sigma_s = Spectrum::new(0.2);
+        let bounds = Bounds3f::new(&Point3f::new(0., 0., 0.), &Point3f::new(1., 1., 1.));
+        let grid = GridDensityMedium::new(sigma_a, sigma_s, 0., [2, 2, 2], vec![2.; 8], bounds, &Transform::default());
+        // the density falls off to zero within half a voxel of the bounds, 1.75 along this ray
+        let ray = Ray::new(Point3f::new(0.5, 0.5, -1.), Vector3f::new(0., 0., 3.), 1.);
+        let mut sampler = RandomSampler::new(1);
+        let n = 2000;
+        let mut tr = 0.;
+        for _ in 0..n {
+            sampler.next_sample();
+            tr += grid.tr(&ray, &mut sampler)[0];
+        }
+        let expected = (-(0.3 + 0.2) * 1.75 as Float).exp();
+        assert!((tr / n as Float - expected).abs() < 0.03);
+    }
+}
@@ -0,0 +1,51 @@
+use crate::*;
+
+#[derive(Debug, Clone)]
+pub struct HomogeneousMedium {
+    sigma_s: Spectrum,
+    sigma_t: Spectrum,
+    g: Float,
+}
+
+impl HomogeneousMedium {
+    pub fn new(sigma_a: Spectrum, sigma_s: Spectrum, g: Float) -> Self {
+        Self {
+            sigma_s,
+            sigma_t: sigma_a + sigma_s,
+            g,
+        }
+    }
+}
+
+// distance along the ray, which may be infinite when the ray leaves the scene
+fn ray_distance(ray: &Ray, t: Float) -> Float {
+    min(t * ray.d.magnitude(), Float::MAX)
+}
+
+impl Medium for HomogeneousMedium {
+    fn tr(&self, ray: &Ray, _: &mut dyn Sampler) -> Spectrum {
+        let distance = ray_distance(ray, ray.t_max);
+        (self.sigma_t * -distance).map_move(Float::exp)
+    }
+    fn sample(&self, ray: &Ray, sampler: &mut dyn Sampler) -> (Spectrum, Option<MediumInteraction>) {
+        // the distance is sampled for one channel chosen uniformly, the pdf averages all of them
+        let channel = sampler.get_usize(3);
+        let u = sampler.get_1d();
+        let length = ray.d.magnitude();
+        let distance = -(1. - u).ln() / self.sigma_t[channel];
+        let t = min(distance / length, ray.t_max);
+        let sampled_medium = t < ray.t_max;
+        let tr = (self.sigma_t * -ray_distance(ray, t)).map_move(Float::exp);
+        let density = if sampled_medium { self.sigma_t * tr } else { tr };
+        let mut pdf = (density[0] + density[1] + density[2]) / 3.;
+        if pdf == 0. {
+            pdf = 1.;
+        }
+        if sampled_medium {
+            let interaction = MediumInteraction::new(ray.eval(t), ray.time, HenyeyGreenstein::new(self.g));
+            (tr * self.sigma_s / pdf, Some(interaction))
+        } else {
+            (tr / pdf, None)
+        }
+    }
+}
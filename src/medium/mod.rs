@@ -0,0 +1,228 @@
+mod grid;
+mod homogeneous;
+use crate::*;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+pub use grid::*;
+pub use homogeneous::*;
+
+pub trait Medium: Sync + Send + std::fmt::Debug {
+    // transmittance from the ray origin to ray.eval(ray.t_max)
+    fn tr(&self, ray: &Ray, sampler: &mut dyn Sampler) -> Spectrum;
+    // samples a scattering point before ray.t_max, the weight is the path throughput of the segment
+    fn sample(&self, ray: &Ray, sampler: &mut dyn Sampler) -> (Spectrum, Option<MediumInteraction>);
+}
+
+pub type NamedMedia = HashMap<String, Arc<dyn Medium>>;
+
+#[derive(Debug, Clone, Copy)]
+pub struct HenyeyGreenstein {
+    g: Float,
+}
+
+pub fn henyey_greenstein(cos_theta: Float, g: Float) -> Float {
+    let denom = 1. + g * g + 2. * g * cos_theta;
+    INV_PI / 4. * (1. - g * g) / (denom * denom.sqrt())
+}
+
+// wo and wi both point away from the scattering point, so forward scattering is wi = -wo
+impl HenyeyGreenstein {
+    pub fn new(g: Float) -> Self {
+        Self { g }
+    }
+    pub fn p(&self, wo: &Vector3f, wi: &Vector3f) -> Float {
+        henyey_greenstein(wo.dot(wi), self.g)
+    }
+    // the sampled direction and its pdf, which is also the value of the phase function
+    pub fn sample_p(&self, wo: &Vector3f, u: &Point2f) -> (Vector3f, Float) {
+        let g = self.g;
+        let cos_theta = if g.abs() < 1e-3 {
+            1. - 2. * u.x
+        } else {
+            let sqr_term = (1. - g * g) / (1. + g - 2. * g * u.x);
+            -(1. + g * g - sqr_term * sqr_term) / (2. * g)
+        };
+        let sin_theta = max(0., 1. - cos_theta * cos_theta).sqrt();
+        let phi = 2. * PI * u.y;
+        let (v1, v2) = coordinate_system(wo);
+        let wi = v1 * sin_theta * phi.cos() + v2 * sin_theta * phi.sin() + wo * cos_theta;
+        (wi, henyey_greenstein(cos_theta, g))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MediumInteraction {
+    pub p: Point3f,
+    pub time: Float,
+    pub phase: HenyeyGreenstein,
+}
+
+impl MediumInteraction {
+    pub fn new(p: Point3f, time: Float, phase: HenyeyGreenstein) -> Self {
+        Self { p, time, phase }
+    }
+    // a point without normal and error bound, rays leave it without any offset
+    pub fn shape_point(&self) -> ShapePoint {
+        let mut shape_point = ShapePoint::new_p_normal(self.p, Normal3f(Vector3f::new(0., 0., 0.)));
+        shape_point.time = self.time;
+        shape_point
+    }
+}
+
+// the media on both sides of a surface, the normal points to the outside, None is vacuum
+#[derive(Debug, Clone, Default)]
+pub struct MediumInterface {
+    pub inside: Option<Arc<dyn Medium>>,
+    pub outside: Option<Arc<dyn Medium>>,
+}
+
+fn same_medium(lhs: &Option<Arc<dyn Medium>>, rhs: &Option<Arc<dyn Medium>>) -> bool {
+    match (lhs, rhs) {
+        (Some(lhs), Some(rhs)) => Arc::as_ptr(lhs) as *const () == Arc::as_ptr(rhs) as *const (),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+impl MediumInterface {
+    pub fn new(inside: Option<Arc<dyn Medium>>, outside: Option<Arc<dyn Medium>>) -> Self {
+        Self { inside, outside }
+    }
+    pub fn is_transition(&self) -> bool {
+        !same_medium(&self.inside, &self.outside)
+    }
+    // the medium a ray leaving the surface in direction w travels through
+    pub fn medium(&self, w: &Vector3f, n: &Normal3f) -> Option<Arc<dyn Medium>> {
+        if w.dot(n) > 0. {
+            self.outside.clone()
+        } else {
+            self.inside.clone()
+        }
+    }
+}
+
+// the grid of a heterogeneous medium is the nx * ny * nz density array or the raw float file densityfile
+fn parse_density_grid(property_set: &PropertySet) -> SceneResult<([usize; 3], Vec<Float>)> {
+    let mut resolution = [1; 3];
+    for (i, name) in ["nx", "ny", "nz"].iter().enumerate() {
+        let n = property_set.get_value::<Integer>(name)?.unwrap_or(1);
+        if n < 1 {
+            return Err(property_set.error("Density grid resolution must be positive"));
+        }
+        resolution[i] = n as usize;
+    }
+    let count = resolution.iter().product();
+    let density = match (
+        property_set.get_value::<Vec<Float>>("density")?,
+        property_set.get_value::<PathBuf>("densityfile")?,
+    ) {
+        (Some(density), _) => density,
+        (None, Some(path)) => read_density_file(&path, count).map_err(|e| {
+            property_set.error(&format!("Cant read density file {}: {}", path.display(), e))
+        })?,
+        (None, None) => return Err(property_set.error("Heterogeneous medium needs density or densityfile")),
+    };
+    if density.len() != count {
+        return Err(property_set.error(&format!(
+            "Expected {} density values, found {}",
+            count,
+            density.len()
+        )));
+    }
+    Ok((resolution, density))
+}
+
+// medium_to_world is the current transform at the directive, it places the grid of heterogeneous media
+pub fn parse_make_named_medium<'a>(
+    property_set: &'a PropertySet,
+    medium_to_world: &Transform,
+) -> SceneResult<(&'a str, Box<dyn Medium>)> {
+    let name = property_set.get_name()?;
+    let medium_type: String = property_set.get_required("type")?;
+    let scale = property_set.get_value("scale")?.unwrap_or(1.);
+    let sigma_a = property_set
+        .get_value("sigma_a")?
+        .unwrap_or_else(|| Spectrum::from([0.0011, 0.0024, 0.014]))
+        * scale;
+    let sigma_s = property_set
+        .get_value("sigma_s")?
+        .unwrap_or_else(|| Spectrum::from([2.55, 3.21, 3.77]))
+        * scale;
+    let g = property_set.get_value("g")?.unwrap_or(0.);
+    let medium: Box<dyn Medium> = match medium_type.as_str() {
+        "homogeneous" => Box::new(HomogeneousMedium::new(sigma_a, sigma_s, g)),
+        "heterogeneous" => {
+            let p0 = property_set.get_value("p0")?.unwrap_or_else(|| Point3f::new(0., 0., 0.));
+            let p1 = property_set.get_value("p1")?.unwrap_or_else(|| Point3f::new(1., 1., 1.));
+            let (resolution, density) = parse_density_grid(property_set)?;
+            Box::new(GridDensityMedium::new(
+                sigma_a,
+                sigma_s,
+                g,
+                resolution,
+                density,
+                Bounds3f::new(&p0, &p1),
+                medium_to_world,
+            ))
+        }
+        medium_type => {
+            return Err(property_set.error(&format!("Unknown medium '{}'", medium_type)));
+        }
+    };
+    Ok((name, medium))
+}
+
+// MediumInterface "inside" "outside", a single name is used for both sides and "" is vacuum
+pub fn parse_medium_interface(property_set: &PropertySet, named_media: &NamedMedia) -> SceneResult<MediumInterface> {
+    let mut property_set = property_set.clone();
+    let inside: String = property_set.get_no_type_value()?;
+    let outside: String = property_set.get_optional_no_type_value()?.unwrap_or_else(|| inside.clone());
+    let get = |name: &str| -> SceneResult<Option<Arc<dyn Medium>>> {
+        if name.is_empty() {
+            return Ok(None);
+        }
+        named_media
+            .get(name)
+            .cloned()
+            .map(Some)
+            .ok_or_else(|| property_set.error(&format!("Unknown medium '{}'", name)))
+    };
+    Ok(MediumInterface::new(get(&inside)?, get(&outside)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn medium_interface_names_both_sides() {
+        let mut named_media = NamedMedia::new();
+        let fog: Arc<dyn Medium> = Arc::new(HomogeneousMedium::new(Spectrum::new(1.), Spectrum::new(1.), 0.));
+        named_media.insert(String::from("fog"), fog);
+        let parse = |interface: &str| {
+            let segments = read_scene_str(&format!("MediumInterface {}", interface), std::path::Path::new("medium.pbrt"))?;
+            parse_medium_interface(segments[0].get_object_by_type("MediumInterface").unwrap(), &named_media)
+        };
+        let interface = parse(r#""fog""#).unwrap();
+        assert!(interface.inside.is_some() && interface.outside.is_some());
+        let interface = parse(r#""fog" """#).unwrap();
+        assert!(interface.inside.is_some() && interface.outside.is_none());
+        let interface = parse(r#""" "fog""#).unwrap();
+        assert!(interface.inside.is_none() && interface.outside.is_some());
+        assert_eq!(parse(r#""fog" "smoke""#).err().unwrap().message, "Unknown medium 'smoke'");
+        assert_eq!(parse(r#""fog" "float density" [2]"#).err().unwrap().message, "Expected 1 values");
+    }
+    #[test]
+    fn henyey_greenstein_sample_matches_p() {
+        let wo = Vector3f::new(0.3, -0.4, 0.5).normalize();
+        for &g in &[-0.7, 0., 0.5] {
+            let phase = HenyeyGreenstein::new(g);
+            for &u in &[Point2f::new(0.1, 0.9), Point2f::new(0.5, 0.25), Point2f::new(0.95, 0.6)] {
+                let (wi, pdf) = phase.sample_p(&wo, &u);
+                assert!((wi.magnitude() - 1.).abs() < 1e-4);
+                assert!((pdf - phase.p(&wo, &wi)).abs() < 1e-3 * pdf);
+            }
+        }
+    }
+}
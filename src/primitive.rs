@@ -6,12 +6,16 @@ use std::collections::VecDeque;
 pub struct Primitive {
     shape: Arc<dyn Shape>,
     source: PrimitiveSource,
+    medium_interface: MediumInterface,
+    id: usize,
 }
 
 #[derive(Debug, Clone)]
 pub enum PrimitiveSource {
     Material(Arc<dyn Material>),
     Light(Arc<dyn Light>),
+    // a surface without material, only separates two media
+    Interface,
 }
 
 impl PrimitiveSource {
@@ -42,21 +46,38 @@ impl Primitive {
         Self {
             shape,
             source,
+            medium_interface: MediumInterface::default(),
+            id: 0,
         }
     }
+    pub fn with_id(self, id: usize) -> Self {
+        Self { id, ..self }
+    }
+    pub fn with_medium_interface(self, medium_interface: MediumInterface) -> Self {
+        Self {
+            medium_interface,
+            ..self
+        }
+    }
+    pub fn id(&self) -> usize {
+        self.id
+    }
+    pub fn has_media(&self) -> bool {
+        self.medium_interface.inside.is_some() || self.medium_interface.outside.is_some()
+    }
 
     pub fn intersect_predicate(&self, ray: &Ray) -> bool {
         self.shape.intersect_predicate(ray)
     }
     pub fn intersect(&self, ray: &Ray) -> Option<PrimitiveIntersect> {
-        let shape_intersect = self.shape.intersect(ray)?;
+        let shape_intersect = self.shape.intersect(ray)?.with_time(ray.time);
         Some(PrimitiveIntersect::new(shape_intersect, self.clone()))
     }
     pub fn intersect_predicate_through_bound(&self, ray: &RayIntersectCache) -> bool {
         self.shape.intersect_predicate_through_bound(ray)
     }
     pub fn intersect_through_bound(&self, ray: &RayIntersectCache) -> Option<PrimitiveIntersect> {
-        let shape_intersect = self.shape.intersect_through_bound(ray)?;
+        let shape_intersect = self.shape.intersect_through_bound(ray)?.with_time(ray.origin_ray().time);
         Some(PrimitiveIntersect::new(shape_intersect, self.clone()))
     }
     pub fn bound(&self) -> Bounds3f {
@@ -92,6 +113,19 @@ impl PrimitiveIntersect {
                 .compute_scattering_functions(&self.shape_intersect),
         )
     }
+    pub fn compute_bssrdf(&self) -> Option<TabulatedBSSRDF> {
+        self.primitive
+            .source
+            .get_material()?
+            .compute_bssrdf(&self.shape_intersect)
+    }
+    // the triangles of a mesh are primitives of their own, they share the material
+    pub fn same_material(&self, rhs: &PrimitiveIntersect) -> bool {
+        match (self.primitive.source.get_material(), rhs.primitive.source.get_material()) {
+            (Some(lhs), Some(rhs)) => Arc::as_ptr(&lhs) as *const () == Arc::as_ptr(&rhs) as *const (),
+            _ => false,
+        }
+    }
     pub fn get_shape_intersect(&self) -> &ShapeIntersect {
         &self.shape_intersect
     }
@@ -104,27 +138,45 @@ impl PrimitiveIntersect {
     pub fn get_light(&self) -> Option<Arc<dyn Light>> {
         self.primitive.source.get_light()
     }
+    pub fn get_primitive(&self) -> &Primitive {
+        &self.primitive
+    }
+    pub fn is_medium_interface(&self) -> bool {
+        matches!(self.primitive.source, PrimitiveSource::Interface)
+    }
+    // the medium a ray leaving the intersection in direction w travels through,
+    // surfaces that are no medium transition keep the medium of the incoming ray
+    pub fn medium_after(&self, w: &Vector3f, medium: Option<Arc<dyn Medium>>) -> Option<Arc<dyn Medium>> {
+        let medium_interface = &self.primitive.medium_interface;
+        if medium_interface.is_transition() {
+            medium_interface.medium(w, &self.get_shape_point().n)
+        } else {
+            medium
+        }
+    }
 }
 
 impl Transformable for Primitive {
     fn apply(self, transform: &Transform) -> Self {
         Self::new(shape_apply(self.shape, transform), self.source)
+            .with_medium_interface(self.medium_interface)
+            .with_id(self.id)
     }
 }
 
-pub fn primitives_apply(primitives: Vec<Primitive>, transform: Transform) -> Vec<Primitive> {
+pub fn primitives_apply(primitives: Vec<Primitive>, transform: AnimatedTransform) -> Vec<Primitive> {
     let mut shapes = Vec::new();
     let mut sources = VecDeque::new();
     for primitive in primitives {
         shapes.push(primitive.shape);
-        sources.push_back(primitive.source);
+        sources.push_back((primitive.source, primitive.medium_interface));
     }
     let mut shapes: VecDeque<_> = shapes_apply(shapes, transform).into();
     let mut r = Vec::new();
     while !shapes.is_empty() {
         let shape = shapes.pop_front().unwrap();
-        let sources = sources.pop_front().unwrap();
-        r.push(Primitive::new(shape, sources));
+        let (source, medium_interface) = sources.pop_front().unwrap();
+        r.push(Primitive::new(shape, source).with_medium_interface(medium_interface));
     }
     r
 }
@@ -2,81 +2,893 @@ use crate::*;
 use indicatif::{ProgressBar, ProgressStyle};
 use rayon::prelude::*;
 use std::collections::VecDeque;
-use std::{path::Path, sync::Mutex};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
-pub fn render(
-    scene: Scene,
-    sampler: Box<dyn Sampler>,
-    integrator: Box<dyn Integrator>,
-    film: Film,
-    camera: Box<dyn Camera>,
-) -> Film {
-    let film_tiles = film.gen_tiles();
-    let len = film_tiles.len() as u64;
-    let progress_bar = ProgressBar::new(len);
-    progress_bar.set_style(ProgressStyle::default_bar().template("{wide_bar} {percent}% ({eta_precise})"));
-    progress_bar.enable_steady_tick(1000);
-    let film = Mutex::new(film);
-    film_tiles.into_par_iter().for_each(|mut tile| {
-        //film_tiles.into_iter().for_each(|mut tile| {
-        let mut sampler = sampler.box_clone();
-        for film_point in tile.bound().index_inside() {
-            trace!("Rendering point {}", film_point);
-            sampler.set_pixel(&film_point);
-            let mut film_point_f = Point2f::new(film_point.x as Float, film_point.y as Float);
-            let sample_per_pixel = sampler.get_sample_per_pixel();
-            let mut samples = Vec::new();
-            for _i in 0..sample_per_pixel {
-                let offset = sampler.get_2d() - Point2f::new(0.5, 0.5);
-                film_point_f += offset;
-                let ray = camera.generate_ray(film_point_f, sampler.as_mut());
-                let li = integrator.li(&ray, &scene, sampler.as_mut());
-                if li.has_nan() {
-                    warn!("li has nan at pixel {} sample {}", film_point, _i);
-                    warn!("");
+#[derive(Debug, Clone, Default)]
+pub struct RenderOptions {
+    pub output: Option<String>,
+    pub resolution: Option<Vector2u>,
+    pub pixel_samples: Option<usize>,
+    pub max_depth: Option<usize>,
+    pub crop_window: Option<Bounds2f>,
+    pub pixel_bounds: Option<Bounds2u>,
+    pub full_frame: bool,
+    pub progressive: Option<ProgressiveOptions>,
+    pub adaptive: Option<AdaptiveOptions>,
+    pub checkpoint: Option<CheckpointOptions>,
+    pub quiet: bool,
+}
+
+impl RenderOptions {
+    pub fn apply(&self, segments: &mut [BlockSegment]) {
+        for segment in segments {
+            if let Some(property_set) = segment.get_object_by_type_mut("Film") {
+                if let Some(output) = &self.output {
+                    property_set.set_value(
+                        "string",
+                        "filename",
+                        vec![BasicType::BasicString(output.clone(), PathBuf::new())].into(),
+                    );
+                }
+                if let Some(resolution) = self.resolution {
+                    property_set.set_value("integer", "xresolution", integer_values(&[resolution.x]));
+                    property_set.set_value("integer", "yresolution", integer_values(&[resolution.y]));
                 }
-                else {
-                    samples.push((offset, li));
+                if let Some(crop_window) = &self.crop_window {
+                    let floats = [
+                        crop_window.min.x,
+                        crop_window.max.x,
+                        crop_window.min.y,
+                        crop_window.max.y,
+                    ];
+                    property_set.set_value(
+                        "float",
+                        "cropwindow",
+                        floats.iter().map(|f| BasicType::BasicFloat(*f)).collect::<Vec<_>>().into(),
+                    );
+                    property_set.remove_value("pixelbounds");
+                }
+                if let Some(pixel_bounds) = &self.pixel_bounds {
+                    let integers = [
+                        pixel_bounds.min.x,
+                        pixel_bounds.max.x,
+                        pixel_bounds.min.y,
+                        pixel_bounds.max.y,
+                    ];
+                    property_set.set_value("integer", "pixelbounds", integer_values(&integers));
+                    property_set.remove_value("cropwindow");
+                }
+                if self.full_frame {
+                    property_set.set_value(
+                        "bool",
+                        "writefullframe",
+                        vec![BasicType::BasicString(String::from("true"), PathBuf::new())].into(),
+                    );
+                }
+            } else if let Some(property_set) = segment.get_object_by_type_mut("Sampler") {
+                if let Some(pixel_samples) = self.pixel_samples {
+                    property_set.set_value("integer", "pixelsamples", integer_values(&[pixel_samples]));
+                }
+            } else if let Some(property_set) = segment.get_object_by_type_mut("Integrator") {
+                if let Some(max_depth) = self.max_depth {
+                    property_set.set_value("integer", "maxdepth", integer_values(&[max_depth]));
+                }
+            }
+        }
+    }
+}
+
+fn integer_values(us: &[usize]) -> BasicTypes {
+    us.iter()
+        .map(|u| BasicType::BasicInteger(*u as Integer))
+        .collect::<Vec<_>>()
+        .into()
+}
+
+#[derive(Debug, Clone)]
+pub struct ProgressiveOptions {
+    pub pass_samples: usize,
+    pub time_limit: Option<Duration>,
+    pub noise_threshold: Option<Float>,
+}
+
+pub fn new_progress_bar(len: u64, quiet: bool, prefix: &str) -> ProgressBar {
+    let progress_bar = if quiet {
+        ProgressBar::hidden()
+    } else {
+        ProgressBar::new(len)
+    };
+    progress_bar.set_style(
+        ProgressStyle::default_bar().template("{prefix} {wide_bar} {percent}% ({eta_precise})"),
+    );
+    progress_bar.set_prefix(prefix);
+    progress_bar.enable_steady_tick(1000);
+    progress_bar
+}
+
+#[derive(Debug, Clone)]
+pub struct AdaptiveOptions {
+    pub error_threshold: Float,
+    pub max_samples: Option<usize>,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn render_tile(
+    scene: &Scene,
+    sampler: &dyn Sampler,
+    integrator: &dyn Integrator,
+    camera: &dyn Camera,
+    sample_range: &(dyn Fn(&Point2u) -> Range<usize> + Sync),
+    has_aovs: bool,
+    tile: &mut FilmTile,
+    splats: &mut Vec<(Point2f, Spectrum)>,
+) -> usize {
+    let mut sampler = sampler.box_clone();
+    let mut splat_samples = 0;
+    for film_point in tile.sample_bound().index_inside() {
+        trace!("Rendering point {}", film_point);
+        let sample_range = sample_range(&film_point);
+        if sample_range.is_empty() {
+            continue;
+        }
+        sampler.set_pixel(&film_point);
+        sampler.set_sample_index(sample_range.start);
+        splat_samples += sample_range.len();
+        let pixel_center = Point2f::new(film_point.x as Float + 0.5, film_point.y as Float + 0.5);
+        let mut samples = Vec::new();
+        let mut aov_samples = Vec::new();
+        for _i in sample_range {
+            sampler.start_slot(DimensionSlot::Film);
+            let offset = sampler.get_2d() - Point2f::new(0.5, 0.5);
+            let (ray, weight) = camera.generate_ray_with_weight(pixel_center + offset, sampler.as_mut());
+            if weight == 0. {
+                samples.push((offset, Spectrum::new(0.)));
+                if has_aovs {
+                    aov_samples.push((offset, AovSample::default()));
                 }
                 sampler.next_sample();
+                continue;
             }
-            tile.add_samples(&film_point, &samples);
+            if has_aovs {
+                aov_samples.push((offset, integrator.first_hit(&ray, scene)));
+            }
+            let li = integrator.li_with_splats(&ray, scene, sampler.as_mut(), camera, splats) * weight;
+            if li.has_nan() {
+                warn!("li has nan at pixel {} sample {}", film_point, _i);
+                warn!("");
+            }
+            else {
+                samples.push((offset, li));
+            }
+            sampler.next_sample();
+        }
+        tile.add_samples(&film_point, &samples);
+        tile.add_aov_samples(&film_point, &aov_samples);
+    }
+    splat_samples
+}
+
+type OnTilesMerged<'a> = dyn FnMut(&Film, usize) + 'a;
+
+// without on_tiles_merged the tiles are merged as they finish, with it they are rendered in chunks
+// and merged in a fixed order after each, so a checkpoint taken there can be resumed exactly
+#[allow(clippy::too_many_arguments)]
+fn render_pass(
+    scene: &Scene,
+    sampler: &dyn Sampler,
+    integrator: &dyn Integrator,
+    film: Film,
+    camera: &dyn Camera,
+    sample_range: &(dyn Fn(&Point2u) -> Range<usize> + Sync),
+    skip_tiles: usize,
+    on_tiles_merged: Option<&mut OnTilesMerged>,
+    progress_bar: &ProgressBar,
+) -> Film {
+    let film_tiles = film.gen_tiles();
+    let tile_count = film_tiles.len();
+    progress_bar.set_length(tile_count as u64);
+    progress_bar.set_position(min(skip_tiles, tile_count) as u64);
+    let has_aovs = film.has_aovs();
+    let on_tiles_merged = match on_tiles_merged {
+        Some(on_tiles_merged) => on_tiles_merged,
+        None => {
+            let film = Mutex::new(film);
+            film_tiles.into_par_iter().skip(skip_tiles).for_each(|mut tile| {
+                let mut splats = Vec::new();
+                let splat_samples =
+                    render_tile(scene, sampler, integrator, camera, sample_range, has_aovs, &mut tile, &mut splats);
+                let mut film = film.lock().unwrap();
+                film.merge_tile(tile);
+                film.add_splats(&splats, splat_samples);
+                progress_bar.inc(1);
+            });
+            return film.into_inner().unwrap();
+        }
+    };
+    let mut film = film;
+    let chunk_size = rayon::current_num_threads() * 8;
+    let mut completed_tiles = skip_tiles;
+    let mut film_tiles: Vec<_> = film_tiles.into_iter().skip(skip_tiles).collect();
+    while !film_tiles.is_empty() {
+        let rest = film_tiles.split_off(min(chunk_size, film_tiles.len()));
+        let rendered_tiles: Vec<_> = film_tiles
+            .into_par_iter()
+            .map(|mut tile| {
+                let mut splats = Vec::new();
+                let splat_samples =
+                    render_tile(scene, sampler, integrator, camera, sample_range, has_aovs, &mut tile, &mut splats);
+                progress_bar.inc(1);
+                (tile, splats, splat_samples)
+            })
+            .collect();
+        completed_tiles += rendered_tiles.len();
+        for (tile, splats, splat_samples) in rendered_tiles {
+            film.merge_tile(tile);
+            film.add_splats(&splats, splat_samples);
+        }
+        on_tiles_merged(&film, completed_tiles);
+        film_tiles = rest;
+    }
+    film
+}
+
+pub fn render(
+    scene: &Scene,
+    sampler: &dyn Sampler,
+    integrator: &dyn Integrator,
+    film: Film,
+    camera: &dyn Camera,
+    options: &RenderOptions,
+    resume: &RenderProgress,
+) -> Film {
+    let progress_bar = new_progress_bar(0, options.quiet, "");
+    let sample_count = sampler.get_sample_per_pixel();
+    let mut checkpointer = options.checkpoint.clone().map(|checkpoint| Checkpointer::new(checkpoint, sampler));
+    let mut on_tiles_merged = checkpointer.as_mut().map(|checkpointer| {
+        move |film: &Film, completed_tiles| {
+            let progress = RenderProgress {
+                sample_index: 0,
+                sample_count,
+                completed_tiles,
+            };
+            checkpointer.update(film, &progress);
         }
-        film.lock().unwrap().merge_tile(tile);
-        progress_bar.inc(1);
     });
+    let film = render_pass(
+        scene,
+        sampler,
+        integrator,
+        film,
+        camera,
+        &|_| 0..sample_count,
+        resume.completed_tiles,
+        on_tiles_merged.as_mut().map(|f| f as &mut OnTilesMerged),
+        &progress_bar,
+    );
     progress_bar.finish_and_clear();
-    film.into_inner().unwrap()
+    film
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn render_progressive<F: FnMut(&Film, usize)>(
+    scene: &Scene,
+    sampler: &dyn Sampler,
+    integrator: &dyn Integrator,
+    mut film: Film,
+    camera: &dyn Camera,
+    options: &RenderOptions,
+    progressive: &ProgressiveOptions,
+    resume: &RenderProgress,
+    mut on_pass: F,
+) -> Film {
+    let start = Instant::now();
+    let total_samples = sampler.get_sample_per_pixel();
+    let pass_samples = max(progressive.pass_samples, 1);
+    let mut checkpointer = options.checkpoint.clone().map(|checkpoint| Checkpointer::new(checkpoint, sampler));
+    let mut sample_index = resume.sample_index;
+    let mut skip_tiles = resume.completed_tiles;
+    let mut last_pass_time = Duration::default();
+    while sample_index < total_samples {
+        if let Some(time_limit) = progressive.time_limit {
+            if sample_index > 0 && start.elapsed() + last_pass_time > time_limit {
+                info!("Time limit reached after {} samples per pixel", sample_index);
+                break;
+            }
+        }
+        let pass_start = Instant::now();
+        let sample_count = if skip_tiles > 0 {
+            resume.sample_count
+        } else {
+            min(pass_samples, total_samples - sample_index)
+        };
+        let progress_bar = new_progress_bar(
+            0,
+            options.quiet,
+            &format!("{}/{} spp", sample_index + sample_count, total_samples),
+        );
+        let mut on_tiles_merged = checkpointer.as_mut().map(|checkpointer| {
+            move |film: &Film, completed_tiles| {
+                let progress = RenderProgress {
+                    sample_index,
+                    sample_count,
+                    completed_tiles,
+                };
+                checkpointer.update(film, &progress);
+            }
+        });
+        film = render_pass(
+            scene,
+            sampler,
+            integrator,
+            film,
+            camera,
+            &|_| sample_index..sample_index + sample_count,
+            skip_tiles,
+            on_tiles_merged.as_mut().map(|f| f as &mut OnTilesMerged),
+            &progress_bar,
+        );
+        progress_bar.finish_and_clear();
+        skip_tiles = 0;
+        sample_index += sample_count;
+        last_pass_time = pass_start.elapsed();
+        on_pass(&film, sample_index);
+        if let Some(noise_threshold) = progressive.noise_threshold {
+            let noise_level = film.noise_level();
+            info!("Noise level {} after {} samples per pixel", noise_level, sample_index);
+            if noise_level <= noise_threshold {
+                break;
+            }
+        }
+    }
+    film
+}
+
+pub fn render_adaptive(
+    scene: &Scene,
+    sampler: &dyn Sampler,
+    integrator: &dyn Integrator,
+    film: Film,
+    camera: &dyn Camera,
+    options: &RenderOptions,
+    adaptive: &AdaptiveOptions,
+) -> Film {
+    let pass_samples = sampler.get_sample_per_pixel();
+    let max_samples = adaptive.max_samples.unwrap_or(pass_samples * 8);
+    let progress_bar = new_progress_bar(0, options.quiet, &format!("{} spp", pass_samples));
+    let mut film = render_pass(
+        scene,
+        sampler,
+        integrator,
+        film,
+        camera,
+        &|_| 0..pass_samples,
+        0,
+        None,
+        &progress_bar,
+    );
+    progress_bar.finish_and_clear();
+    let resolution = film.bound().diagonal();
+    let mut next_samples = FixedVec2D::new(pass_samples, resolution);
+    loop {
+        let mut sample_ranges = FixedVec2D::new(0..0, resolution);
+        let mut pixel_count = 0;
+        for point in film.crop_bound().index_inside() {
+            let next_sample = next_samples[point];
+            if next_sample < max_samples
+                && film.get_pixel(&point).relative_error() > adaptive.error_threshold
+            {
+                let sample_count = min(pass_samples, max_samples - next_sample);
+                sample_ranges[point] = next_sample..next_sample + sample_count;
+                next_samples[point] += sample_count;
+                pixel_count += 1;
+            }
+        }
+        if pixel_count == 0 {
+            break;
+        }
+        info!("Adaptive pass over {} pixels", pixel_count);
+        let progress_bar = new_progress_bar(0, options.quiet, &format!("{} px", pixel_count));
+        film = render_pass(
+            scene,
+            sampler,
+            integrator,
+            film,
+            camera,
+            &|point| sample_ranges[*point].clone(),
+            0,
+            None,
+            &progress_bar,
+        );
+        progress_bar.finish_and_clear();
+    }
+    film
 }
 
 fn parse_find_eat<'a, R: ParseFromBlockSegment<'a>>(
     segments: &'a mut VecDeque<BlockSegment>,
-) -> Option<R::T> {
+) -> SceneResult<Option<R::T>> {
+    for segment in segments {
+        if let Some(r) = R::parse_from_segment(segment)? {
+            return Ok(Some(r));
+        }
+    }
+    Ok(None)
+}
+
+// the transform in effect at the Camera directive is the world to camera transform
+fn parse_camera_transform(segments: &VecDeque<BlockSegment>) -> SceneResult<TransformState> {
+    let mut transform = TransformState::default();
+    for segment in segments {
+        if segment.get_object_by_type("Camera").is_some() {
+            break;
+        }
+        transform.parse(segment)?;
+    }
+    Ok(transform)
+}
+
+// media made outside the world block, the camera is in the outside medium of the interface at the Camera directive,
+// their grids are placed in world space as is
+fn parse_media(segments: &VecDeque<BlockSegment>) -> SceneResult<(NamedMedia, Option<Arc<dyn Medium>>)> {
+    let mut named_media = NamedMedia::default();
+    let mut camera_medium = None;
+    let mut before_camera = true;
     for segment in segments {
-        if let Some(r) = R::parse_from_segment(segment) {
-            return Some(r);
+        let (object_type, property_set) = match segment.get_object() {
+            Some(object) => object,
+            None => continue,
+        };
+        match object_type {
+            "Camera" => before_camera = false,
+            "MakeNamedMedium" => {
+                let (name, medium) = parse_make_named_medium(property_set, &Transform::default())?;
+                named_media.insert(String::from(name), medium.into());
+            }
+            "MediumInterface" if before_camera => {
+                camera_medium = parse_medium_interface(property_set, &named_media)?.outside;
+            }
+            _ => (),
         }
     }
-    None
+    Ok((named_media, camera_medium))
+}
+
+fn parse_find_eat_required<'a, R: ParseFromBlockSegment<'a>>(
+    segments: &'a mut VecDeque<BlockSegment>,
+    path: &Path,
+    directive: &str,
+) -> SceneResult<R::T> {
+    parse_find_eat::<R>(segments)?
+        .ok_or_else(|| SceneError::in_file(path, format!("Missing {} directive", directive)))
 }
 
-pub fn render_from_file(path: &Path) {
-    let mut segments = read_scene(path).into_iter().collect::<VecDeque<_>>();
-    let camera_transform = parse_find_eat::<Transform>(&mut segments);
-    let camera_factory = parse_find_eat::<Box<dyn Camera>>(&mut segments).unwrap();
-    let sampler_factory = parse_find_eat::<Box<dyn Sampler>>(&mut segments).unwrap();
-    let (film, file_name, resolution) = parse_find_eat::<Film>(&mut segments).unwrap();
-    let mut camera = camera_factory(resolution);
-    if let Some(transform) = camera_transform {
-        camera = camera_apply(camera, &transform);
+pub fn render_from_file(path: &Path) -> SceneResult<()> {
+    render_from_file_with_options(path, &RenderOptions::default())
+}
+
+// everything a scene file sets up for rendering, with a camera for each view
+pub struct LoadedScene {
+    pub scene: Scene,
+    pub sampler: Box<dyn Sampler>,
+    pub integrator: Box<dyn Integrator>,
+    pub film: Film,
+    pub file_name: String,
+    pub views: Vec<(String, Transform, Float)>,
+    pub cameras: Vec<Box<dyn Camera>>,
+}
+
+pub fn load_scene(path: &Path, options: &RenderOptions) -> SceneResult<LoadedScene> {
+    let mut segments = read_scene(path)?;
+    options.apply(&mut segments);
+    let mut segments = segments.into_iter().collect::<VecDeque<_>>();
+    let camera_transform = parse_camera_transform(&segments)?;
+    let (named_media, camera_medium) = parse_media(&segments)?;
+    let transform_times = parse_find_eat::<TransformTimes>(&mut segments)?.unwrap_or_default();
+    let camera_rig = parse_find_eat::<CameraRig>(&mut segments)?.unwrap_or_default();
+    let camera_factory = parse_find_eat_required::<Box<dyn Camera>>(&mut segments, path, "Camera")?;
+    let sampler_factory = parse_find_eat_required::<Box<dyn Sampler>>(&mut segments, path, "Sampler")?;
+    let (mut film, file_name, resolution) = parse_find_eat_required::<Film>(&mut segments, path, "Film")?;
+    if let Some(filter) = parse_find_eat::<Arc<dyn Filter>>(&mut segments)? {
+        film.set_filter(filter);
+    }
+    if camera_rig.views() > 1 && options.checkpoint.is_some() {
+        return Err(SceneError::in_file(
+            path,
+            String::from("Checkpoints are not supported with multiple views"),
+        ));
+    }
+    if options.progressive.is_some() && options.adaptive.is_some() {
+        return Err(SceneError::in_file(
+            path,
+            String::from("Progressive rendering cant be combined with adaptive sampling"),
+        ));
+    }
+    if options.adaptive.is_some() && options.checkpoint.is_some() {
+        return Err(SceneError::in_file(
+            path,
+            String::from("Checkpoints are not supported with adaptive sampling"),
+        ));
+    }
+    let views = camera_rig.view_transforms();
+    let mut cameras = Vec::new();
+    for (_, view_transform, view_shift) in &views {
+        let mut camera = camera_factory(&film, *view_shift)?;
+        if views.len() > 1 {
+            camera = camera_apply(camera, view_transform);
+        }
+        if let Some(transform) = camera_transform.animated(&transform_times) {
+            camera = camera_apply_animated(camera, &transform);
+        }
+        cameras.push(camera);
+    }
+    let mut sampler = sampler_factory(resolution);
+    let integrator =
+        parse_find_eat_required::<Box<dyn Integrator>>(&mut segments, path, "Integrator")?;
+    if integrator.renders_whole_film() {
+        let unsupported = [
+            (options.progressive.is_some(), "Progressive rendering"),
+            (options.adaptive.is_some(), "Adaptive sampling"),
+            (options.checkpoint.is_some(), "Checkpointing"),
+            (film.has_aovs(), "AOV output"),
+        ];
+        if let Some((_, feature)) = unsupported.iter().find(|(enabled, _)| *enabled) {
+            return Err(SceneError::in_file(
+                path,
+                format!("{} is not supported by integrators that render the whole film", feature),
+            ));
+        }
     }
-    let sampler = sampler_factory(resolution);
-    let integrator = parse_find_eat::<Box<dyn Integrator>>(&mut segments).unwrap();
     let aggregate = Box::new(BVHAggregate::default());
-    let scene_builder = parse_find_eat::<SceneBuilder>(&mut segments).unwrap();
-    let mut scene = scene_builder.build_with_clipper(Some(camera.as_clipper()));
+    let scene_builder = parse_find_eat_required::<SceneBuilder>(&mut segments, path, "World")?
+        .with_transform_times(transform_times)
+        .with_media(named_media, camera_medium);
+    // one scene is built for all the views
+    let mut scene = scene_builder.build_with_clipper(Some(&cameras))?;
+    if scene.has_media() && !integrator.supports_media() {
+        return Err(SceneError::in_file(
+            path,
+            String::from("Integrator ignores participating media, use 'volpath' for scenes with media"),
+        ));
+    }
     scene.build_aggregate(aggregate);
-    let film = render(scene, sampler, integrator, film, camera);
-    film.write_image(&Path::new(file_name.as_str()));
-    println!("{}", file_name);
+    integrator.preprocess(&scene, sampler.as_mut());
+    Ok(LoadedScene {
+        scene,
+        sampler,
+        integrator,
+        film,
+        file_name,
+        views,
+        cameras,
+    })
+}
+
+pub fn render_from_file_with_options(path: &Path, options: &RenderOptions) -> SceneResult<()> {
+    let LoadedScene {
+        scene,
+        sampler,
+        integrator,
+        mut film,
+        file_name,
+        views,
+        cameras,
+    } = load_scene(path, options)?;
+    let mut resume = RenderProgress::default();
+    if let Some(checkpoint) = &options.checkpoint {
+        if checkpoint.resume && checkpoint.path.exists() {
+            resume = read_checkpoint(&checkpoint.path, &mut film, &SamplerSignature::new(sampler.as_ref())).map_err(|e| {
+                SceneError::in_file(
+                    path,
+                    format!("Cant resume from checkpoint '{}': {}", checkpoint.path.display(), e),
+                )
+            })?;
+            info!(
+                "Resuming from {} at sample {} tile {}",
+                checkpoint.path.display(),
+                resume.sample_index,
+                resume.completed_tiles
+            );
+        }
+    }
+    for ((suffix, _, _), camera) in views.iter().zip(cameras.iter()) {
+        let file_name = view_file_name(&file_name, suffix);
+        let film = if let Some(film) =
+            integrator.render(&scene, sampler.as_ref(), &film, camera.as_ref(), options.quiet)
+        {
+            film
+        } else {
+            let film = film.clone();
+            match &options.progressive {
+                Some(progressive) => {
+                    let on_pass = |film: &Film, sample_index| {
+                        if let Err(e) = film.write_image(Path::new(file_name.as_str())) {
+                            warn!("Cant write intermediate image '{}': {}", file_name, e);
+                        } else {
+                            info!("Wrote {} after {} samples per pixel", file_name, sample_index);
+                        }
+                    };
+                    render_progressive(
+                        &scene,
+                        sampler.as_ref(),
+                        integrator.as_ref(),
+                        film,
+                        camera.as_ref(),
+                        options,
+                        progressive,
+                        &resume,
+                        on_pass,
+                    )
+                }
+                None => match &options.adaptive {
+                    Some(adaptive) => render_adaptive(
+                        &scene,
+                        sampler.as_ref(),
+                        integrator.as_ref(),
+                        film,
+                        camera.as_ref(),
+                        options,
+                        adaptive,
+                    ),
+                    None => render(
+                        &scene,
+                        sampler.as_ref(),
+                        integrator.as_ref(),
+                        film,
+                        camera.as_ref(),
+                        options,
+                        &resume,
+                    ),
+                },
+            }
+        };
+        film.write_image(Path::new(file_name.as_str())).map_err(|e| {
+            SceneError::in_file(path, format!("Cant write image '{}': {}", file_name, e))
+        })?;
+        if !options.quiet {
+            println!("{}", file_name);
+        }
+    }
+    if let Some(checkpoint) = &options.checkpoint {
+        if checkpoint.path.exists() {
+            if let Err(e) = std::fs::remove_file(&checkpoint.path) {
+                warn!("Cant remove checkpoint {}: {}", checkpoint.path.display(), e);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_scene::*;
+    fn assert_close(lhs: &[Spectrum], rhs: &[Spectrum]) {
+        for (l, r) in lhs.iter().zip(rhs) {
+            for c in 0..3 {
+                assert!((l[c] - r[c]).abs() <= 1e-4 * max(1., r[c].abs()), "{} != {}", l[c], r[c]);
+            }
+        }
+    }
+    #[test]
+    fn progressive_passes_add_up_to_one_render() {
+        let scene = box_scene(r#"Sampler "random" "integer pixelsamples" [8]"#, r#"Integrator "path""#);
+        let full = render_scene("full", &scene, &RenderOptions::default()).unwrap();
+        let progressive = ProgressiveOptions {
+            pass_samples: 3,
+            time_limit: None,
+            noise_threshold: None,
+        };
+        let options = RenderOptions {
+            progressive: Some(progressive.clone()),
+            ..RenderOptions::default()
+        };
+        assert_close(&render_scene("progressive", &scene, &options).unwrap(), &full);
+        // a noise threshold every pass meets stops after the first one
+        let options = RenderOptions {
+            progressive: Some(ProgressiveOptions {
+                noise_threshold: Some(Float::INFINITY),
+                ..progressive
+            }),
+            ..RenderOptions::default()
+        };
+        let first_pass = render_scene("first-pass", &scene, &options).unwrap();
+        assert!(first_pass.iter().zip(&full).any(|(l, r)| (l[0] - r[0]).abs() > 1e-3));
+    }
+    #[test]
+    fn resumed_render_matches_uninterrupted() {
+        // a single thread renders chunks of 8 tiles, so the checkpoint is taken after 8 of the 12 tiles
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        pool.install(|| {
+            let scene = box_scene(r#"Sampler "random" "integer pixelsamples" [2]"#, r#"Integrator "path""#);
+            let options = RenderOptions {
+                resolution: Some(Vector2u::new(64, 48)),
+                ..RenderOptions::default()
+            };
+            let loaded = load_test_scene("resume", &scene, &options).unwrap();
+            let (scene, sampler, integrator, camera) =
+                (&loaded.scene, loaded.sampler.as_ref(), loaded.integrator.as_ref(), loaded.cameras[0].as_ref());
+            let sample_count = sampler.get_sample_per_pixel();
+            let signature = SamplerSignature::new(sampler);
+            let checkpoint = std::env::temp_dir().join(format!("curry-pbrt-resume-{}.ck", std::process::id()));
+            let progress_bar = ProgressBar::hidden();
+            let mut on_tiles_merged = |film: &Film, completed_tiles| {
+                if completed_tiles == 8 {
+                    let progress = RenderProgress {
+                        sample_index: 0,
+                        sample_count,
+                        completed_tiles,
+                    };
+                    write_checkpoint(&checkpoint, film, &signature, &progress).unwrap();
+                }
+            };
+            let samples = |_: &Point2u| 0..sample_count;
+            let film = loaded.film.clone();
+            let uninterrupted =
+                render_pass(scene, sampler, integrator, film, camera, &samples, 0, Some(&mut on_tiles_merged), &progress_bar);
+            let mut film = loaded.film.clone();
+            let progress = read_checkpoint(&checkpoint, &mut film, &signature).unwrap();
+            assert_eq!(progress.completed_tiles, 8);
+            let mut on_tiles_merged = |_: &Film, _| {};
+            let resumed =
+                render_pass(scene, sampler, integrator, film, camera, &samples, 8, Some(&mut on_tiles_merged), &progress_bar);
+            let film = loaded.film.clone();
+            let unchunked = render_pass(scene, sampler, integrator, film, camera, &samples, 0, None, &progress_bar);
+            for point in uninterrupted.crop_bound().index_inside() {
+                let (lhs, rhs) = (resumed.get_pixel(&point), uninterrupted.get_pixel(&point));
+                assert_eq!(lhs.weight, rhs.weight);
+                for c in 0..3 {
+                    assert_eq!(lhs.contribution[c], rhs.contribution[c]);
+                }
+                assert_close(&[unchunked.get_pixel(&point).value()], &[rhs.value()]);
+            }
+            // a checkpoint only resumes with the sampler it was rendered with
+            let other = SamplerSignature {
+                name: String::from("halton"),
+                sample_per_pixel: 2,
+            };
+            assert!(read_checkpoint(&checkpoint, &mut loaded.film.clone(), &other).is_err());
+            std::fs::remove_file(&checkpoint).unwrap();
+        });
+    }
+    #[test]
+    fn samplers_need_samples() {
+        for sampler in &["halton", "random", "sobol", "02sequence", "zsobol", "stratified"] {
+            for pixel_samples in &[0, -2] {
+                let line = format!("Sampler \"{}\" \"integer pixelsamples\" [{}]", sampler, pixel_samples);
+                let scene = box_scene(&line, r#"Integrator "path""#);
+                assert!(load_test_scene("no-samples", &scene, &RenderOptions::default()).is_err(), "{}", line);
+            }
+            // the command line overrides the scene file
+            let scene = box_scene(&format!("Sampler \"{}\" \"integer pixelsamples\" [4]", sampler), r#"Integrator "path""#);
+            let options = RenderOptions {
+                pixel_samples: Some(0),
+                ..RenderOptions::default()
+            };
+            let error = load_test_scene("no-samples", &scene, &options).err().unwrap();
+            assert!(error.message.contains("sample"), "{}", error.message);
+        }
+    }
+    #[test]
+    fn max_depth_stays_within_sampler_dimensions() {
+        let scene = box_scene(r#"Sampler "random""#, r#"Integrator "path""#);
+        let depth = |max_depth| RenderOptions {
+            max_depth: Some(max_depth),
+            ..RenderOptions::default()
+        };
+        assert!(load_test_scene("max-depth", &scene, &depth(MAX_DEPTH)).is_ok());
+        let error = load_test_scene("too-deep", &scene, &depth(MAX_DEPTH + 1)).err().unwrap();
+        assert!(error.message.contains("maxdepth"), "{}", error.message);
+    }
+    #[test]
+    fn surface_integrators_reject_media() {
+        let fog = "MakeNamedMedium \"fog\" \"string type\" \"homogeneous\"\nMediumInterface \"\" \"fog\"\n";
+        for integrator in &["path", "bdpt", "sppm", "mlt"] {
+            let scene = box_scene(r#"Sampler "random""#, &format!("Integrator \"{}\"", integrator));
+            let error = load_test_scene("media", &format!("{}{}", fog, scene), &RenderOptions::default()).err().unwrap();
+            assert!(error.message.contains("participating media"), "{}", error.message);
+            assert!(load_test_scene("no-media", &scene, &RenderOptions::default()).is_ok());
+        }
+        let scene = box_scene(r#"Sampler "random""#, "Integrator \"volpath\"");
+        assert!(load_test_scene("volpath", &format!("{}{}", fog, scene), &RenderOptions::default()).is_ok());
+    }
+    #[test]
+    fn whole_film_integrators_reject_per_sample_options() {
+        let progressive = RenderOptions {
+            progressive: Some(ProgressiveOptions {
+                pass_samples: 1,
+                time_limit: Some(Duration::from_secs(1)),
+                noise_threshold: None,
+            }),
+            ..RenderOptions::default()
+        };
+        let adaptive = RenderOptions {
+            adaptive: Some(AdaptiveOptions {
+                error_threshold: 0.1,
+                max_samples: None,
+            }),
+            ..RenderOptions::default()
+        };
+        let checkpoint = RenderOptions {
+            checkpoint: Some(CheckpointOptions {
+                path: PathBuf::from("whole-film.ckpt"),
+                interval: Duration::from_secs(1),
+                resume: true,
+            }),
+            ..RenderOptions::default()
+        };
+        for integrator in &["sppm", "mlt"] {
+            let scene = box_scene(r#"Sampler "random""#, &format!("Integrator \"{}\"", integrator));
+            for options in &[&progressive, &adaptive, &checkpoint] {
+                let error = load_test_scene("whole-film", &scene, options).err().unwrap();
+                assert!(error.message.contains("render the whole film"), "{}", error.message);
+            }
+            let aovs = scene.replace("Film \"image\"", "Film \"image\" \"string filename\" \"aov.exr\" \"string aovs\" [\"depth\"]");
+            let error = load_test_scene("whole-film-aovs", &aovs, &RenderOptions::default()).err().unwrap();
+            assert!(error.message.contains("AOV output"), "{}", error.message);
+        }
+        let scene = box_scene(r#"Sampler "random""#, r#"Integrator "path""#);
+        for options in &[&progressive, &adaptive, &checkpoint] {
+            assert!(load_test_scene("per-sample", &scene, options).is_ok());
+        }
+    }
+    #[test]
+    fn progressive_rendering_rejects_adaptive_sampling() {
+        let scene = box_scene(r#"Sampler "random""#, r#"Integrator "path""#);
+        let options = RenderOptions {
+            progressive: Some(ProgressiveOptions {
+                pass_samples: 1,
+                time_limit: None,
+                noise_threshold: None,
+            }),
+            adaptive: Some(AdaptiveOptions {
+                error_threshold: 0.1,
+                max_samples: None,
+            }),
+            ..RenderOptions::default()
+        };
+        let error = load_test_scene("progressive-adaptive", &scene, &options).err().unwrap();
+        assert!(error.message.contains("adaptive sampling"), "{}", error.message);
+    }
+    #[test]
+    fn adaptive_sampling_rejects_checkpoints() {
+        let scene = box_scene(r#"Sampler "random""#, r#"Integrator "path""#);
+        let options = RenderOptions {
+            adaptive: Some(AdaptiveOptions {
+                error_threshold: 0.1,
+                max_samples: None,
+            }),
+            checkpoint: Some(CheckpointOptions {
+                path: PathBuf::from("adaptive.ckpt"),
+                interval: Duration::from_secs(1),
+                resume: true,
+            }),
+            ..RenderOptions::default()
+        };
+        let error = load_test_scene("adaptive-checkpoint", &scene, &options).err().unwrap();
+        assert!(error.message.contains("adaptive sampling"), "{}", error.message);
+    }
+    #[test]
+    fn adaptive_passes_refine_noisy_pixels() {
+        let sampler = |spp: usize| format!(r#"Sampler "random" "integer pixelsamples" [{}]"#, spp);
+        let scene = box_scene(&sampler(4), r#"Integrator "path""#);
+        let adaptive = |error_threshold| RenderOptions {
+            adaptive: Some(AdaptiveOptions {
+                error_threshold,
+                max_samples: Some(12),
+            }),
+            ..RenderOptions::default()
+        };
+        // every pixel misses a negative threshold, so all of them get the extra samples
+        let refined = render_scene("adaptive", &scene, &adaptive(-1.)).unwrap();
+        let full = render_scene("adaptive-full", &box_scene(&sampler(12), r#"Integrator "path""#), &RenderOptions::default());
+        assert_close(&refined, &full.unwrap());
+        let unrefined = render_scene("adaptive-none", &scene, &adaptive(Float::INFINITY)).unwrap();
+        assert_close(&unrefined, &render_scene("adaptive-first", &scene, &RenderOptions::default()).unwrap());
+    }
 }
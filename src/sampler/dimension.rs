@@ -0,0 +1,194 @@
+pub const CAMERA_DIMENSIONS: usize = 5;
+pub const BOUNCE_DIMENSIONS: usize = 9;
+// bidirectional integrators trace their light subpaths and connect them to the camera subpaths here
+pub const LIGHT_PATH_DIMENSION_START: usize = 1024;
+pub const LIGHT_PATH_DIMENSIONS: usize = 9;
+// media have their own per bounce slots, so scenes without media keep their samples
+pub const MEDIUM_DIMENSION_START: usize = 2048;
+pub const MEDIUM_DIMENSIONS: usize = 5;
+// the exit points of subsurface scattering sample their own light and bsdf
+pub const SUBSURFACE_DIMENSION_START: usize = 3072;
+pub const SUBSURFACE_DIMENSIONS: usize = 11;
+// arrays live far away from the per bounce slots
+pub const ARRAY_DIMENSION_START: usize = 4096;
+
+const fn bounce_slots(start: usize, end: usize, dimensions: usize) -> usize {
+    (end - start) / dimensions
+}
+const fn min_slots(a: usize, b: usize) -> usize {
+    if a < b {
+        a
+    } else {
+        b
+    }
+}
+// the bounces every range has room for, the path strategy sits right below the light subpaths
+pub const BOUNCE_SLOTS: usize = min_slots(
+    min_slots(
+        bounce_slots(CAMERA_DIMENSIONS, LIGHT_PATH_DIMENSION_START - 1, BOUNCE_DIMENSIONS),
+        bounce_slots(LIGHT_PATH_DIMENSION_START + 5, MEDIUM_DIMENSION_START, LIGHT_PATH_DIMENSIONS),
+    ),
+    min_slots(
+        bounce_slots(MEDIUM_DIMENSION_START, SUBSURFACE_DIMENSION_START, MEDIUM_DIMENSIONS),
+        bounce_slots(SUBSURFACE_DIMENSION_START, ARRAY_DIMENSION_START, SUBSURFACE_DIMENSIONS),
+    ),
+);
+// bidirectional integrators connect at up to two vertices past the deepest bounce
+pub const MAX_DEPTH: usize = BOUNCE_SLOTS - 3;
+
+// fixed dimensions for every use of the sampler, so a change in one code path
+// (say a light that needs no samples) does not shift the samples of the others
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DimensionSlot {
+    Film,
+    Lens,
+    Time,
+    LightChoice(usize),
+    Light(usize),
+    LightBsdf(usize),
+    Bsdf(usize),
+    RussianRoulette(usize),
+    Medium(usize),
+    Phase(usize),
+    Transmittance(usize),
+    Subsurface(usize),
+    SubsurfaceLightChoice(usize),
+    SubsurfaceLight(usize),
+    SubsurfaceLightBsdf(usize),
+    SubsurfaceBsdf(usize),
+    LightPathChoice,
+    LightPathOrigin,
+    LightPathDirection,
+    LightPathBsdf(usize),
+    ConnectLight(usize),
+    ConnectCamera(usize),
+    LightPathRussianRoulette(usize),
+    PathStrategy,
+}
+
+impl DimensionSlot {
+    pub fn dimension(self) -> usize {
+        let bounce = |bounce: usize, offset: usize| CAMERA_DIMENSIONS + bounce * BOUNCE_DIMENSIONS + offset;
+        let medium = |bounce: usize, offset: usize| MEDIUM_DIMENSION_START + bounce * MEDIUM_DIMENSIONS + offset;
+        let subsurface =
+            |bounce: usize, offset: usize| SUBSURFACE_DIMENSION_START + bounce * SUBSURFACE_DIMENSIONS + offset;
+        let light_path =
+            |bounce: usize, offset: usize| LIGHT_PATH_DIMENSION_START + 5 + bounce * LIGHT_PATH_DIMENSIONS + offset;
+        match self {
+            Self::Film => 0,
+            Self::Lens => 2,
+            Self::Time => 4,
+            Self::LightChoice(b) => bounce(b, 0),
+            Self::Light(b) => bounce(b, 1),
+            Self::LightBsdf(b) => bounce(b, 3),
+            Self::Bsdf(b) => bounce(b, 5),
+            Self::RussianRoulette(b) => bounce(b, 8),
+            Self::Medium(b) => medium(b, 0),
+            Self::Phase(b) => medium(b, 2),
+            Self::Transmittance(b) => medium(b, 4),
+            Self::Subsurface(b) => subsurface(b, 0),
+            Self::SubsurfaceLightChoice(b) => subsurface(b, 3),
+            Self::SubsurfaceLight(b) => subsurface(b, 4),
+            Self::SubsurfaceLightBsdf(b) => subsurface(b, 6),
+            Self::SubsurfaceBsdf(b) => subsurface(b, 8),
+            Self::LightPathChoice => LIGHT_PATH_DIMENSION_START,
+            Self::LightPathOrigin => LIGHT_PATH_DIMENSION_START + 1,
+            Self::LightPathDirection => LIGHT_PATH_DIMENSION_START + 3,
+            Self::LightPathBsdf(b) => light_path(b, 0),
+            Self::ConnectLight(b) => light_path(b, 3),
+            Self::ConnectCamera(b) => light_path(b, 6),
+            Self::LightPathRussianRoulette(b) => light_path(b, 8),
+            // the strategy metropolis light transport picks for a path, just below the light subpath
+            Self::PathStrategy => LIGHT_PATH_DIMENSION_START - 1,
+        }
+    }
+}
+
+// array sizes requested before rendering, handed out in request order for every sample
+#[derive(Debug, Clone, Default)]
+pub struct SampleArrays {
+    sizes_1d: Vec<usize>,
+    sizes_2d: Vec<usize>,
+    next_1d: usize,
+    next_2d: usize,
+}
+
+impl SampleArrays {
+    pub fn request_1d(&mut self, n: usize) {
+        self.sizes_1d.push(n);
+    }
+    pub fn request_2d(&mut self, n: usize) {
+        self.sizes_2d.push(n);
+    }
+    pub fn start_sample(&mut self) {
+        self.next_1d = 0;
+        self.next_2d = 0;
+    }
+    // the array id of the next 1d array of size n and the dimension it starts at
+    pub fn next_1d(&mut self, n: usize) -> Option<(usize, usize)> {
+        let array = self.next_1d;
+        if self.sizes_1d.get(array) != Some(&n) {
+            return None;
+        }
+        self.next_1d += 1;
+        Some((array, ARRAY_DIMENSION_START + self.sizes_1d[..array].iter().sum::<usize>()))
+    }
+    pub fn next_2d(&mut self, n: usize) -> Option<(usize, usize)> {
+        let array = self.next_2d;
+        if self.sizes_2d.get(array) != Some(&n) {
+            return None;
+        }
+        self.next_2d += 1;
+        let start = ARRAY_DIMENSION_START + self.sizes_1d.iter().sum::<usize>();
+        Some((array, start + 2 * self.sizes_2d[..array].iter().sum::<usize>()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    #[test]
+    fn slots_never_overlap_within_max_depth() {
+        let mut slots = vec![
+            (DimensionSlot::Film, 2),
+            (DimensionSlot::Lens, 2),
+            (DimensionSlot::Time, 1),
+            (DimensionSlot::LightPathChoice, 1),
+            (DimensionSlot::LightPathOrigin, 2),
+            (DimensionSlot::LightPathDirection, 2),
+            (DimensionSlot::PathStrategy, 1),
+        ];
+        // bidirectional connections index past the deepest bounce
+        for b in 0..MAX_DEPTH + 3 {
+            slots.extend_from_slice(&[
+                (DimensionSlot::LightChoice(b), 1),
+                (DimensionSlot::Light(b), 2),
+                (DimensionSlot::LightBsdf(b), 2),
+                (DimensionSlot::Bsdf(b), 3),
+                (DimensionSlot::RussianRoulette(b), 1),
+                (DimensionSlot::Medium(b), 2),
+                (DimensionSlot::Phase(b), 2),
+                (DimensionSlot::Transmittance(b), 1),
+                (DimensionSlot::Subsurface(b), 3),
+                (DimensionSlot::SubsurfaceLightChoice(b), 1),
+                (DimensionSlot::SubsurfaceLight(b), 2),
+                (DimensionSlot::SubsurfaceLightBsdf(b), 2),
+                (DimensionSlot::SubsurfaceBsdf(b), 3),
+                (DimensionSlot::LightPathBsdf(b), 3),
+                (DimensionSlot::ConnectLight(b), 3),
+                (DimensionSlot::ConnectCamera(b), 2),
+                (DimensionSlot::LightPathRussianRoulette(b), 1),
+            ]);
+        }
+        let mut owners = HashMap::new();
+        for (slot, width) in slots {
+            for dim in slot.dimension()..slot.dimension() + width {
+                assert!(dim < ARRAY_DIMENSION_START, "{:?} reaches the arrays", slot);
+                if let Some(owner) = owners.insert(dim, slot) {
+                    panic!("{:?} and {:?} share dimension {}", owner, slot, dim);
+                }
+            }
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use super::Sampler;
 use crate::*;
-use rand::{seq::SliceRandom, thread_rng};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
 
 fn multiplicative_inverse(a: usize, n: usize) -> usize {
     let (x, _) = extended_gcd(a, n);
@@ -62,20 +62,31 @@ fn scrambled_radical_inverse(x: usize, base_index: usize) -> Float {
         * (accumulated as Float
             + inv_base * permutation_map(0, base_index) as Float / (1. - inv_base))
 }
-fn radical_inverse(x: usize, base_index: usize) -> Float {
+pub fn radical_inverse(x: usize, base_index: usize) -> Float {
     let base = PRIMS[base_index];
     let (accumulated, digit_count) = inverse(x, base_index);
     let inv_base_n = 1. / base.pow(digit_count as u32) as Float;
     inv_base_n * (accumulated as Float)
 }
+fn hashed_sample(index: usize, dim: usize) -> Float {
+    let mut x = (index as u64) ^ ((dim as u64) << 32);
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^= x >> 31;
+    (x >> 40) as Float / (1u64 << 24) as Float
+}
 #[derive(Clone)]
 pub struct HaltonSampler {
     sample_per_pixel: usize,
+    pixel_hash: u64,
+    pixel_index: usize,
     index: usize,
     scale: Vector2u,
     exp: Vector2u,
     mult_inverse: Vector2u,
-    dim: usize
+    dim: usize,
+    arrays: SampleArrays,
 }
 impl HaltonSampler {
     pub fn new(sample_per_pixel: usize, resolution: Vector2u) -> Self {
@@ -93,11 +104,14 @@ impl HaltonSampler {
         mult_inverse[1] = multiplicative_inverse(scale[0], scale[1]);
         Self {
             sample_per_pixel,
+            pixel_hash: 0,
+            pixel_index: 0,
             index: 0,
             scale,
             mult_inverse,
             dim: 0,
             exp,
+            arrays: SampleArrays::default(),
         }
     }
 }
@@ -115,11 +129,41 @@ impl Sampler for HaltonSampler {
             self.index += pixel_inverse[i] * (self.scale[1 - i]) * self.mult_inverse[i];
         }
         self.index %= self.scale.x * self.scale.y;
+        self.pixel_index = self.index;
+        self.pixel_hash = hash_pixel(pixel);
+        self.dim = 0;
+        self.arrays.start_sample();
+    }
+    fn set_sample_index(&mut self, index: usize) {
+        self.index = self.pixel_index + index * self.scale.x * self.scale.y;
         self.dim = 0;
+        self.arrays.start_sample();
     }
     fn next_sample(&mut self) {
         self.index += self.scale.x * self.scale.y;
         self.dim = 0;
+        self.arrays.start_sample();
+    }
+    fn set_dimension(&mut self, dim: usize) {
+        self.dim = dim;
+    }
+    fn dimension(&self) -> usize {
+        self.dim
+    }
+    fn sample_arrays(&mut self) -> &mut SampleArrays {
+        &mut self.arrays
+    }
+    fn round_count(&self, n: usize) -> usize {
+        n.next_power_of_two()
+    }
+    // the arrays dimensions are past the primes, so they come from (0,2)-sequences instead
+    fn array_1d(&mut self, _array: usize, dim: usize, n: usize) -> Vec<Float> {
+        let sample_index = (self.index - self.pixel_index) / (self.scale.x * self.scale.y);
+        zero_two_array_1d(sample_index, n, hash_values(&[self.pixel_hash, dim as u64]))
+    }
+    fn array_2d(&mut self, _array: usize, dim: usize, n: usize) -> Vec<Point2f> {
+        let sample_index = (self.index - self.pixel_index) / (self.scale.x * self.scale.y);
+        zero_two_array_2d(sample_index, n, hash_values(&[self.pixel_hash, dim as u64]))
     }
     fn get_sample(&mut self) -> Float {
         let r = if self.dim < 2 {
@@ -128,12 +172,15 @@ impl Sampler for HaltonSampler {
             scrambled_radical_inverse(self.index, self.dim)
         }
         else {
-            rand::random()       
+            hashed_sample(self.index, self.dim)
         }
         ;
         self.dim += 1;
         r
     }
+    fn name(&self) -> &'static str {
+        "halton"
+    }
     fn get_sample_per_pixel(&self) -> usize {
         self.sample_per_pixel
     }
@@ -216,7 +263,7 @@ lazy_static! {
 lazy_static! {
     static ref PERMUTATION: Vec<usize> = {
         let mut r = Vec::new();
-        let mut rng = thread_rng();
+        let mut rng = StdRng::seed_from_u64(0);
         for n in PRIMS.iter() {
             let mut this_permutation = vec![0; *n];
             for (i, this_perm) in this_permutation.iter_mut().enumerate() {
@@ -246,4 +293,27 @@ mod tests {
         test_pixel(&mut sampler, &Point2u::new(4, 3));
         test_pixel(&mut sampler, &Point2u::new(8, 0));
     }
+    #[test]
+    fn halton_arrays_stratified() {
+        let mut sampler = HaltonSampler::new(4, Vector2u::new(9, 4));
+        let n = sampler.round_count(13);
+        assert_eq!(n, 16);
+        sampler.request_1d_array(n);
+        sampler.request_2d_array(n);
+        sampler.set_pixel(&Point2u::new(5, 2));
+        for _ in 0..4 {
+            let mut strata: Vec<_> = sampler.get_1d_array(n).unwrap().iter().map(|u| (u * 16.) as usize).collect();
+            strata.sort_unstable();
+            assert_eq!(strata, (0..16).collect::<Vec<_>>());
+            let mut cells: Vec<_> = sampler
+                .get_2d_array(n)
+                .unwrap()
+                .iter()
+                .map(|p| (p.y * 4.) as usize * 4 + (p.x * 4.) as usize)
+                .collect();
+            cells.sort_unstable();
+            assert_eq!(cells, (0..16).collect::<Vec<_>>());
+            sampler.next_sample();
+        }
+    }
 }
@@ -0,0 +1,149 @@
+use super::Sampler;
+use crate::*;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy)]
+struct PrimarySample {
+    value: Float,
+    last_modification: usize,
+    value_backup: Float,
+    modification_backup: usize,
+}
+
+// the sampler of metropolis light transport, its sample vector is mutated every iteration
+// and rolled back when the mutation is rejected
+#[derive(Clone)]
+pub struct MLTSampler {
+    mutations_per_pixel: usize,
+    rng: Rng,
+    sigma: Float,
+    large_step_probability: Float,
+    samples: HashMap<usize, PrimarySample>,
+    dim: usize,
+    iteration: usize,
+    large_step: bool,
+    last_large_step_iteration: usize,
+    arrays: SampleArrays,
+}
+
+impl MLTSampler {
+    pub fn new(mutations_per_pixel: usize, seed: u64, sigma: Float, large_step_probability: Float) -> Self {
+        Self {
+            mutations_per_pixel,
+            rng: Rng::new(seed),
+            sigma,
+            large_step_probability,
+            samples: HashMap::new(),
+            dim: 0,
+            iteration: 0,
+            large_step: true,
+            last_large_step_iteration: 0,
+            arrays: SampleArrays::default(),
+        }
+    }
+    // the next mutation, a large step draws every sample anew, a small one moves it a little
+    pub fn start_iteration(&mut self) {
+        self.iteration += 1;
+        self.large_step = self.rng.uniform_float() < self.large_step_probability;
+        self.arrays.start_sample();
+        self.set_dimension(0);
+    }
+    pub fn accept(&mut self) {
+        if self.large_step {
+            self.last_large_step_iteration = self.iteration;
+        }
+    }
+    pub fn reject(&mut self) {
+        for sample in self.samples.values_mut() {
+            if sample.last_modification == self.iteration {
+                sample.value = sample.value_backup;
+                sample.last_modification = sample.modification_backup;
+            }
+        }
+        self.iteration -= 1;
+    }
+    // samples are only mutated when they are used, so the mutations they missed are caught up here
+    fn mutate(&mut self, dim: usize) -> Float {
+        let (iteration, last_large_step_iteration) = (self.iteration, self.last_large_step_iteration);
+        let rng = &mut self.rng;
+        let sample = self.samples.entry(dim).or_insert_with(|| {
+            let value = rng.uniform_float();
+            PrimarySample {
+                value,
+                last_modification: last_large_step_iteration,
+                value_backup: value,
+                modification_backup: last_large_step_iteration,
+            }
+        });
+        if sample.last_modification == iteration {
+            return sample.value;
+        }
+        if sample.last_modification < last_large_step_iteration {
+            sample.value = rng.uniform_float();
+            sample.last_modification = last_large_step_iteration;
+        }
+        sample.value_backup = sample.value;
+        sample.modification_backup = sample.last_modification;
+        if self.large_step {
+            sample.value = rng.uniform_float();
+        } else {
+            let small_steps = (iteration - sample.last_modification) as Float;
+            let (u1, u2) = (rng.uniform_float(), rng.uniform_float());
+            let normal = (-2. * (1. - u1).ln()).sqrt() * (2. * PI * u2).cos();
+            sample.value += normal * self.sigma * small_steps.sqrt();
+            sample.value -= sample.value.floor();
+        }
+        sample.last_modification = iteration;
+        sample.value
+    }
+}
+
+impl Sampler for MLTSampler {
+    fn box_clone(&self) -> Box<dyn Sampler> {
+        Box::new(self.clone())
+    }
+    // the chain decides where its samples go, not the pixel
+    fn set_pixel(&mut self, _pixel: &Point2u) {}
+    fn set_sample_index(&mut self, _index: usize) {}
+    fn next_sample(&mut self) {
+        self.start_iteration();
+    }
+    fn get_sample(&mut self) -> Float {
+        let r = self.mutate(self.dim);
+        self.dim += 1;
+        r
+    }
+    fn name(&self) -> &'static str {
+        "mlt"
+    }
+    fn get_sample_per_pixel(&self) -> usize {
+        self.mutations_per_pixel
+    }
+    fn set_dimension(&mut self, dim: usize) {
+        self.dim = dim;
+    }
+    fn dimension(&self) -> usize {
+        self.dim
+    }
+    fn sample_arrays(&mut self) -> &mut SampleArrays {
+        &mut self.arrays
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn mlt_reject_restores_samples() {
+        let mut sampler = MLTSampler::new(1, 7, 0.01, 0.3);
+        let first = sampler.get_1ds(4);
+        for _ in 0..16 {
+            sampler.start_iteration();
+            let mutated = sampler.get_1ds(4);
+            assert!(mutated.iter().all(|u| (0. ..1.).contains(u)));
+            sampler.reject();
+            sampler.set_dimension(0);
+            assert_eq!(sampler.get_1ds(4), first);
+        }
+    }
+}
@@ -1,11 +1,28 @@
 use crate::*;
+mod dimension;
+pub use dimension::*;
 mod halton;
 pub use halton::*;
+mod mlt;
+pub use mlt::*;
+mod random;
+pub use random::*;
+mod rng;
+pub use rng::*;
+mod sobol;
+pub use sobol::*;
+mod stratified;
+pub use stratified::*;
+mod zero_two_sequence;
+pub use zero_two_sequence::*;
+mod zsobol;
+pub use zsobol::*;
 
 pub trait Sampler: Sync + Send {
     fn get_sample(&mut self) -> Float;
     fn box_clone(&self) -> Box<dyn Sampler>;
     fn set_pixel(&mut self, pixel: &Point2u);
+    fn set_sample_index(&mut self, index: usize);
     fn next_sample(&mut self);
     fn get_1d(&mut self) -> Float {
         let r = self.get_sample();
@@ -40,20 +57,122 @@ pub trait Sampler: Sync + Send {
     fn get_2ds(&mut self, count: usize) -> Vec<Point2f> {
         (0..count).map(|_| self.get_2d()).collect()
     }
+    // the name the scene file gives the sampler
+    fn name(&self) -> &'static str;
     fn get_sample_per_pixel(&self) -> usize;
+    fn set_dimension(&mut self, dim: usize);
+    fn dimension(&self) -> usize;
+    fn start_slot(&mut self, slot: DimensionSlot) {
+        self.set_dimension(slot.dimension());
+    }
+    fn sample_arrays(&mut self) -> &mut SampleArrays;
+    fn round_count(&self, n: usize) -> usize {
+        n
+    }
+    fn request_1d_array(&mut self, n: usize) {
+        self.sample_arrays().request_1d(n);
+    }
+    fn request_2d_array(&mut self, n: usize) {
+        self.sample_arrays().request_2d(n);
+    }
+    // the next requested array for this sample, None when n does not match the request
+    fn get_1d_array(&mut self, n: usize) -> Option<Vec<Float>> {
+        let (array, dim) = self.sample_arrays().next_1d(n)?;
+        Some(self.array_1d(array, dim, n))
+    }
+    fn get_2d_array(&mut self, n: usize) -> Option<Vec<Point2f>> {
+        let (array, dim) = self.sample_arrays().next_2d(n)?;
+        Some(self.array_2d(array, dim, n))
+    }
+    fn array_1d(&mut self, _array: usize, dim: usize, n: usize) -> Vec<Float> {
+        let current = self.dimension();
+        self.set_dimension(dim);
+        let r = self.get_1ds(n);
+        self.set_dimension(current);
+        r
+    }
+    fn array_2d(&mut self, _array: usize, dim: usize, n: usize) -> Vec<Point2f> {
+        let current = self.dimension();
+        self.set_dimension(dim);
+        let r = self.get_2ds(n);
+        self.set_dimension(current);
+        r
+    }
+}
+
+// a sampler without samples would render a black image
+fn parse_pixel_samples(property_set: &PropertySet, default: Option<usize>) -> SceneResult<usize> {
+    let pixel_samples = match default {
+        Some(default) => property_set.get_value::<Integer>("pixelsamples")?.unwrap_or(default as Integer),
+        None => property_set.get_required::<Integer>("pixelsamples")?,
+    };
+    if pixel_samples <= 0 {
+        return Err(property_set.error("Sampler pixelsamples must be positive"));
+    }
+    Ok(pixel_samples as usize)
 }
 
 impl ParseFromBlockSegment<'_> for Box<dyn Sampler> {
     type T = Box<dyn FnOnce(Vector2u) -> Box<dyn Sampler>>;
-    fn parse_from_segment(segment: &BlockSegment) -> Option<Self::T> {
-        let property_set = segment.get_object_by_type("Sampler")?;
-        if property_set.get_name().unwrap() == "halton" {
-            let pixel_samples = property_set.get_value("pixelsamples").unwrap();
-            Some(Box::new(move |resolution| {
-                Box::new(HaltonSampler::new(pixel_samples, resolution))
-            }))
-        } else {
-            panic!()
+    fn parse_from_segment(segment: &BlockSegment) -> SceneResult<Option<Self::T>> {
+        let property_set = match segment.get_object_by_type("Sampler") {
+            Some(property_set) => property_set,
+            None => return Ok(None),
+        };
+        match property_set.get_name()? {
+            "halton" => {
+                let pixel_samples = parse_pixel_samples(property_set, None)?;
+                Ok(Some(Box::new(move |resolution| {
+                    Box::new(HaltonSampler::new(pixel_samples, resolution))
+                })))
+            }
+            "random" => {
+                let pixel_samples = parse_pixel_samples(property_set, Some(4))?;
+                Ok(Some(Box::new(move |_| Box::new(RandomSampler::new(pixel_samples)))))
+            }
+            "sobol" => {
+                let pixel_samples = parse_pixel_samples(property_set, Some(16))?;
+                let pixel_samples = round_up_pow2_samples("sobol", pixel_samples);
+                Ok(Some(Box::new(move |_| Box::new(SobolSampler::new(pixel_samples)))))
+            }
+            "lowdiscrepancy" | "02sequence" => {
+                let pixel_samples = parse_pixel_samples(property_set, Some(16))?;
+                let pixel_samples = round_up_pow2_samples("02sequence", pixel_samples);
+                Ok(Some(Box::new(move |_| Box::new(ZeroTwoSequenceSampler::new(pixel_samples)))))
+            }
+            "zsobol" => {
+                let pixel_samples = parse_pixel_samples(property_set, Some(16))?;
+                let pixel_samples = round_up_pow2_samples("zsobol", pixel_samples);
+                Ok(Some(Box::new(move |resolution| {
+                    Box::new(ZSobolSampler::new(pixel_samples, resolution))
+                })))
+            }
+            "stratified" => {
+                let (x_samples, y_samples) = match property_set.get_value::<usize>("pixelsamples")? {
+                    // the command line overrides pixelsamples, so split it into a grid as square as possible
+                    Some(pixel_samples) => {
+                        let x_samples = (1..=pixel_samples)
+                            .take_while(|x| x * x <= pixel_samples)
+                            .filter(|x| pixel_samples % x == 0)
+                            .last()
+                            .unwrap_or(0);
+                        (x_samples, pixel_samples / max(x_samples, 1))
+                    }
+                    None => (
+                        property_set.get_value("xsamples")?.unwrap_or(4),
+                        property_set.get_value("ysamples")?.unwrap_or(4),
+                    ),
+                };
+                let jitter = property_set.get_value("jitter")?.unwrap_or(true);
+                if x_samples == 0 || y_samples == 0 {
+                    return Err(property_set.error("Stratified sampler needs at least one sample"));
+                }
+                let samples = Vector2u::new(x_samples, y_samples);
+                Ok(Some(Box::new(move |_| Box::new(StratifiedSampler::new(samples, jitter)))))
+            }
+            sampler_type => {
+                Err(property_set.error(&format!("Unknown sampler '{}'", sampler_type)))
+            }
         }
     }
 }
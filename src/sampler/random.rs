@@ -0,0 +1,95 @@
+use super::Sampler;
+use crate::*;
+
+// a sample never uses more than this many random numbers
+const SAMPLE_STRIDE: u64 = 1 << 16;
+
+#[derive(Clone)]
+pub struct RandomSampler {
+    sample_per_pixel: usize,
+    pixel_hash: u64,
+    index: usize,
+    dim: usize,
+    rng: Rng,
+    arrays: SampleArrays,
+}
+
+impl RandomSampler {
+    pub fn new(sample_per_pixel: usize) -> Self {
+        Self {
+            sample_per_pixel,
+            pixel_hash: 0,
+            index: 0,
+            dim: 0,
+            rng: Rng::default(),
+            arrays: SampleArrays::default(),
+        }
+    }
+    fn start_sample(&mut self) {
+        self.arrays.start_sample();
+        self.set_dimension(0);
+    }
+}
+
+impl Sampler for RandomSampler {
+    fn box_clone(&self) -> Box<dyn Sampler> {
+        Box::new(self.clone())
+    }
+    fn set_pixel(&mut self, pixel: &Point2u) {
+        self.pixel_hash = hash_pixel(pixel);
+        self.index = 0;
+        self.start_sample();
+    }
+    fn set_sample_index(&mut self, index: usize) {
+        self.index = index;
+        self.start_sample();
+    }
+    fn next_sample(&mut self) {
+        self.index += 1;
+        self.start_sample();
+    }
+    fn get_sample(&mut self) -> Float {
+        self.dim += 1;
+        self.rng.uniform_float()
+    }
+    fn name(&self) -> &'static str {
+        "random"
+    }
+    fn get_sample_per_pixel(&self) -> usize {
+        self.sample_per_pixel
+    }
+    fn set_dimension(&mut self, dim: usize) {
+        self.dim = dim;
+        self.rng.set_sequence(self.pixel_hash);
+        self.rng.advance(self.index as u64 * SAMPLE_STRIDE + dim as u64);
+    }
+    fn dimension(&self) -> usize {
+        self.dim
+    }
+    fn sample_arrays(&mut self) -> &mut SampleArrays {
+        &mut self.arrays
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn random_samples_replay_by_index_and_dimension() {
+        let mut sampler = RandomSampler::new(4);
+        sampler.set_pixel(&Point2u::new(7, 2));
+        sampler.set_sample_index(2);
+        let first = sampler.get_1ds(8);
+        assert!(first.iter().all(|u| (0. ..1.).contains(u)));
+        sampler.set_dimension(3);
+        assert_eq!(sampler.get_1ds(5), first[3..]);
+        sampler.set_sample_index(2);
+        assert_eq!(sampler.get_1ds(8), first);
+        // other samples and pixels draw other numbers
+        sampler.set_sample_index(3);
+        assert_ne!(sampler.get_1ds(8), first);
+        sampler.set_pixel(&Point2u::new(7, 3));
+        sampler.set_sample_index(2);
+        assert_ne!(sampler.get_1ds(8), first);
+    }
+}
@@ -0,0 +1,124 @@
+use crate::*;
+
+const PCG32_DEFAULT_STATE: u64 = 0x853c_49e6_748f_ea9b;
+const PCG32_DEFAULT_STREAM: u64 = 0xda3e_39cb_94b9_5bdb;
+const PCG32_MULT: u64 = 0x5851_f42d_4c95_7f2d;
+
+pub fn u32_to_float(v: u32) -> Float {
+    let r = v as Float * (1. / (1u64 << 32) as Float);
+    if r < ONE_MINUS_EPSILON {
+        r
+    } else {
+        ONE_MINUS_EPSILON
+    }
+}
+
+pub fn mix_bits(mut v: u64) -> u64 {
+    v ^= v >> 31;
+    v = v.wrapping_mul(0x7fb5_d329_728e_a185);
+    v ^= v >> 27;
+    v = v.wrapping_mul(0x81da_def4_bc2d_d44d);
+    v ^= v >> 33;
+    v
+}
+
+pub fn hash_values(values: &[u64]) -> u64 {
+    values
+        .iter()
+        .fold(0, |h, v| mix_bits(h ^ v.wrapping_add(0x9e37_79b9_7f4a_7c15)))
+}
+
+pub fn hash_pixel(pixel: &Point2u) -> u64 {
+    hash_values(&[pixel.x as u64, pixel.y as u64])
+}
+
+// element i of a random permutation of 0..n chosen by seed, without building the permutation
+pub fn permutation_element(mut i: u32, n: u32, seed: u32) -> u32 {
+    let mut w = n - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= seed >> 16;
+        i ^= (i & w) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= seed >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < n {
+            break;
+        }
+    }
+    (i.wrapping_add(seed)) % n
+}
+
+// pcg32, can jump to any position of a sequence so samplers stay deterministic per sample index
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+    inc: u64,
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Self {
+            state: PCG32_DEFAULT_STATE,
+            inc: PCG32_DEFAULT_STREAM,
+        }
+    }
+}
+
+impl Rng {
+    pub fn new(sequence: u64) -> Self {
+        let mut rng = Self::default();
+        rng.set_sequence(sequence);
+        rng
+    }
+    pub fn set_sequence(&mut self, sequence: u64) {
+        self.state = 0;
+        self.inc = (sequence << 1) | 1;
+        self.uniform_u32();
+        self.state = self.state.wrapping_add(mix_bits(sequence));
+        self.uniform_u32();
+    }
+    pub fn advance(&mut self, mut delta: u64) {
+        let mut cur_mult = PCG32_MULT;
+        let mut cur_plus = self.inc;
+        let mut acc_mult = 1u64;
+        let mut acc_plus = 0u64;
+        while delta > 0 {
+            if delta & 1 == 1 {
+                acc_mult = acc_mult.wrapping_mul(cur_mult);
+                acc_plus = acc_plus.wrapping_mul(cur_mult).wrapping_add(cur_plus);
+            }
+            cur_plus = cur_mult.wrapping_add(1).wrapping_mul(cur_plus);
+            cur_mult = cur_mult.wrapping_mul(cur_mult);
+            delta /= 2;
+        }
+        self.state = acc_mult.wrapping_mul(self.state).wrapping_add(acc_plus);
+    }
+    pub fn uniform_u32(&mut self) -> u32 {
+        let old_state = self.state;
+        self.state = old_state.wrapping_mul(PCG32_MULT).wrapping_add(self.inc);
+        let xor_shifted = (((old_state >> 18) ^ old_state) >> 27) as u32;
+        let rot = (old_state >> 59) as u32;
+        xor_shifted.rotate_right(rot)
+    }
+    pub fn uniform_float(&mut self) -> Float {
+        u32_to_float(self.uniform_u32())
+    }
+}
@@ -0,0 +1,242 @@
+use super::Sampler;
+use crate::*;
+
+// primitive polynomial degree, its coefficients and the initial direction numbers, from Joe and Kuo
+static SOBOL_DIRECTIONS: [(u32, u32, &[u32]); 15] = [
+    (1, 0, &[1]),
+    (2, 1, &[1, 3]),
+    (3, 1, &[1, 3, 1]),
+    (3, 2, &[1, 1, 1]),
+    (4, 1, &[1, 1, 3, 3]),
+    (4, 4, &[1, 3, 5, 13]),
+    (5, 2, &[1, 1, 5, 5, 17]),
+    (5, 4, &[1, 1, 5, 5, 5]),
+    (5, 7, &[1, 1, 7, 11, 19]),
+    (5, 11, &[1, 1, 5, 1, 1]),
+    (5, 13, &[1, 1, 1, 3, 11]),
+    (5, 14, &[1, 3, 5, 5, 31]),
+    (6, 1, &[1, 3, 3, 9, 7, 49]),
+    (6, 13, &[1, 1, 1, 15, 21, 21]),
+    (6, 16, &[1, 3, 1, 13, 27, 49]),
+];
+
+pub const SOBOL_DIMENSIONS: usize = SOBOL_DIRECTIONS.len() + 1;
+
+lazy_static! {
+    // 64 columns so 64 bit indices work, the columns past the 32nd only keep their top bits
+    static ref SOBOL_MATRICES: Vec<[u32; 64]> = {
+        let mut matrices = Vec::with_capacity(SOBOL_DIMENSIONS);
+        let mut van_der_corput = [0; 64];
+        for (k, column) in van_der_corput.iter_mut().enumerate().take(32) {
+            *column = 1 << (31 - k);
+        }
+        matrices.push(van_der_corput);
+        for (s, a, initial) in SOBOL_DIRECTIONS.iter() {
+            let s = *s as usize;
+            let mut m = [0u64; 64];
+            m[..s].copy_from_slice(&initial.iter().map(|m| *m as u64).collect::<Vec<_>>());
+            for k in s..64 {
+                m[k] = m[k - s] ^ (m[k - s] << s);
+                for i in 1..s {
+                    if (a >> (s - 1 - i)) & 1 == 1 {
+                        m[k] ^= m[k - i] << i;
+                    }
+                }
+            }
+            let mut matrix = [0; 64];
+            for (k, column) in matrix.iter_mut().enumerate() {
+                *column = if k < 32 { m[k] << (31 - k) } else { m[k] >> (k - 31) } as u32;
+            }
+            matrices.push(matrix);
+        }
+        matrices
+    };
+}
+
+pub fn sobol_sample(mut index: u64, dim: usize) -> u32 {
+    let matrix = &SOBOL_MATRICES[dim];
+    let mut v = 0;
+    let mut k = 0;
+    while index != 0 {
+        if index & 1 == 1 {
+            v ^= matrix[k];
+        }
+        index >>= 1;
+        k += 1;
+    }
+    v
+}
+
+// random permutation of every subinterval in base 2, keeps the stratification of the sequence
+pub fn owen_scramble(mut v: u32, seed: u64) -> u32 {
+    if seed & 1 == 1 {
+        v ^= 1 << 31;
+    }
+    for b in 1..32 {
+        let mask = !0u32 << (32 - b);
+        if mix_bits((v & mask) as u64 ^ seed) & (1 << b) != 0 {
+            v ^= 1 << (31 - b);
+        }
+    }
+    v
+}
+
+// shuffles sample indices, aligned power of two blocks stay aligned blocks so the points keep their stratification
+pub fn shuffle_index(index: u32, seed: u64) -> u32 {
+    owen_scramble(index, seed)
+}
+
+// every dimension gets its own shuffled and scrambled (0,2)-sequence, used past the tabulated sobol dimensions too
+pub fn zero_two_sample(index: u32, dim: usize, seed: u64) -> Float {
+    let index = shuffle_index(index, hash_values(&[seed, dim as u64]));
+    u32_to_float(owen_scramble(sobol_sample(index as u64, 0), hash_values(&[seed, dim as u64, 1])))
+}
+
+pub fn zero_two_sample_2d(index: u32, dim: usize, seed: u64) -> Point2f {
+    let index = shuffle_index(index, hash_values(&[seed, dim as u64]));
+    Point2f::new(
+        u32_to_float(owen_scramble(sobol_sample(index as u64, 0), hash_values(&[seed, dim as u64, 1]))),
+        u32_to_float(owen_scramble(sobol_sample(index as u64, 1), hash_values(&[seed, dim as u64, 2]))),
+    )
+}
+
+// the arrays of consecutive samples are consecutive blocks of one sequence
+pub fn zero_two_array_1d(sample_index: usize, n: usize, seed: u64) -> Vec<Float> {
+    (0..n)
+        .map(|i| zero_two_sample((sample_index * n + i) as u32, 0, seed))
+        .collect()
+}
+
+pub fn zero_two_array_2d(sample_index: usize, n: usize, seed: u64) -> Vec<Point2f> {
+    (0..n)
+        .map(|i| zero_two_sample_2d((sample_index * n + i) as u32, 0, seed))
+        .collect()
+}
+
+pub fn round_up_pow2_samples(sampler_type: &str, sample_per_pixel: usize) -> usize {
+    let rounded = sample_per_pixel.next_power_of_two();
+    if rounded != sample_per_pixel {
+        warn!(
+            "Sampler '{}' needs a power of two sample count, using {} instead of {}",
+            sampler_type, rounded, sample_per_pixel
+        );
+    }
+    rounded
+}
+
+#[derive(Clone)]
+pub struct SobolSampler {
+    sample_per_pixel: usize,
+    pixel_hash: u64,
+    index: usize,
+    dim: usize,
+    arrays: SampleArrays,
+}
+
+impl SobolSampler {
+    pub fn new(sample_per_pixel: usize) -> Self {
+        Self {
+            sample_per_pixel,
+            pixel_hash: 0,
+            index: 0,
+            dim: 0,
+            arrays: SampleArrays::default(),
+        }
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn box_clone(&self) -> Box<dyn Sampler> {
+        Box::new(self.clone())
+    }
+    fn set_pixel(&mut self, pixel: &Point2u) {
+        self.pixel_hash = hash_pixel(pixel);
+        self.index = 0;
+        self.dim = 0;
+        self.arrays.start_sample();
+    }
+    fn set_sample_index(&mut self, index: usize) {
+        self.index = index;
+        self.dim = 0;
+        self.arrays.start_sample();
+    }
+    fn next_sample(&mut self) {
+        self.index += 1;
+        self.dim = 0;
+        self.arrays.start_sample();
+    }
+    fn get_sample(&mut self) -> Float {
+        let index = self.index as u32;
+        let r = if self.dim < SOBOL_DIMENSIONS {
+            let v = sobol_sample(index as u64, self.dim);
+            u32_to_float(owen_scramble(v, hash_values(&[self.pixel_hash, self.dim as u64])))
+        } else {
+            zero_two_sample(index, self.dim, self.pixel_hash)
+        };
+        self.dim += 1;
+        r
+    }
+    fn get_2d(&mut self) -> Point2f {
+        if self.dim + 1 < SOBOL_DIMENSIONS {
+            return Point2f::new(self.get_sample(), self.get_sample());
+        }
+        let r = zero_two_sample_2d(self.index as u32, self.dim, self.pixel_hash);
+        self.dim += 2;
+        r
+    }
+    fn name(&self) -> &'static str {
+        "sobol"
+    }
+    fn get_sample_per_pixel(&self) -> usize {
+        self.sample_per_pixel
+    }
+    fn set_dimension(&mut self, dim: usize) {
+        self.dim = dim;
+    }
+    fn dimension(&self) -> usize {
+        self.dim
+    }
+    fn sample_arrays(&mut self) -> &mut SampleArrays {
+        &mut self.arrays
+    }
+    fn round_count(&self, n: usize) -> usize {
+        n.next_power_of_two()
+    }
+    fn array_1d(&mut self, _array: usize, dim: usize, n: usize) -> Vec<Float> {
+        zero_two_array_1d(self.index, n, hash_values(&[self.pixel_hash, dim as u64]))
+    }
+    fn array_2d(&mut self, _array: usize, dim: usize, n: usize) -> Vec<Point2f> {
+        zero_two_array_2d(self.index, n, hash_values(&[self.pixel_hash, dim as u64]))
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn assert_stratified(values: &[u32]) {
+        let mut strata: Vec<_> = values.iter().map(|v| v >> (32 - 4)).collect();
+        strata.sort_unstable();
+        assert_eq!(strata, (0..16).collect::<Vec<_>>());
+    }
+    #[test]
+    fn sobol_stratified() {
+        for dim in 0..SOBOL_DIMENSIONS {
+            let values: Vec<_> = (0..16).map(|i| sobol_sample(i, dim)).collect();
+            assert_stratified(&values);
+            let scrambled: Vec<_> = values.iter().map(|v| owen_scramble(*v, 7 + dim as u64)).collect();
+            assert_stratified(&scrambled);
+            let shuffled: Vec<_> = (0..16).map(|i| sobol_sample(shuffle_index(i as u32, 3) as u64, dim)).collect();
+            assert_stratified(&shuffled);
+        }
+        // the first two dimensions form a (0,2)-sequence
+        let mut cells: Vec<_> = (0..16)
+            .map(|i| (sobol_sample(i, 0) >> 30) * 4 + (sobol_sample(i, 1) >> 30))
+            .collect();
+        cells.sort_unstable();
+        assert_eq!(cells, (0..16).collect::<Vec<_>>());
+        // aligned blocks past 32 bit indices are stratified too
+        for dim in 0..SOBOL_DIMENSIONS {
+            let values: Vec<_> = (0..16).map(|i| sobol_sample((5 << 40) + i, dim)).collect();
+            assert_stratified(&values);
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use super::Sampler;
+use crate::*;
+
+#[derive(Clone)]
+pub struct StratifiedSampler {
+    samples: Vector2u,
+    jitter: bool,
+    pixel_hash: u64,
+    index: usize,
+    dim: usize,
+    arrays: SampleArrays,
+}
+
+impl StratifiedSampler {
+    pub fn new(samples: Vector2u, jitter: bool) -> Self {
+        Self {
+            samples,
+            jitter,
+            pixel_hash: 0,
+            index: 0,
+            dim: 0,
+            arrays: SampleArrays::default(),
+        }
+    }
+    fn start_sample(&mut self) {
+        self.dim = 0;
+        self.arrays.start_sample();
+    }
+    fn rng(&self, dim: usize) -> Rng {
+        Rng::new(hash_values(&[self.pixel_hash, self.index as u64, dim as u64]))
+    }
+    // every dimension visits the strata in its own order, samples past the stratum count start a new round
+    fn stratum(&mut self) -> (usize, Rng) {
+        let count = self.get_sample_per_pixel();
+        let round = self.index / count;
+        let seed = hash_values(&[self.pixel_hash, self.dim as u64, round as u64]);
+        let rng = self.rng(self.dim);
+        self.dim += 1;
+        let stratum = permutation_element((self.index % count) as u32, count as u32, seed as u32);
+        (stratum as usize, rng)
+    }
+    fn offset(&self, rng: &mut Rng) -> Float {
+        if self.jitter {
+            rng.uniform_float()
+        } else {
+            0.5
+        }
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn box_clone(&self) -> Box<dyn Sampler> {
+        Box::new(self.clone())
+    }
+    fn set_pixel(&mut self, pixel: &Point2u) {
+        self.pixel_hash = hash_pixel(pixel);
+        self.index = 0;
+        self.start_sample();
+    }
+    fn set_sample_index(&mut self, index: usize) {
+        self.index = index;
+        self.start_sample();
+    }
+    fn next_sample(&mut self) {
+        self.index += 1;
+        self.start_sample();
+    }
+    fn get_sample(&mut self) -> Float {
+        let count = self.get_sample_per_pixel();
+        let (stratum, mut rng) = self.stratum();
+        (stratum as Float + self.offset(&mut rng)) / count as Float
+    }
+    fn get_2d(&mut self) -> Point2f {
+        let (stratum, mut rng) = self.stratum();
+        let x = stratum % self.samples.x;
+        let y = stratum / self.samples.x;
+        let dx = self.offset(&mut rng);
+        let dy = self.offset(&mut rng);
+        Point2f::new(
+            (x as Float + dx) / self.samples.x as Float,
+            (y as Float + dy) / self.samples.y as Float,
+        )
+    }
+    fn name(&self) -> &'static str {
+        "stratified"
+    }
+    fn get_sample_per_pixel(&self) -> usize {
+        self.samples.x * self.samples.y
+    }
+    fn set_dimension(&mut self, dim: usize) {
+        self.dim = dim;
+    }
+    fn dimension(&self) -> usize {
+        self.dim
+    }
+    fn sample_arrays(&mut self) -> &mut SampleArrays {
+        &mut self.arrays
+    }
+    // the values of one array are stratified against each other
+    fn array_1d(&mut self, array: usize, dim: usize, n: usize) -> Vec<Float> {
+        let seed = hash_values(&[self.pixel_hash, self.index as u64, dim as u64, array as u64]) as u32;
+        let mut rng = self.rng(dim);
+        (0..n)
+            .map(|i| {
+                let stratum = permutation_element(i as u32, n as u32, seed);
+                (stratum as Float + self.offset(&mut rng)) / n as Float
+            })
+            .collect()
+    }
+    // latin hypercube, stratified in each axis for any n
+    fn array_2d(&mut self, array: usize, dim: usize, n: usize) -> Vec<Point2f> {
+        let seed = hash_values(&[self.pixel_hash, self.index as u64, dim as u64, array as u64]);
+        let mut rng = self.rng(dim);
+        (0..n)
+            .map(|i| {
+                let x = permutation_element(i as u32, n as u32, seed as u32);
+                let y = permutation_element(i as u32, n as u32, (seed >> 32) as u32);
+                Point2f::new(
+                    (x as Float + self.offset(&mut rng)) / n as Float,
+                    (y as Float + self.offset(&mut rng)) / n as Float,
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn stratified_samples_cover_every_stratum() {
+        let mut sampler = StratifiedSampler::new(Vector2u::new(4, 4), true);
+        sampler.set_pixel(&Point2u::new(3, 5));
+        let (mut strata, mut cells) = (Vec::new(), Vec::new());
+        for _ in 0..16 {
+            let u = sampler.get_sample();
+            let p = sampler.get_2d();
+            strata.push((u * 16.) as usize);
+            cells.push((p.y * 4.) as usize * 4 + (p.x * 4.) as usize);
+            sampler.next_sample();
+        }
+        strata.sort_unstable();
+        cells.sort_unstable();
+        assert_eq!(strata, (0..16).collect::<Vec<_>>());
+        assert_eq!(cells, (0..16).collect::<Vec<_>>());
+        // without jitter the samples sit at the stratum centers
+        let mut sampler = StratifiedSampler::new(Vector2u::new(2, 2), false);
+        sampler.set_pixel(&Point2u::new(0, 0));
+        let mut values: Vec<_> = (0..4)
+            .map(|_| {
+                let u = sampler.get_sample();
+                sampler.next_sample();
+                u
+            })
+            .collect();
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(values, vec![0.125, 0.375, 0.625, 0.875]);
+    }
+}
@@ -0,0 +1,80 @@
+use super::Sampler;
+use crate::*;
+
+// every 1d or 2d request gets its own shuffled and scrambled (0,2)-sequence
+#[derive(Clone)]
+pub struct ZeroTwoSequenceSampler {
+    sample_per_pixel: usize,
+    pixel_hash: u64,
+    index: usize,
+    dim: usize,
+    arrays: SampleArrays,
+}
+
+impl ZeroTwoSequenceSampler {
+    pub fn new(sample_per_pixel: usize) -> Self {
+        Self {
+            sample_per_pixel,
+            pixel_hash: 0,
+            index: 0,
+            dim: 0,
+            arrays: SampleArrays::default(),
+        }
+    }
+}
+
+impl Sampler for ZeroTwoSequenceSampler {
+    fn box_clone(&self) -> Box<dyn Sampler> {
+        Box::new(self.clone())
+    }
+    fn set_pixel(&mut self, pixel: &Point2u) {
+        self.pixel_hash = hash_pixel(pixel);
+        self.index = 0;
+        self.dim = 0;
+        self.arrays.start_sample();
+    }
+    fn set_sample_index(&mut self, index: usize) {
+        self.index = index;
+        self.dim = 0;
+        self.arrays.start_sample();
+    }
+    fn next_sample(&mut self) {
+        self.index += 1;
+        self.dim = 0;
+        self.arrays.start_sample();
+    }
+    fn get_sample(&mut self) -> Float {
+        let r = zero_two_sample(self.index as u32, self.dim, self.pixel_hash);
+        self.dim += 1;
+        r
+    }
+    fn get_2d(&mut self) -> Point2f {
+        let r = zero_two_sample_2d(self.index as u32, self.dim, self.pixel_hash);
+        self.dim += 2;
+        r
+    }
+    fn name(&self) -> &'static str {
+        "02sequence"
+    }
+    fn get_sample_per_pixel(&self) -> usize {
+        self.sample_per_pixel
+    }
+    fn set_dimension(&mut self, dim: usize) {
+        self.dim = dim;
+    }
+    fn dimension(&self) -> usize {
+        self.dim
+    }
+    fn sample_arrays(&mut self) -> &mut SampleArrays {
+        &mut self.arrays
+    }
+    fn round_count(&self, n: usize) -> usize {
+        n.next_power_of_two()
+    }
+    fn array_1d(&mut self, _array: usize, dim: usize, n: usize) -> Vec<Float> {
+        zero_two_array_1d(self.index, n, hash_values(&[self.pixel_hash, dim as u64]))
+    }
+    fn array_2d(&mut self, _array: usize, dim: usize, n: usize) -> Vec<Point2f> {
+        zero_two_array_2d(self.index, n, hash_values(&[self.pixel_hash, dim as u64]))
+    }
+}
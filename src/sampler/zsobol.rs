@@ -0,0 +1,192 @@
+use super::Sampler;
+use crate::*;
+
+static PERMUTATIONS: [[u64; 4]; 24] = [
+    [0, 1, 2, 3],
+    [0, 1, 3, 2],
+    [0, 2, 1, 3],
+    [0, 2, 3, 1],
+    [0, 3, 2, 1],
+    [0, 3, 1, 2],
+    [1, 0, 2, 3],
+    [1, 0, 3, 2],
+    [1, 2, 0, 3],
+    [1, 2, 3, 0],
+    [1, 3, 2, 0],
+    [1, 3, 0, 2],
+    [2, 1, 0, 3],
+    [2, 1, 3, 0],
+    [2, 0, 1, 3],
+    [2, 0, 3, 1],
+    [2, 3, 0, 1],
+    [2, 3, 1, 0],
+    [3, 1, 2, 0],
+    [3, 1, 0, 2],
+    [3, 2, 1, 0],
+    [3, 2, 0, 1],
+    [3, 0, 2, 1],
+    [3, 0, 1, 2],
+];
+
+fn left_shift_2(mut x: u64) -> u64 {
+    x &= 0xffff_ffff;
+    x = (x ^ (x << 16)) & 0x0000_ffff_0000_ffff;
+    x = (x ^ (x << 8)) & 0x00ff_00ff_00ff_00ff;
+    x = (x ^ (x << 4)) & 0x0f0f_0f0f_0f0f_0f0f;
+    x = (x ^ (x << 2)) & 0x3333_3333_3333_3333;
+    x = (x ^ (x << 1)) & 0x5555_5555_5555_5555;
+    x
+}
+
+fn encode_morton_2(x: usize, y: usize) -> u64 {
+    (left_shift_2(y as u64) << 1) | left_shift_2(x as u64)
+}
+
+// Ahmed and Wonka's screen space sample ordering: the pixels along the z-order curve share one
+// sobol sequence, so neighbouring pixels get well distributed samples and the error looks like blue noise
+#[derive(Clone)]
+pub struct ZSobolSampler {
+    sample_per_pixel: usize,
+    log2_sample_per_pixel: usize,
+    base4_digits: usize,
+    morton_index: u64,
+    round: usize,
+    index: usize,
+    dim: usize,
+    arrays: SampleArrays,
+}
+
+impl ZSobolSampler {
+    pub fn new(sample_per_pixel: usize, resolution: Vector2u) -> Self {
+        let log2_sample_per_pixel = sample_per_pixel.trailing_zeros() as usize;
+        let res = max(resolution.x, resolution.y).next_power_of_two();
+        let log4_sample_per_pixel = log2_sample_per_pixel.div_ceil(2);
+        Self {
+            sample_per_pixel,
+            log2_sample_per_pixel,
+            base4_digits: res.trailing_zeros() as usize + log4_sample_per_pixel,
+            morton_index: 0,
+            round: 0,
+            index: 0,
+            dim: 0,
+            arrays: SampleArrays::default(),
+        }
+    }
+    fn start_sample(&mut self, pixel_morton: u64) {
+        // samples past the sample count repeat the pattern with another scramble
+        self.round = self.index / self.sample_per_pixel;
+        let index = (self.index % self.sample_per_pixel) as u64;
+        self.morton_index = (pixel_morton << self.log2_sample_per_pixel) | index;
+        self.dim = 0;
+        self.arrays.start_sample();
+    }
+    fn pixel_morton(&self) -> u64 {
+        self.morton_index >> self.log2_sample_per_pixel
+    }
+    fn dim_hash(&self) -> u64 {
+        hash_values(&[self.dim as u64, self.round as u64])
+    }
+    // shuffles the base 4 digits of the morton index, each digit by the digits above it
+    fn sample_index(&self) -> u64 {
+        let dim_mask = 0x5555_5555u64.wrapping_mul(self.dim as u64 + self.round as u64 * 0x1_0000);
+        let odd = self.log2_sample_per_pixel & 1 == 1;
+        let last_digit = if odd { 1 } else { 0 };
+        let mut sample_index = 0u64;
+        for i in (last_digit..self.base4_digits).rev() {
+            let digit_shift = 2 * i - last_digit;
+            let digit = (self.morton_index >> digit_shift) & 3;
+            let higher_digits = self.morton_index >> (digit_shift + 2);
+            let p = (mix_bits(higher_digits ^ dim_mask) >> 24) % 24;
+            sample_index |= PERMUTATIONS[p as usize][digit as usize] << digit_shift;
+        }
+        if odd {
+            let digit = self.morton_index & 1;
+            sample_index |= digit ^ (mix_bits((self.morton_index >> 1) ^ dim_mask) & 1);
+        }
+        sample_index
+    }
+}
+
+impl Sampler for ZSobolSampler {
+    fn box_clone(&self) -> Box<dyn Sampler> {
+        Box::new(self.clone())
+    }
+    fn set_pixel(&mut self, pixel: &Point2u) {
+        self.index = 0;
+        self.start_sample(encode_morton_2(pixel.x, pixel.y));
+    }
+    fn set_sample_index(&mut self, index: usize) {
+        self.index = index;
+        self.start_sample(self.pixel_morton());
+    }
+    fn next_sample(&mut self) {
+        self.index += 1;
+        self.start_sample(self.pixel_morton());
+    }
+    fn get_sample(&mut self) -> Float {
+        let v = sobol_sample(self.sample_index(), 0);
+        let r = u32_to_float(owen_scramble(v, self.dim_hash()));
+        self.dim += 1;
+        r
+    }
+    fn get_2d(&mut self) -> Point2f {
+        let sample_index = self.sample_index();
+        let hash = self.dim_hash();
+        let r = Point2f::new(
+            u32_to_float(owen_scramble(sobol_sample(sample_index, 0), hash)),
+            u32_to_float(owen_scramble(sobol_sample(sample_index, 1), hash >> 32)),
+        );
+        self.dim += 2;
+        r
+    }
+    fn name(&self) -> &'static str {
+        "zsobol"
+    }
+    fn get_sample_per_pixel(&self) -> usize {
+        self.sample_per_pixel
+    }
+    fn set_dimension(&mut self, dim: usize) {
+        self.dim = dim;
+    }
+    fn dimension(&self) -> usize {
+        self.dim
+    }
+    fn sample_arrays(&mut self) -> &mut SampleArrays {
+        &mut self.arrays
+    }
+    fn round_count(&self, n: usize) -> usize {
+        n.next_power_of_two()
+    }
+    fn array_1d(&mut self, _array: usize, dim: usize, n: usize) -> Vec<Float> {
+        let seed = hash_values(&[self.pixel_morton(), dim as u64]);
+        zero_two_array_1d(self.index, n, seed)
+    }
+    fn array_2d(&mut self, _array: usize, dim: usize, n: usize) -> Vec<Point2f> {
+        let seed = hash_values(&[self.pixel_morton(), dim as u64]);
+        zero_two_array_2d(self.index, n, seed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn zsobol_keeps_indices_past_32_bits() {
+        // 2^17 pixels a side and 4 samples need 36 bits of sample index
+        let mut sampler = ZSobolSampler::new(4, Vector2u::new(1 << 17, 1 << 17));
+        let mut indices = Vec::new();
+        for pixel in &[Point2u::new(0, 0), Point2u::new(1, 0), Point2u::new(0, 1 << 16), Point2u::new(1, 1 << 16)] {
+            sampler.set_pixel(pixel);
+            for _ in 0..4 {
+                indices.push(sampler.sample_index());
+                let u = sampler.get_2d();
+                assert!((0. ..1.).contains(&u.x) && (0. ..1.).contains(&u.y));
+                sampler.next_sample();
+            }
+        }
+        assert!(indices[8..].iter().all(|index| *index >= 1 << 32));
+        indices.sort_unstable();
+        indices.dedup();
+        assert_eq!(indices.len(), 16);
+    }
+}
@@ -11,6 +11,10 @@ pub struct Scene {
     lights: Vec<Arc<dyn Light>>,
     materials: Vec<Arc<dyn Material>>,
     aggregate: AggregateBuilder,
+    primitive_count: usize,
+    camera_medium: Option<Arc<dyn Medium>>,
+    world_bound: Option<Bounds3f>,
+    has_media: bool,
 }
 
 impl Scene {
@@ -20,21 +24,77 @@ impl Scene {
     pub fn intersect(&self, ray: &Ray) -> Option<PrimitiveIntersect> {
         self.aggregate.get().intersect(&ray)
     }
+    // the first intersection with a surface that is not only a medium boundary,
+    // with the transmittance of the media the ray passed on the way
+    pub fn intersect_tr(
+        &self,
+        ray: &Ray,
+        medium: Option<Arc<dyn Medium>>,
+        sampler: &mut dyn Sampler,
+    ) -> (Option<PrimitiveIntersect>, Spectrum) {
+        let mut tr = Spectrum::new(1.);
+        let mut ray = *ray;
+        let mut medium = medium;
+        loop {
+            let intersect = self.intersect(&ray);
+            if let Some(medium) = &medium {
+                let t_max = intersect.as_ref().map_or(ray.t_max, |i| i.get_shape_intersect().get_t());
+                tr *= medium.tr(&Ray { t_max, ..ray }, sampler);
+            }
+            match intersect {
+                Some(intersect) if intersect.is_medium_interface() => {
+                    medium = intersect.medium_after(&ray.d, medium);
+                    ray = Ray::new_shape_point_d(intersect.get_shape_point(), ray.d);
+                }
+                intersect => return (intersect, tr),
+            }
+        }
+    }
+    pub fn camera_medium(&self) -> Option<Arc<dyn Medium>> {
+        self.camera_medium.clone()
+    }
     pub fn get_lights(&self) -> &[Arc<dyn Light>] {
         &self.lights
     }
+    // lights at infinity send their light from just outside this bound
+    pub fn world_bound(&self) -> Bounds3f {
+        let origin = Point3f::new(0., 0., 0.);
+        self.world_bound.clone().unwrap_or_else(|| Bounds3f::new(&origin, &origin))
+    }
+    pub fn has_media(&self) -> bool {
+        self.has_media || self.camera_medium.is_some()
+    }
     pub fn build_aggregate(&mut self, aggregate: Box<dyn Aggregate>) {
         self.aggregate.build(aggregate);
     }
+    pub fn material_id(&self, material: &Arc<dyn Material>) -> Option<usize> {
+        let ptr = Arc::as_ptr(material) as *const ();
+        self.materials
+            .iter()
+            .position(|m| Arc::as_ptr(m) as *const () == ptr)
+    }
+    fn add_primitive(&mut self, primitive: Primitive) {
+        let id = self.primitive_count;
+        self.primitive_count += 1;
+        self.has_media |= primitive.has_media();
+        self.world_bound = Some(match self.world_bound.take() {
+            Some(world_bound) => world_bound | &primitive.bound(),
+            None => primitive.bound(),
+        });
+        self.aggregate.add_primitive(primitive.with_id(id));
+    }
 }
 
 #[derive(Default, Clone)]
 struct SceneParseStack {
     material: Option<Arc<dyn Material>>,
-    transform: Option<Transform>,
+    transform: TransformState,
+    transform_times: TransformTimes,
     area_light_factory: Option<AreaLightFactory>,
     texture_map: texture_map::TextureMap,
     named_material: HashMap<String, Arc<dyn Material>>,
+    interface_material: bool,
+    medium_interface: MediumInterface,
     object_name: Option<String>,
 }
 
@@ -44,26 +104,30 @@ impl SceneParseStack {
         segment: &BlockSegment,
         scene: &mut Scene,
         objects: &mut HashMap<String, Vec<Primitive>>,
+        named_media: &mut NamedMedia,
         clipper: Option<&dyn PrimitiveClipper>,
-    ) {
+    ) -> SceneResult<()> {
         if let Some((block_type, block_name, segments)) = segment.as_block() {
             match block_type {
                 "Attribute" => {
                     let mut attribute_stack = self.clone();
                     for segment in segments {
-                        attribute_stack.parse(segment, scene, objects, clipper);
+                        attribute_stack.parse(segment, scene, objects, named_media, clipper)?;
                     }
                 }
                 "Object" => {
                     let mut object_stack = self.clone();
-                    object_stack.object_name = Some(block_name.as_ref().unwrap().clone());
+                    let object_name = block_name
+                        .clone()
+                        .ok_or_else(|| segment.error("Missing object name"))?;
+                    object_stack.object_name = Some(object_name);
                     for segment in segments {
-                        object_stack.parse(segment, scene, objects, clipper);
+                        object_stack.parse(segment, scene, objects, named_media, clipper)?;
                     }
                 }
-                _ => panic!(),
+                _ => return Err(segment.error("Unknown block")),
             }
-            return;
+            return Ok(());
         }
         let (object_type, property_set) = segment.get_object().unwrap();
         match object_type {
@@ -72,32 +136,54 @@ impl SceneParseStack {
                     property_set,
                     &self.texture_map,
                     &self.named_material,
-                );
-                self.named_material.insert(String::from(name), m.into());
+                )?;
+                let m: Arc<dyn Material> = m.into();
+                scene.materials.push(m.clone());
+                self.named_material.insert(String::from(name), m);
             }
             "Material" => {
+                // shapes without material only separate media
+                if let "" | "none" | "interface" = property_set.get_name()? {
+                    self.material = None;
+                    self.interface_material = true;
+                    return Ok(());
+                }
                 let m: Arc<dyn Material> =
-                    parse_material(property_set, &self.texture_map, &self.named_material).into();
+                    parse_material(property_set, &self.texture_map, &self.named_material)?.into();
                 self.material = Some(m.clone());
+                self.interface_material = false;
                 scene.materials.push(m);
             }
+            "MakeNamedMedium" => {
+                let medium_to_world = self.transform.start().cloned().unwrap_or_default();
+                let (name, medium) = parse_make_named_medium(property_set, &medium_to_world)?;
+                named_media.insert(String::from(name), medium.into());
+            }
+            "MediumInterface" => {
+                self.medium_interface = parse_medium_interface(property_set, named_media)?;
+            }
             "Shape" => {
-                let shapes = if let Some(transform) = &self.transform {
-                    shapes_apply(parse_shape(property_set), transform.clone())
-                } else {
-                    parse_shape(property_set)
+                let shapes = match self.transform.animated(&self.transform_times) {
+                    Some(transform) if transform.is_animated() && self.area_light_factory.is_some() => {
+                        warn!("{}", property_set.error("Animated transform is ignored for area lights"));
+                        shapes_apply(parse_shape(property_set)?, transform.start_transform().clone().into())
+                    }
+                    Some(transform) => shapes_apply(parse_shape(property_set)?, transform),
+                    None => parse_shape(property_set)?,
                 };
                 for shape in shapes {
                     let primitive = if let Some(area_light_factory) = &self.area_light_factory {
                         let area_light: Arc<dyn Light> = area_light_factory(shape.clone()).into();
                         scene.lights.push(area_light.clone());
                         Primitive::new(shape, PrimitiveSource::light(area_light))
+                    } else if let Some(material) = self.material.clone() {
+                        Primitive::new(shape, PrimitiveSource::material(material))
+                    } else if self.interface_material {
+                        Primitive::new(shape, PrimitiveSource::Interface)
                     } else {
-                        Primitive::new(
-                            shape,
-                            PrimitiveSource::material(self.material.clone().unwrap()),
-                        )
+                        return Err(property_set.error("Shape has no material"));
                     };
+                    let primitive = primitive.with_medium_interface(self.medium_interface.clone());
                     if let Some(object_name) = &self.object_name {
                         objects
                             .entry(object_name.clone())
@@ -111,16 +197,16 @@ impl SceneParseStack {
                             }
                         }
                         if !clip {
-                            scene.aggregate.add_primitive(primitive);
+                            scene.add_primitive(primitive);
                         }
                     }
                 }
             }
             "ObjectInstance" => {
-                let object_name = property_set.get_name().unwrap();
+                let object_name = property_set.get_name()?;
                 if let Some(primitives) = objects.get(object_name) {
-                    let primitives = if let Some(transform) = &self.transform {
-                        primitives_apply(primitives.clone(), transform.clone())
+                    let primitives = if let Some(transform) = self.transform.animated(&self.transform_times) {
+                        primitives_apply(primitives.clone(), transform)
                     }
                     else {
                         primitives.clone()
@@ -133,68 +219,85 @@ impl SceneParseStack {
                             }
                         }
                         if !clip {
-                            scene.aggregate.add_primitive(primitive);
+                            scene.add_primitive(primitive);
                         }
                     }
                 }
             }
             "LightSource" => {
-                let mut light = parse_light(property_set);
-                if let Some(transform) = &self.transform {
+                let mut light = parse_light(property_set)?;
+                if let Some(transform) = self.transform.start() {
                     light = light.box_apply(transform);
                 }
                 scene.lights.push(light.into());
             }
             "AreaLightSource" => {
-                self.area_light_factory = Some(parse_area_light(property_set));
+                self.area_light_factory = Some(parse_area_light(property_set)?);
             }
             "Texture" => {
-                self.texture_map.add_texture(property_set);
-            }
-            "Transform" => {
-                self.transform = Some(Transform::parse_from_segment(segment).unwrap());
+                self.texture_map.add_texture(property_set)?;
             }
             _ => {
-                if let Some(this_transform) = Transform::parse_from_segment(segment) {
-                    self.transform = Some(
-                        self.transform
-                            .clone()
-                            .map_or(this_transform.clone(), |transform| {
-                                transform.apply(&this_transform)
-                            }),
-                    );
-                } else {
-                    error!("Parsing scene error");
-                    error!("object_type {}", object_type);
-                    error!("property_set {:#?}", property_set);
+                if !self.transform.parse(segment)? {
+                    error!("{}", property_set.error("Unsupported directive"));
                 }
             }
         }
+        Ok(())
     }
 }
 
 pub struct SceneBuilder<'a> {
     segments: &'a Vec<BlockSegment>,
+    transform_times: TransformTimes,
+    named_media: NamedMedia,
+    camera_medium: Option<Arc<dyn Medium>>,
 }
 
 impl<'a> SceneBuilder<'a> {
-    pub fn build_with_clipper(&self, clipper: Option<&dyn PrimitiveClipper>) -> Scene {
-        let mut scene = Scene::default();
-        let mut scene_parse_stack = SceneParseStack::default();
+    pub fn with_transform_times(self, transform_times: TransformTimes) -> Self {
+        Self {
+            transform_times,
+            ..self
+        }
+    }
+    // media made before the world block and the medium the camera is in
+    pub fn with_media(self, named_media: NamedMedia, camera_medium: Option<Arc<dyn Medium>>) -> Self {
+        Self {
+            named_media,
+            camera_medium,
+            ..self
+        }
+    }
+    pub fn build_with_clipper(&self, clipper: Option<&dyn PrimitiveClipper>) -> SceneResult<Scene> {
+        let mut scene = Scene {
+            camera_medium: self.camera_medium.clone(),
+            ..Scene::default()
+        };
+        let mut scene_parse_stack = SceneParseStack {
+            transform_times: self.transform_times,
+            ..SceneParseStack::default()
+        };
+        // like objects, named media outlive the attribute block they are made in
         let mut objects = HashMap::new();
+        let mut named_media = self.named_media.clone();
         for segment in self.segments {
-            scene_parse_stack.parse(segment, &mut scene, &mut objects, clipper);
+            scene_parse_stack.parse(segment, &mut scene, &mut objects, &mut named_media, clipper)?;
         }
-        scene
+        Ok(scene)
     }
 }
 
 impl<'a> ParseFromBlockSegment<'a> for SceneBuilder<'a> {
     type T = SceneBuilder<'a>;
-    fn parse_from_segment(segment: &'a BlockSegment) -> Option<Self::T> {
-        let (_, block_segments) = segment.get_block("World")?;
-        Some(SceneBuilder {
-            segments: block_segments,
-        })
+    fn parse_from_segment(segment: &'a BlockSegment) -> SceneResult<Option<Self::T>> {
+        Ok(segment
+            .get_block("World")
+            .map(|(_, block_segments)| SceneBuilder {
+                segments: block_segments,
+                transform_times: TransformTimes::default(),
+                named_media: NamedMedia::default(),
+                camera_medium: None,
+            }))
     }
 }
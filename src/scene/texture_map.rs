@@ -1,5 +1,5 @@
 use crate::def::Float;
-use crate::scene_file_parser::PropertySet;
+use crate::scene_file_parser::{PropertySet, SceneResult};
 use crate::spectrum::Spectrum;
 use crate::texture;
 use crate::texture::ImageTexture;
@@ -14,23 +14,23 @@ pub struct TextureMap {
 }
 
 impl TextureMap {
-    pub fn add_texture(&mut self, property_set: &PropertySet) {
-        let file_name: PathBuf = property_set.get_value("filename").unwrap();
-        let mut property_set = property_set.clone();
-        let name = String::from(
-            property_set
-                .as_one_basic_types(1)
-                .unwrap()
-                .get_string()
-                .unwrap(),
-        );
-        let texture_type = String::from(
-            property_set
+    pub fn add_texture(&mut self, property_set: &PropertySet) -> SceneResult<()> {
+        let file_name: PathBuf = property_set.get_required("filename")?;
+        if !file_name.is_file() {
+            return Err(property_set.error(&format!(
+                "Cant find texture file {}",
+                file_name.display()
+            )));
+        }
+        let mut names = property_set.clone();
+        let mut next_string = || {
+            names
                 .as_one_basic_types(1)
-                .unwrap()
-                .get_string()
-                .unwrap(),
-        );
+                .and_then(|basic_types| basic_types.get_string().map(String::from))
+                .ok_or_else(|| property_set.error("Expected texture name and type"))
+        };
+        let name = next_string()?;
+        let texture_type = next_string()?;
         match texture_type.as_str() {
             "float" => {
                 self.map.insert(
@@ -45,8 +45,14 @@ impl TextureMap {
                     texture
                 });
             }
-            _ => panic!(),
+            _ => {
+                return Err(property_set.error(&format!(
+                    "Unknown texture type '{}'",
+                    texture_type
+                )));
+            }
         }
+        Ok(())
     }
 }
 
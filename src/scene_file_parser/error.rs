@@ -0,0 +1,69 @@
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SourceLocation {
+    pub file: PathBuf,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl SourceLocation {
+    pub fn new(file: PathBuf, line: usize, column: usize) -> Self {
+        Self { file, line, column }
+    }
+    pub fn from_file(file: &Path) -> Self {
+        Self::new(PathBuf::from(file), 0, 0)
+    }
+}
+
+impl Display for SourceLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}", self.file.display())
+        } else {
+            write!(f, "{}:{}:{}", self.file.display(), self.line, self.column)
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SceneError {
+    pub file: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    pub directive: Option<String>,
+    pub message: String,
+}
+
+pub type SceneResult<T> = Result<T, SceneError>;
+
+impl SceneError {
+    pub fn new<S: Into<String>>(location: &SourceLocation, directive: Option<&str>, message: S) -> Self {
+        Self {
+            file: location.file.clone(),
+            line: location.line,
+            column: location.column,
+            directive: directive.map(String::from),
+            message: message.into(),
+        }
+    }
+    pub fn in_file<S: Into<String>>(file: &Path, message: S) -> Self {
+        Self::new(&SourceLocation::from_file(file), None, message)
+    }
+    pub fn location(&self) -> SourceLocation {
+        SourceLocation::new(self.file.clone(), self.line, self.column)
+    }
+}
+
+impl Display for SceneError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at {}", self.message, self.location())?;
+        if let Some(directive) = &self.directive {
+            write!(f, " [{}]", directive)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SceneError {}
@@ -11,11 +11,11 @@ pub struct TokenWithPos {
     pub origin: String,
 }
 impl TokenWithPos {
-    pub fn panic(&self, s: &str) {
-        panic!(
-            "file [{:?}] line {} column {} [{}]: {}",
-            self.file, self.line, self.column, self.origin, s
-        );
+    pub fn location(&self) -> SourceLocation {
+        SourceLocation::new(self.file.clone(), self.line, self.column)
+    }
+    pub fn error(&self, s: &str) -> SceneError {
+        SceneError::new(&self.location(), Some(&self.origin), s)
     }
 }
 #[derive(Debug)]
@@ -72,13 +72,11 @@ impl<'a> LexParser<'a> {
         };
         self.token_with_pos.push(token_with_pos);
     }
-    fn panic(&self, s: &str) {
-        panic!(
-            "file [{:?}] line {} column {}: {}",
-            self.file, self.line, self.column, s
-        );
+    fn error(&self, s: &str) -> SceneError {
+        let location = SourceLocation::new(PathBuf::from(self.file), self.line, self.column);
+        SceneError::new(&location, None, s)
     }
-    fn parse(mut self) -> Vec<TokenWithPos> {
+    fn parse(mut self) -> SceneResult<Vec<TokenWithPos>> {
         let mut array_left_info = None;
         while self.index != self.s.len() {
             match self.s[self.index..].chars().next().unwrap() {
@@ -98,8 +96,10 @@ impl<'a> LexParser<'a> {
                         if let Some(token) = self.token_with_pos.last() {
                             if let Token::Include = token.token {
                                 self.token_with_pos.pop();
-                                let include_file = self.file.parent().unwrap().join(Path::new(content));
-                                let tokens = parse_lex(&include_file);
+                                // a file without a directory includes from the current one
+                                let directory = self.file.parent().unwrap_or_else(|| Path::new(""));
+                                let include_file = directory.join(Path::new(content));
+                                let tokens = parse_lex(&include_file)?;
                                 self.token_with_pos.extend(tokens.into_iter());
                                 is_include = true;
                             }
@@ -113,7 +113,7 @@ impl<'a> LexParser<'a> {
                         self.column += end - self.index + 1;
                         self.index = end + 1;
                     } else {
-                        self.panic("Unpaired '\"'");
+                        return Err(self.error("Unpaired '\"'"));
                     }
                 }
                 '#' => {
@@ -150,12 +150,19 @@ impl<'a> LexParser<'a> {
                         self.column += 1;
                         self.index += 1;
                     } else {
-                        self.panic("Unpaired ']'");
+                        return Err(self.error("Unpaired ']'"));
                     }
                 }
                 'A'..='Z' => {
                     if let Some((end, word)) = self.word() {
-                        if word == "Include" {
+                        let after_active_transform = match self.token_with_pos.last() {
+                            Some(TokenWithPos { token: Token::Type(t), .. }) => t == "ActiveTransform",
+                            _ => false,
+                        };
+                        if after_active_transform {
+                            // StartTime, EndTime or All is the argument of ActiveTransform, not a directive
+                            self.push_token(Token::String(String::from(word)), word);
+                        } else if word == "Include" {
                             self.push_token(Token::Include, word);
                         } else if word.ends_with("Begin") {
                             self.push_token(
@@ -183,7 +190,7 @@ impl<'a> LexParser<'a> {
                         } else if let Ok(f) = word.parse::<Float>() {
                             self.push_token(Token::Float(f), word);
                         } else {
-                            self.panic(&format!("Cant parse number \"{}\"", word));
+                            return Err(self.error(&format!("Cant parse number \"{}\"", word)));
                         }
                         self.column += end - self.index;
                         self.index = end;
@@ -192,14 +199,19 @@ impl<'a> LexParser<'a> {
                     }
                 }
                 c => {
-                    self.panic(&format!("Cant parse char '{}", c));
+                    return Err(self.error(&format!("Cant parse char '{}'", c)));
                 }
             }
         }
-        self.token_with_pos
+        Ok(self.token_with_pos)
     }
 }
-pub fn parse_lex(file: &Path) -> Vec<TokenWithPos> {
-    let s = fs::read_to_string(file).unwrap();
-    LexParser::new(&s, file).parse()
+pub fn parse_lex(file: &Path) -> SceneResult<Vec<TokenWithPos>> {
+    let s = fs::read_to_string(file)
+        .map_err(|e| SceneError::in_file(file, format!("Cant read file: {}", e)))?;
+    parse_lex_str(&s, file)
+}
+// the file only names the tokens of s for errors
+pub fn parse_lex_str(s: &str, file: &Path) -> SceneResult<Vec<TokenWithPos>> {
+    LexParser::new(s, file).parse()
 }
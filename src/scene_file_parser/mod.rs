@@ -1,7 +1,9 @@
+mod error;
 pub mod lex;
 
 use crate::*;
-use lex::{parse_lex, Token, TokenWithPos};
+pub use error::*;
+use lex::{parse_lex, parse_lex_str, Token, TokenWithPos};
 use std::collections::VecDeque;
 use std::path::PathBuf;
 use std::{ops::Index, path::Path};
@@ -9,13 +11,13 @@ use std::{ops::Index, path::Path};
 #[derive(Debug)]
 pub struct FileBlock(Vec<BlockSegment>);
 impl FileBlock {
-    fn from_lex(tokens: Vec<TokenWithPos>) -> Self {
+    fn from_lex(tokens: Vec<TokenWithPos>) -> SceneResult<Self> {
         let mut tokens = tokens.into_iter().collect::<VecDeque<_>>();
         let mut segments = Vec::new();
         while !tokens.is_empty() {
-            segments.push(BlockSegment::from_lex(&mut tokens));
+            segments.push(BlockSegment::from_lex(&mut tokens)?);
         }
-        Self(segments)
+        Ok(Self(segments))
     }
 }
 
@@ -31,6 +33,7 @@ pub enum BlockSegment {
         block_type: String,
         name: Option<String>,
         block_segments: Vec<BlockSegment>,
+        location: SourceLocation,
     },
     Object {
         object_type: String,
@@ -38,8 +41,9 @@ pub enum BlockSegment {
     },
 }
 impl BlockSegment {
-    fn from_lex(tokens: &mut VecDeque<TokenWithPos>) -> Self {
+    fn from_lex(tokens: &mut VecDeque<TokenWithPos>) -> SceneResult<Self> {
         let token = tokens.pop_front().unwrap();
+        let location = token.location();
         match token.token {
             Token::Type(object_type) => {
                 let mut object_value = Vec::new();
@@ -49,14 +53,15 @@ impl BlockSegment {
                         Token::BlockBegin(_) => break,
                         Token::BlockEnd(_) => break,
                         _ => {
-                            object_value.push(Property::from_lex(tokens));
+                            object_value.push(Property::from_lex(tokens)?);
                         }
                     }
                 }
-                Self::Object {
+                let object_value = PropertySet::new(object_value, &object_type, location);
+                Ok(Self::Object {
                     object_type,
-                    object_value: PropertySet::from(object_value),
-                }
+                    object_value,
+                })
             }
             Token::BlockBegin(block_type) => {
                 let mut name = None;
@@ -71,31 +76,38 @@ impl BlockSegment {
                         }
                     }
                 }
+                let mut closed = false;
                 while let Some(token) = tokens.front() {
                     match &token.token {
                         Token::BlockEnd(end_type) => {
                             if end_type == &block_type {
                                 tokens.pop_front();
+                                closed = true;
                                 break;
                             } else {
-                                token.panic("Unpaired block end");
+                                return Err(token.error("Unpaired block end"));
                             }
                         }
                         _ => {
-                            block_segments.push(BlockSegment::from_lex(tokens));
+                            block_segments.push(BlockSegment::from_lex(tokens)?);
                         }
                     }
                 }
-                Self::Block {
+                if !closed {
+                    return Err(SceneError::new(
+                        &location,
+                        Some(&format!("{}Begin", block_type)),
+                        "Unclosed block",
+                    ));
+                }
+                Ok(Self::Block {
                     block_type,
                     name,
                     block_segments,
-                }
-            }
-            _ => {
-                token.panic("Unexpected block segments");
-                unreachable!()
+                    location,
+                })
             }
+            _ => Err(token.error("Unexpected block segments")),
         }
     }
     pub fn get_object(&self) -> Option<(&str, &PropertySet)> {
@@ -122,11 +134,21 @@ impl BlockSegment {
             _ => None,
         }
     }
+    pub fn get_object_by_type_mut(&mut self, to_find_object_type: &str) -> Option<&mut PropertySet> {
+        match self {
+            BlockSegment::Object {
+                object_type,
+                object_value,
+            } if object_type == to_find_object_type => Some(object_value),
+            _ => None,
+        }
+    }
     pub fn as_block(&self) -> Option<(&str, &Option<String>, &Vec<BlockSegment>)> {
         if let BlockSegment::Block {
             block_type,
             name,
             block_segments,
+            ..
         } = self
         {
             Some((block_type, name, block_segments))
@@ -142,30 +164,63 @@ impl BlockSegment {
             None
         }
     }
+    pub fn error(&self, s: &str) -> SceneError {
+        match self {
+            BlockSegment::Block {
+                block_type,
+                location,
+                ..
+            } => SceneError::new(location, Some(&format!("{}Begin", block_type)), s),
+            BlockSegment::Object { object_value, .. } => object_value.error(s),
+        }
+    }
 }
 
 pub trait ParseFromBlockSegment<'a> {
     type T;
-    fn parse_from_segment(segment: &'a BlockSegment) -> Option<Self::T>;
+    fn parse_from_segment(segment: &'a BlockSegment) -> SceneResult<Option<Self::T>>;
 }
 #[derive(Debug, Clone)]
-pub struct PropertySet(VecDeque<Property>);
+pub struct PropertySet {
+    properties: VecDeque<Property>,
+    directive: String,
+    location: SourceLocation,
+}
 impl From<Vec<Property>> for PropertySet {
     fn from(xs: Vec<Property>) -> Self {
-        Self(xs.into_iter().collect())
+        Self::new(xs, "", SourceLocation::default())
     }
 }
 
 impl PropertySet {
-    pub fn get_name(&self) -> Option<&str> {
-        self[0].get_string()
+    pub fn new(xs: Vec<Property>, directive: &str, location: SourceLocation) -> Self {
+        Self {
+            properties: xs.into_iter().collect(),
+            directive: String::from(directive),
+            location,
+        }
+    }
+    pub fn directive(&self) -> &str {
+        &self.directive
+    }
+    pub fn location(&self) -> &SourceLocation {
+        &self.location
+    }
+    pub fn error(&self, s: &str) -> SceneError {
+        SceneError::new(&self.location, Some(&self.directive), s)
     }
-    pub fn get_string(&self, name: &str) -> Option<String> {
+    pub fn get_name(&self) -> SceneResult<&str> {
+        self.properties
+            .front()
+            .and_then(|p| p.basic_types().get_string())
+            .ok_or_else(|| self.error("Missing name"))
+    }
+    pub fn get_string(&self, name: &str) -> SceneResult<Option<String>> {
         self.get_value::<String>(name)
     }
-    pub fn get_value<T: ParseFromProperty>(&self, name: &str) -> Option<T> {
+    pub fn get_value<T: ParseFromProperty>(&self, name: &str) -> SceneResult<Option<T>> {
         let name_ = name;
-        for p in &self.0 {
+        for p in &self.properties {
             if let Property::TypedValue {
                 type_name,
                 name,
@@ -173,29 +228,73 @@ impl PropertySet {
             } = p
             {
                 if name == name_ {
-                    return Some(T::parse_from_property(type_name, values));
+                    return T::parse_from_property(type_name, values)
+                        .map(Some)
+                        .ok_or_else(|| {
+                            self.error(&format!(
+                                "Invalid value for parameter '{} {}'",
+                                type_name, name
+                            ))
+                        });
+                }
+            }
+        }
+        Ok(None)
+    }
+    pub fn set_value(&mut self, type_name: &str, name: &str, values: BasicTypes) {
+        let property = Property::TypedValue {
+            type_name: String::from(type_name),
+            name: String::from(name),
+            values,
+        };
+        for p in &mut self.properties {
+            if let Property::TypedValue { name: name_, .. } = p {
+                if name_ == name {
+                    *p = property;
+                    return;
                 }
             }
         }
-        None
+        self.properties.push_back(property);
     }
-    pub fn get_no_type_value<T: ParseFromProperty + ParseConsumeProperty>(&mut self) -> Option<T> {
-        Some(T::parse_from_property(
-            "",
-            &self.as_one_basic_types(T::consume_size())?,
-        ))
+    pub fn remove_value(&mut self, name: &str) {
+        self.properties.retain(|p| match p {
+            Property::TypedValue { name: name_, .. } => name_ != name,
+            _ => true,
+        });
+    }
+    pub fn get_required<T: ParseFromProperty>(&self, name: &str) -> SceneResult<T> {
+        self.get_value(name)?
+            .ok_or_else(|| self.error(&format!("Missing parameter '{}'", name)))
     }
-    pub fn get_default<T: ParseFromProperty>(&self, name: &str) -> T {
-        self.get_value(name).unwrap_or_else(T::parse_default)
+    pub fn get_no_type_value<T: ParseFromProperty + ParseConsumeProperty>(
+        &mut self,
+    ) -> SceneResult<T> {
+        let consume_size = T::consume_size();
+        self.as_one_basic_types(consume_size)
+            .and_then(|basic_types| T::parse_from_property("", &basic_types))
+            .ok_or_else(|| self.error(&format!("Expected {} values", consume_size)))
+    }
+    // None only when no values are left, values that dont parse are still an error
+    pub fn get_optional_no_type_value<T: ParseFromProperty + ParseConsumeProperty>(
+        &mut self,
+    ) -> SceneResult<Option<T>> {
+        if self.properties.is_empty() {
+            return Ok(None);
+        }
+        self.get_no_type_value().map(Some)
+    }
+    pub fn get_default<T: ParseFromProperty>(&self, name: &str) -> SceneResult<T> {
+        Ok(self.get_value(name)?.unwrap_or_else(T::parse_default))
     }
     pub fn as_one_basic_types(&mut self, mut size: usize) -> Option<BasicTypes> {
         let mut basic_type_vec = Vec::new();
         while size > 0 {
-            let basic_type = self.0.pop_front()?.into_basic_types();
+            let basic_type = self.properties.pop_front()?.into_basic_types();
             for i in 0..min(size, basic_type.0.len()) {
                 basic_type_vec.push(basic_type.0[i].clone());
             }
-            size -= basic_type.0.len();
+            size -= min(size, basic_type.0.len());
         }
         Some(BasicTypes(basic_type_vec.into()))
     }
@@ -203,7 +302,7 @@ impl PropertySet {
 impl Index<usize> for PropertySet {
     type Output = BasicTypes;
     fn index(&self, index: usize) -> &Self::Output {
-        self.0[index].basic_types()
+        self.properties[index].basic_types()
     }
 }
 
@@ -237,60 +336,64 @@ impl Property {
             } => values,
         }
     }
-    fn from_lex(tokens: &mut VecDeque<TokenWithPos>) -> Self {
-        let token = tokens.front().unwrap();
+    fn from_lex(tokens: &mut VecDeque<TokenWithPos>) -> SceneResult<Self> {
+        let token = tokens.pop_front().unwrap();
         match &token.token {
             Token::String(s) => {
                 let words = s.split_whitespace().collect::<Vec<_>>();
                 if words.len() == 2 {
                     match words[0] {
-                        "string" | "float" | "spectrum" | "texture" | "integer" | "rgb" | "point" => {
+                        "string" | "float" | "spectrum" | "texture" | "integer" | "rgb" | "point"
+                        | "bool" => {
                             // TypedValue
-                            let words = s.split_whitespace().collect::<Vec<_>>();
                             let type_name = String::from(words[0]);
                             let name = String::from(words[1]);
-                            tokens.pop_front();
-                            let values = BasicTypes::from_lex(tokens);
-                            return Self::TypedValue {
+                            let value_token = tokens
+                                .pop_front()
+                                .ok_or_else(|| token.error("Missing parameter value"))?;
+                            let values = BasicTypes::from_lex(value_token)?;
+                            return Ok(Self::TypedValue {
                                 type_name,
                                 name,
                                 values,
-                            };
+                            });
                         }
                         _ => (),
                     }
-                    if let Token::Array(_) = tokens[1].token {}
                 }
                 // SingleString
-                let token_with_pos = tokens.pop_front().unwrap();
-                if let Token::String(s) = token_with_pos.token {
-                    Self::Value(BasicTypes(
-                        vec![BasicType::BasicString(s, token_with_pos.file)].into(),
-                    ))
+                if let Token::String(s) = token.token {
+                    Ok(Self::Value(BasicTypes(
+                        vec![BasicType::BasicString(s, token.file)].into(),
+                    )))
                 } else {
                     unreachable!()
                 }
             }
-            _ => Self::Value(BasicTypes::from_lex(tokens)),
+            _ => Ok(Self::Value(BasicTypes::from_lex(token)?)),
         }
     }
 }
 #[derive(Debug, Clone)]
 pub struct BasicTypes(VecDeque<BasicType>);
+impl From<Vec<BasicType>> for BasicTypes {
+    fn from(xs: Vec<BasicType>) -> Self {
+        Self(xs.into())
+    }
+}
 impl BasicTypes {
-    fn from_lex(tokens: &mut VecDeque<TokenWithPos>) -> Self {
-        let token = tokens.pop_front().unwrap();
+    fn from_lex(token: TokenWithPos) -> SceneResult<Self> {
         match token.token {
             Token::Array(internal_tokens) => {
                 let values = internal_tokens
                     .into_iter()
                     .map(BasicType::from_lex)
-                    .collect::<Vec<_>>();
-                Self(values.into())
+                    .collect::<SceneResult<Vec<_>>>()?;
+                Ok(Self(values.into()))
             }
             _ => {
-                let value = BasicType::from_lex(token);
-                Self(vec![value].into())
+                let value = BasicType::from_lex(token)?;
+                Ok(Self(vec![value].into()))
             }
         }
     }
@@ -299,7 +402,7 @@ impl BasicTypes {
     }
     pub fn get_path(&self) -> Option<PathBuf> {
         if let BasicType::BasicString(s, file) = self.0.front()? {
-            Some(file.as_path().parent().unwrap().join(s))
+            Some(file.as_path().parent().unwrap_or_else(|| Path::new("")).join(s))
         } else {
             None
         }
@@ -317,7 +420,7 @@ impl BasicTypes {
             match basic_type {
                 BasicType::BasicFloat(f) => r.push(*f),
                 BasicType::BasicInteger(i) => r.push(*i as Float),
-                _ => panic!(),
+                _ => return None,
             }
         }
         Some(r)
@@ -337,8 +440,8 @@ impl BasicTypes {
         }
     }
 }
-pub trait ParseFromProperty {
-    fn parse_from_property(property_type: &str, basic_type: &BasicTypes) -> Self;
+pub trait ParseFromProperty: Sized {
+    fn parse_from_property(property_type: &str, basic_type: &BasicTypes) -> Option<Self>;
     fn parse_default() -> Self;
 }
 
@@ -355,13 +458,16 @@ impl<T: ParseConsumeProperty, R: ParseConsumeProperty> ParseConsumeProperty for
 impl<T: ParseConsumeProperty + ParseFromProperty, R: ParseConsumeProperty + ParseFromProperty>
     ParseFromProperty for (T, R)
 {
-    fn parse_from_property(property_type: &str, basic_type: &BasicTypes) -> Self {
+    fn parse_from_property(property_type: &str, basic_type: &BasicTypes) -> Option<Self> {
+        if basic_type.0.len() < T::consume_size() {
+            return None;
+        }
         let mut basic_type_r = basic_type.clone();
         let basic_type_t = basic_type_r.split(T::consume_size());
-        (
-            T::parse_from_property(property_type, &basic_type_t),
-            R::parse_from_property(property_type, &basic_type_r),
-        )
+        Some((
+            T::parse_from_property(property_type, &basic_type_t)?,
+            R::parse_from_property(property_type, &basic_type_r)?,
+        ))
     }
     fn parse_default() -> Self {
         (T::parse_default(), R::parse_default())
@@ -369,11 +475,11 @@ impl<T: ParseConsumeProperty + ParseFromProperty, R: ParseConsumeProperty + Pars
 }
 
 impl<T: ParseConsumeProperty + ParseFromProperty> ParseFromProperty for Vec<T> {
-    fn parse_from_property(property_type: &str, basic_type: &BasicTypes) -> Self {
+    fn parse_from_property(property_type: &str, basic_type: &BasicTypes) -> Option<Self> {
         let consume_size = T::consume_size();
         let len = basic_type.0.len() / consume_size;
         if len * consume_size != basic_type.0.len() {
-            panic!();
+            return None;
         }
         let mut r = Vec::new();
         let mut basic_type = basic_type.clone();
@@ -381,9 +487,9 @@ impl<T: ParseConsumeProperty + ParseFromProperty> ParseFromProperty for Vec<T> {
             r.push(T::parse_from_property(
                 property_type,
                 &basic_type.split(consume_size),
-            ));
+            )?);
         }
-        r
+        Some(r)
     }
     fn parse_default() -> Self {
         Vec::new()
@@ -391,8 +497,8 @@ impl<T: ParseConsumeProperty + ParseFromProperty> ParseFromProperty for Vec<T> {
 }
 
 impl ParseFromProperty for String {
-    fn parse_from_property(_: &str, basic_type: &BasicTypes) -> Self {
-        String::from(basic_type.get_string().unwrap())
+    fn parse_from_property(_: &str, basic_type: &BasicTypes) -> Option<Self> {
+        Some(String::from(basic_type.get_string()?))
     }
     fn parse_default() -> Self {
         String::new()
@@ -400,8 +506,8 @@ impl ParseFromProperty for String {
 }
 impl ParseConsumeProperty for String {}
 impl ParseFromProperty for PathBuf {
-    fn parse_from_property(_: &str, basic_type: &BasicTypes) -> Self {
-        basic_type.get_path().unwrap()
+    fn parse_from_property(_: &str, basic_type: &BasicTypes) -> Option<Self> {
+        basic_type.get_path()
     }
     fn parse_default() -> Self {
         PathBuf::new()
@@ -409,8 +515,8 @@ impl ParseFromProperty for PathBuf {
 }
 impl ParseConsumeProperty for PathBuf {}
 impl ParseFromProperty for Float {
-    fn parse_from_property(_: &str, basic_type: &BasicTypes) -> Self {
-        basic_type.get_float().unwrap()
+    fn parse_from_property(_: &str, basic_type: &BasicTypes) -> Option<Self> {
+        basic_type.get_float()
     }
     fn parse_default() -> Self {
         0.
@@ -418,17 +524,35 @@ impl ParseFromProperty for Float {
 }
 impl ParseConsumeProperty for Float {}
 impl ParseFromProperty for Integer {
-    fn parse_from_property(_: &str, basic_type: &BasicTypes) -> Self {
-        basic_type.get_integer().unwrap()
+    fn parse_from_property(_: &str, basic_type: &BasicTypes) -> Option<Self> {
+        basic_type.get_integer()
     }
     fn parse_default() -> Self {
         0
     }
 }
 impl ParseConsumeProperty for Integer {}
+impl ParseFromProperty for bool {
+    fn parse_from_property(_: &str, basic_type: &BasicTypes) -> Option<Self> {
+        match basic_type.get_string()? {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        }
+    }
+    fn parse_default() -> Self {
+        false
+    }
+}
+impl ParseConsumeProperty for bool {}
 impl ParseFromProperty for usize {
-    fn parse_from_property(_: &str, basic_type: &BasicTypes) -> Self {
-        basic_type.get_integer().unwrap() as usize
+    fn parse_from_property(_: &str, basic_type: &BasicTypes) -> Option<Self> {
+        let i = basic_type.get_integer()?;
+        if i < 0 {
+            None
+        } else {
+            Some(i as usize)
+        }
     }
     fn parse_default() -> Self {
         0
@@ -443,20 +567,49 @@ pub enum BasicType {
     BasicInteger(Integer),
 }
 impl BasicType {
-    fn from_lex(token: TokenWithPos) -> Self {
+    fn from_lex(token: TokenWithPos) -> SceneResult<Self> {
         match token.token {
-            Token::String(s) => Self::BasicString(s, token.file),
-            Token::Integer(i) => Self::BasicInteger(i),
-            Token::Float(f) => Self::BasicFloat(f),
-            _ => {
-                token.panic("Unexpected basic type");
-                unreachable!()
-            }
+            Token::String(s) => Ok(Self::BasicString(s, token.file)),
+            Token::Integer(i) => Ok(Self::BasicInteger(i)),
+            Token::Float(f) => Ok(Self::BasicFloat(f)),
+            _ => Err(token.error("Unexpected basic type")),
         }
     }
 }
 
-pub fn read_scene(file: &Path) -> Vec<BlockSegment> {
-    let tokens = parse_lex(file);
-    FileBlock::from_lex(tokens).0
+pub fn read_scene(file: &Path) -> SceneResult<Vec<BlockSegment>> {
+    let tokens = parse_lex(file)?;
+    Ok(FileBlock::from_lex(tokens)?.0)
+}
+
+pub fn read_scene_str(s: &str, file: &Path) -> SceneResult<Vec<BlockSegment>> {
+    let tokens = parse_lex_str(s, file)?;
+    Ok(FileBlock::from_lex(tokens)?.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn scene_errors_carry_location() {
+        let file = Path::new("scene.pbrt");
+        let scene = "LookAt 0 0 0  0 0 1  0 1 0\nCamera \"perspective\"\nSampler \"bogus\"\n";
+        let segments = read_scene_str(scene, file).unwrap();
+        let error = match Box::<dyn Sampler>::parse_from_segment(&segments[2]) {
+            Err(error) => error,
+            Ok(_) => panic!("unknown sampler parsed"),
+        };
+        assert_eq!(error.message, "Unknown sampler 'bogus'");
+        assert_eq!((error.file.as_path(), error.line, error.column), (file, 3, 1));
+        assert_eq!(error.directive.as_deref(), Some("Sampler"));
+        let error = read_scene_str("Shape \"sphere\"\n  \"float radius\" [1 ?]", file).unwrap_err();
+        assert_eq!((error.line, error.column), (2, 21));
+    }
+    #[test]
+    fn include_without_directory() {
+        // the scene has no file name at all, the include is looked up in the current directory
+        let error = read_scene_str("Include \"missing-include.pbrt\"\n", Path::new("")).unwrap_err();
+        assert!(error.message.starts_with("Cant read file"), "{}", error.message);
+        assert_eq!(error.file.as_path(), Path::new("missing-include.pbrt"));
+    }
 }
@@ -187,20 +187,23 @@ impl_num_op_assign!(MulAssign, mul_assign, *=);
 impl_num_op_assign!(DivAssign, div_assign, /=);
 
 impl ParseFromProperty for RGBSpectrum {
-    fn parse_from_property(type_name: &str, basic_type: &BasicTypes) -> Self {
+    fn parse_from_property(type_name: &str, basic_type: &BasicTypes) -> Option<Self> {
         match type_name {
             "rgb" => {
-                let floats = basic_type.get_floats().unwrap();
+                let floats = basic_type.get_floats()?;
                 if floats.len() != 3 {
-                    panic!()
+                    return None;
                 }
-                RGBSpectrum::from([floats[0], floats[1], floats[2]])
+                Some(RGBSpectrum::from([floats[0], floats[1], floats[2]]))
             }
             "spectrum" => {
-                let sampled = Vec::parse_from_property(type_name, basic_type);
-                RGBSpectrum::from_sampled(sampled)
+                let sampled: Vec<(Float, Float)> = Vec::parse_from_property(type_name, basic_type)?;
+                if sampled.is_empty() {
+                    return None;
+                }
+                Some(RGBSpectrum::from_sampled(sampled))
             }
-            _ => Self::parse_default(),
+            _ => Some(Self::parse_default()),
         }
     }
     fn parse_default() -> Self {
@@ -0,0 +1,101 @@
+use crate::*;
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::io::{BufRead, Cursor};
+use std::path::{Path, PathBuf};
+
+// a box open towards the camera, lit by a small sphere below its ceiling
+pub fn box_scene(sampler: &str, integrator: &str) -> String {
+    format!(
+        r#"LookAt 0 0 -3.4  0 0 0  0 1 0
+Camera "perspective" "float fov" [40]
+{}
+Film "image" "integer xresolution" [16] "integer yresolution" [16]
+{}
+WorldBegin
+AttributeBegin
+  AreaLightSource "diffuse" "rgb L" [4 4 4]
+  Translate 0 0.7 0
+  Shape "sphere" "float radius" [0.2]
+AttributeEnd
+Material "matte" "rgb Kd" [0.7 0.7 0.7]
+Shape "trianglemesh" "integer indices" [0 1 2 2 3 0  4 5 6 6 7 4  8 9 10 10 11 8]
+  "point P" [-1 -1 -1  1 -1 -1  1 -1 1  -1 -1 1
+             -1 1 -1  1 1 -1  1 1 1  -1 1 1
+             -1 -1 1  1 -1 1  1 1 1  -1 1 1]
+AttributeBegin
+  Material "matte" "rgb Kd" [0.7 0.1 0.1]
+  Shape "trianglemesh" "integer indices" [0 1 2 2 3 0]
+    "point P" [-1 -1 -1  -1 -1 1  -1 1 1  -1 1 -1]
+AttributeEnd
+AttributeBegin
+  Material "matte" "rgb Kd" [0.1 0.7 0.1]
+  Shape "trianglemesh" "integer indices" [0 1 2 2 3 0]
+    "point P" [1 -1 -1  1 -1 1  1 1 1  1 1 -1]
+AttributeEnd
+WorldEnd
+"#,
+        sampler, integrator
+    )
+}
+
+fn temp_path(name: &str, extension: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("curry-pbrt-{}-{}.{}", name, std::process::id(), extension))
+}
+
+pub fn read_pfm(path: &Path) -> (Vector2u, Vec<Spectrum>) {
+    let mut r = Cursor::new(std::fs::read(path).unwrap());
+    let mut header = String::new();
+    for _ in 0..3 {
+        r.read_line(&mut header).unwrap();
+    }
+    let size: Vec<usize> = header.lines().nth(1).unwrap().split(' ').map(|s| s.parse().unwrap()).collect();
+    let resolution = Vector2u::new(size[0], size[1]);
+    let mut pixels = vec![Spectrum::new(0.); size[0] * size[1]];
+    // the bottom row comes first
+    for y in (0..size[1]).rev() {
+        for x in 0..size[0] {
+            let mut rgb = [0.; 3];
+            for c in &mut rgb {
+                *c = r.read_f32::<LittleEndian>().unwrap();
+            }
+            pixels[y * size[0] + x] = Spectrum::from(rgb);
+        }
+    }
+    (resolution, pixels)
+}
+
+pub fn load_test_scene(name: &str, scene: &str, options: &RenderOptions) -> SceneResult<LoadedScene> {
+    let scene_path = temp_path(name, "pbrt");
+    std::fs::write(&scene_path, scene).unwrap();
+    let loaded = load_scene(&scene_path, options);
+    std::fs::remove_file(&scene_path).unwrap();
+    loaded
+}
+
+// renders the scene through the scene file path the binary takes, the image comes back linear
+pub fn render_scene(name: &str, scene: &str, options: &RenderOptions) -> SceneResult<Vec<Spectrum>> {
+    let scene_path = temp_path(name, "pbrt");
+    let image_path = temp_path(name, "pfm");
+    std::fs::write(&scene_path, scene).unwrap();
+    let options = RenderOptions {
+        output: Some(image_path.to_str().unwrap().to_owned()),
+        quiet: true,
+        ..options.clone()
+    };
+    let result = render_from_file_with_options(&scene_path, &options);
+    std::fs::remove_file(&scene_path).unwrap();
+    result?;
+    let (_, pixels) = read_pfm(&image_path);
+    std::fs::remove_file(&image_path).unwrap();
+    Ok(pixels)
+}
+
+pub fn mean(pixels: &[Spectrum]) -> [Float; 3] {
+    let mut sum = [0.; 3];
+    for pixel in pixels {
+        for (c, sum) in sum.iter_mut().enumerate() {
+            *sum += pixel[c];
+        }
+    }
+    sum.map(|s| s / pixels.len() as Float)
+}
@@ -1,12 +1,12 @@
-use crate::texture::image::exr::ExrImageFileReader;
+use crate::texture::image::exr::{ExrImageFileReader, ExrImageFileWriter};
 use crate::*;
-use ::png::{BitDepth, ColorType, Encoder, HasParameters};
-use std::fs::File;
-use std::io::BufWriter;
+use std::io;
 use std::path::Path;
 mod exr;
+mod pfm;
 mod png;
-use crate::texture::image::png::PngImageFileReader;
+use crate::texture::image::pfm::PfmImageFileWriter;
+use crate::texture::image::png::{PngImageFileReader, PngImageFileWriter};
 
 pub trait ImageTextureContent {
     fn default() -> Self;
@@ -67,6 +67,35 @@ impl<T: Clone + ImageTextureContent + Default> ImageTexture<T> {
 pub trait ImageFileReader {
     fn read_file(&self, file_path: &Path) -> (Vector2u, Vec<Spectrum>);
 }
+
+pub trait ImageFileWriter {
+    fn write_file(&self, file_path: &Path, resolution: Vector2u, pixels: &[Spectrum]) -> io::Result<()>;
+    fn is_hdr(&self) -> bool;
+    fn supports_channels(&self) -> bool {
+        false
+    }
+    fn write_channels(
+        &self,
+        _file_path: &Path,
+        _resolution: Vector2u,
+        _channels: Vec<(&str, Vec<Float>)>,
+    ) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Image format does not support extra channels",
+        ))
+    }
+}
+
+pub fn image_file_writer(file_path: &Path) -> Option<Box<dyn ImageFileWriter>> {
+    let extension = file_path.extension()?.to_str()?.to_lowercase();
+    match extension.as_str() {
+        "png" => Some(Box::new(PngImageFileWriter {})),
+        "exr" => Some(Box::new(ExrImageFileWriter {})),
+        "pfm" => Some(Box::new(PfmImageFileWriter {})),
+        _ => None,
+    }
+}
 impl<T: Clone + ImageTextureContent> ImageTexture<T> {
     pub fn from_file(file_path: &Path) -> Self {
         let image_file_reader: Box<dyn ImageFileReader> =
@@ -105,25 +134,13 @@ impl<T: ImageTextureContent + Clone + std::marker::Sync + std::marker::Send + st
 }
 
 impl ImageTexture<Spectrum> {
-    pub fn into_file(self, file_path: &Path) {
-        let file = File::create(file_path).unwrap();
-        let w = BufWriter::new(file);
+    pub fn into_file(self, file_path: &Path) -> io::Result<()> {
+        let image_file_writer = image_file_writer(file_path).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "Unsupported image format")
+        })?;
         let resolution = self.pixels.size();
-        let mut encoder = Encoder::new(w, resolution.x as u32, resolution.y as u32);
-        encoder.set(ColorType::RGB).set(BitDepth::Eight);
-        let mut writer = encoder.write_header().unwrap();
-        let mut data = Vec::new();
-        let mut floats = Vec::new();
-        for pixel in self.pixels {
-            let [r, g, b]: [Float; 3] = pixel.into();
-            floats.push(r);
-            floats.push(g);
-            floats.push(b);
-        }
-        for float in floats {
-            data.push(clamp(float * 255. + 0.5, 0., 255.) as u8);
-        }
-        writer.write_image_data(&data).unwrap()
+        let pixels: Vec<Spectrum> = self.pixels.into_iter().collect();
+        image_file_writer.write_file(file_path, resolution, &pixels)
     }
     pub fn post_effect<F: Fn(&FixedVec2D<Spectrum>, Point2u) -> Spectrum>(&self, f: F) -> Self {
         let mut pixels = FixedVec2D::new(Spectrum::new(0.), self.pixels.size());
@@ -133,3 +150,53 @@ impl ImageTexture<Spectrum> {
         ImageTexture::from(pixels)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::{LittleEndian, ReadBytesExt};
+    use std::io::{BufRead, Cursor};
+    fn assert_same(lhs: &[Spectrum], rhs: &[Spectrum]) {
+        assert_eq!(lhs.len(), rhs.len());
+        for (l, r) in lhs.iter().zip(rhs) {
+            assert_eq!((l[0], l[1], l[2]), (r[0], r[1], r[2]));
+        }
+    }
+    #[test]
+    fn hdr_writers_round_trip() {
+        let resolution = Vector2u::new(3, 2);
+        let pixels: Vec<Spectrum> = (0..6)
+            .map(|i| Spectrum::from([i as Float * 1.5, 0.25, 100. + i as Float]))
+            .collect();
+        let dir = std::env::temp_dir();
+        let exr_path = dir.join(format!("curry-pbrt-round-trip-{}.exr", std::process::id()));
+        image_file_writer(&exr_path).unwrap().write_file(&exr_path, resolution, &pixels).unwrap();
+        let (exr_resolution, exr_pixels) = ExrImageFileReader {}.read_file(&exr_path);
+        std::fs::remove_file(&exr_path).unwrap();
+        assert_eq!(exr_resolution, resolution);
+        assert_same(&exr_pixels, &pixels);
+        let pfm_path = dir.join(format!("curry-pbrt-round-trip-{}.pfm", std::process::id()));
+        let writer = image_file_writer(&pfm_path).unwrap();
+        assert!(writer.is_hdr());
+        writer.write_file(&pfm_path, resolution, &pixels).unwrap();
+        let mut r = Cursor::new(std::fs::read(&pfm_path).unwrap());
+        std::fs::remove_file(&pfm_path).unwrap();
+        let mut header = String::new();
+        for _ in 0..3 {
+            r.read_line(&mut header).unwrap();
+        }
+        assert_eq!(header, "PF\n3 2\n-1.0\n");
+        let mut pfm_pixels = vec![Spectrum::new(0.); 6];
+        // the bottom row comes first
+        for y in (0..2).rev() {
+            for x in 0..3 {
+                let mut rgb = [0.; 3];
+                for c in &mut rgb {
+                    *c = r.read_f32::<LittleEndian>().unwrap();
+                }
+                pfm_pixels[y * 3 + x] = Spectrum::from(rgb);
+            }
+        }
+        assert_same(&pfm_pixels, &pixels);
+    }
+}
@@ -1,5 +1,7 @@
 use crate::*;
 use exr::prelude::*;
+use std::convert::TryInto;
+use std::io;
 use std::path::Path;
 pub struct ExrImageFileReader {}
 
@@ -60,3 +62,45 @@ impl ImageFileReader for ExrImageFileReader {
         (resolution, s)
     }
 }
+
+pub struct ExrImageFileWriter {}
+
+impl ImageFileWriter for ExrImageFileWriter {
+    fn write_file(&self, file_path: &Path, resolution: Vector2u, pixels: &[Spectrum]) -> io::Result<()> {
+        let channels = ["R", "G", "B"]
+            .iter()
+            .enumerate()
+            .map(|(c, name)| (*name, pixels.iter().map(|s| s[c]).collect()))
+            .collect();
+        self.write_channels(file_path, resolution, channels)
+    }
+    fn is_hdr(&self) -> bool {
+        true
+    }
+    fn supports_channels(&self) -> bool {
+        true
+    }
+    fn write_channels(
+        &self,
+        file_path: &Path,
+        resolution: Vector2u,
+        channels: Vec<(&str, Vec<Float>)>,
+    ) -> io::Result<()> {
+        let channels: Vec<simple::Channel> = channels
+            .into_iter()
+            .map(|(name, samples)| {
+                simple::Channel::new_linear(name.try_into().unwrap(), simple::Samples::F32(samples))
+            })
+            .collect();
+        let layer = simple::Layer::new(
+            "curry-pbrt".try_into().unwrap(),
+            (resolution.x, resolution.y),
+            channels.into(),
+        )
+        .with_compression(Compression::ZIP16)
+        .with_block_format(None, attributes::LineOrder::Increasing);
+        simple::Image::new_from_single_layer(layer)
+            .write_to_file(file_path, write_options::high())
+            .map_err(|e| io::Error::other(e.to_string()))
+    }
+}
@@ -0,0 +1,27 @@
+use crate::*;
+use byteorder::{LittleEndian, WriteBytesExt};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+pub struct PfmImageFileWriter {}
+
+impl ImageFileWriter for PfmImageFileWriter {
+    fn write_file(&self, file_path: &Path, resolution: Vector2u, pixels: &[Spectrum]) -> io::Result<()> {
+        let file = File::create(file_path)?;
+        let mut w = BufWriter::new(file);
+        // negative scale marks little endian data
+        write!(w, "PF\n{} {}\n-1.0\n", resolution.x, resolution.y)?;
+        // pfm scanlines go from bottom to top
+        for row in pixels.chunks(resolution.x).rev() {
+            for pixel in row {
+                for c in 0..3 {
+                    w.write_f32::<LittleEndian>(pixel[c])?;
+                }
+            }
+        }
+        w.flush()
+    }
+    fn is_hdr(&self) -> bool {
+        true
+    }
+}
@@ -1,5 +1,7 @@
 use crate::*;
+use ::png::{BitDepth, ColorType, Encoder, HasParameters};
 use std::fs::File;
+use std::io::{self, BufWriter};
 use std::path::Path;
 pub struct PngImageFileReader {}
 impl ImageFileReader for PngImageFileReader {
@@ -25,3 +27,26 @@ impl ImageFileReader for PngImageFileReader {
 fn to_rgb(u: u8) -> Float {
     u as Float / 255.
 }
+
+pub struct PngImageFileWriter {}
+impl ImageFileWriter for PngImageFileWriter {
+    fn write_file(&self, file_path: &Path, resolution: Vector2u, pixels: &[Spectrum]) -> io::Result<()> {
+        let file = File::create(file_path)?;
+        let w = BufWriter::new(file);
+        let mut encoder = Encoder::new(w, resolution.x as u32, resolution.y as u32);
+        encoder.set(ColorType::RGB).set(BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        let mut data = Vec::new();
+        for pixel in pixels {
+            let rgb: [Float; 3] = (*pixel).into();
+            for float in rgb.iter() {
+                data.push(clamp(float * 255. + 0.5, 0., 255.) as u8);
+            }
+        }
+        writer.write_image_data(&data)?;
+        Ok(())
+    }
+    fn is_hdr(&self) -> bool {
+        false
+    }
+}
@@ -28,10 +28,13 @@ pub enum TextureParseResult<T> {
 impl<T: ImageTextureContent + Send + Sync + std::fmt::Debug + Clone + 'static>
     TextureParseResult<T>
 {
-    pub fn into_texture<M: TextureMap>(self, map: &M) -> Arc<dyn Texture<T>> {
+    pub fn into_texture<M: TextureMap>(self, map: &M) -> Result<Arc<dyn Texture<T>>, String> {
         match self {
-            Self::Value(r) => r,
-            Self::FromName(name) => map.get(&name).unwrap(),
+            Self::Value(r) => Ok(r),
+            Self::FromName(name) => match map.get(&name) {
+                Some(texture) => Ok(texture),
+                None => Err(name),
+            },
         }
     }
 }
@@ -39,14 +42,15 @@ impl<T: ImageTextureContent + Send + Sync + std::fmt::Debug + Clone + 'static>
 impl<T: std::fmt::Debug + Sync + Send + Clone + 'static + ParseFromProperty> ParseFromProperty
     for TextureParseResult<T>
 {
-    fn parse_from_property(property_type: &str, basic_type: &BasicTypes) -> Self {
+    fn parse_from_property(property_type: &str, basic_type: &BasicTypes) -> Option<Self> {
         match property_type {
-            "texture" => {
-                TextureParseResult::FromName(String::parse_from_property(property_type, basic_type))
-            }
-            _ => TextureParseResult::Value(Arc::new(ConstantTexture::from(
-                T::parse_from_property(property_type, basic_type),
-            ))),
+            "texture" => Some(TextureParseResult::FromName(String::parse_from_property(
+                property_type,
+                basic_type,
+            )?)),
+            _ => Some(TextureParseResult::Value(Arc::new(ConstantTexture::from(
+                T::parse_from_property(property_type, basic_type)?,
+            )))),
         }
     }
     fn parse_default() -> Self {
@@ -57,13 +61,16 @@ impl<T: std::fmt::Debug + Sync + Send + Clone + 'static + ParseFromProperty> Par
 impl<T: 'static + std::fmt::Debug + Sync + Send + Clone + ImageTextureContent> ParseFromProperty
     for Arc<dyn Texture<T>>
 {
-    fn parse_from_property(type_name: &str, basic_type: &BasicTypes) -> Self {
+    fn parse_from_property(type_name: &str, basic_type: &BasicTypes) -> Option<Self> {
         match type_name {
             "string" => {
-                let texture_path = basic_type.get_path().unwrap();
-                Arc::new(ImageTexture::from_file(&texture_path))
+                let texture_path = basic_type.get_path()?;
+                if !texture_path.is_file() {
+                    return None;
+                }
+                Some(Arc::new(ImageTexture::from_file(&texture_path)))
             }
-            _ => panic!()
+            _ => None,
         }
     }
     fn parse_default() -> Self {
@@ -83,8 +90,10 @@ fn option_to_texture<
 >(
     texture_parse_result: Option<TextureParseResult<T>>,
     m: &M,
-) -> Option<Arc<dyn Texture<T>>> {
-    texture_parse_result.map(|texture_parse_result| texture_parse_result.into_texture(m))
+) -> Result<Option<Arc<dyn Texture<T>>>, String> {
+    texture_parse_result
+        .map(|texture_parse_result| texture_parse_result.into_texture(m))
+        .transpose()
 }
 
 pub fn get_texture<
@@ -94,6 +103,7 @@ pub fn get_texture<
     property_set: &PropertySet,
     name: &str,
     map: &M,
-) -> Option<Arc<dyn Texture<T>>> {
-    option_to_texture(property_set.get_value(name), map)
+) -> SceneResult<Option<Arc<dyn Texture<T>>>> {
+    option_to_texture(property_set.get_value(name)?, map)
+        .map_err(|texture_name| property_set.error(&format!("Unknown texture '{}'", texture_name)))
 }